@@ -18,6 +18,16 @@ gm.register_mod({name="example_mod", version=000_001_000})
 
 gm.registry.pl_registry.register("example_mod/listener", listener)
 
+local function chat_listener(packet, is_inbound, connection_id)
+    if packet.message == "!ping" then
+        gm.send(connection_id, gm.packets.new("SCMessage", {message = "pong"}))
+        return nil
+    end
+    return packet
+end
+
+gm.registry.gpl_registry.register("example_mod/chat_listener", {"CSChat"}, chat_listener)
+
 print("Print available registries")
 for k,_ in gm.registry do
     print(k)
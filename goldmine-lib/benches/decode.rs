@@ -0,0 +1,138 @@
+//! Compares the owned datagram decode path with the borrowed one used by the packet listener.
+//! Run with `cargo bench -p goldmine-lib --bench decode`.
+
+use std::{
+    alloc::{GlobalAlloc, Layout, System},
+    hint::black_box,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Instant,
+};
+
+use declio::{Decode, Encode};
+use goldmine_lib::{
+    codes::BlockFace,
+    game_packets::{Encapsulation, GamePacket},
+    packets::Packet,
+    protocol,
+};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+const ITERATIONS: usize = 100_000;
+
+fn sample_datagram() -> Vec<u8> {
+    let game_packets = [
+        GamePacket::MovePlayer {
+            entity_id: 1,
+            pos_x: 128.5,
+            pos_y: 70.0,
+            pos_z: 128.5,
+            rot_y: 90.0,
+            rot_x: 0.0,
+        },
+        GamePacket::CSRequestChunk {
+            index_x: 3,
+            index_z: 4,
+        },
+        GamePacket::PlaceBlock {
+            entity_id: 1,
+            pos_x: 128,
+            pos_z: 129,
+            pos_y: 64,
+            block_id: 1,
+            block_aux: 0,
+            face: BlockFace::Up,
+        },
+    ];
+    let encapsulated = game_packets
+        .iter()
+        .map(|game_packet| {
+            let mut bytes = Vec::new();
+            game_packet.encode((), &mut bytes).unwrap();
+            Encapsulation::Simple {
+                length: (bytes.len() * 8) as u16,
+                game_packet: bytes,
+            }
+        })
+        .collect();
+    let mut datagram = Vec::new();
+    Packet::Custom {
+        count: 7.into(),
+        encapsulated,
+    }
+    .encode((), &mut datagram)
+    .unwrap();
+    datagram
+}
+
+/// The path before the borrowed decoder: owned encapsulations and a copy per game packet
+fn decode_owned(datagram: &[u8]) -> usize {
+    let mut decoded = 0;
+    if let Packet::Custom { encapsulated, .. } = Packet::decode((), &mut &datagram[..]).unwrap() {
+        for encapsulation in encapsulated {
+            let bytes = encapsulation.to_game_packet();
+            let internal = bytes.to_vec();
+            black_box(GamePacket::decode((), &mut internal.as_slice()).unwrap());
+            decoded += 1;
+        }
+    }
+    decoded
+}
+
+fn decode_borrowed(datagram: &[u8]) -> usize {
+    let mut decoded = 0;
+    if let Some((_, encapsulated)) = Packet::borrow_custom(datagram) {
+        for bytes in encapsulated {
+            let protocol = protocol::default_protocol();
+            black_box(protocol::decode_game_packet(protocol, bytes).unwrap());
+            decoded += 1;
+        }
+    }
+    decoded
+}
+
+fn measure(name: &str, datagram: &[u8], decode: fn(&[u8]) -> usize) -> usize {
+    assert_eq!(decode(datagram), 3);
+    let allocations_before = ALLOCATIONS.load(Ordering::Relaxed);
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        black_box(decode(black_box(datagram)));
+    }
+    let elapsed = start.elapsed();
+    let allocations = (ALLOCATIONS.load(Ordering::Relaxed) - allocations_before) / ITERATIONS;
+    println!(
+        "{:<10} {:>8.1} ns/datagram {:>4} allocations/datagram",
+        name,
+        elapsed.as_nanos() as f64 / ITERATIONS as f64,
+        allocations
+    );
+    allocations
+}
+
+fn main() {
+    let datagram = sample_datagram();
+    let owned = measure("owned", &datagram, decode_owned);
+    let borrowed = measure("borrowed", &datagram, decode_borrowed);
+    assert!(
+        borrowed < owned,
+        "borrowed decode path allocates {} times per datagram, owned {}",
+        borrowed,
+        owned
+    );
+}
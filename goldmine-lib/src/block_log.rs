@@ -0,0 +1,330 @@
+//! The block log: every block a player places or breaks and every container slot they change,
+//! kept in `blocklog.jsonl` in the world directory so griefing can be looked up and rolled back.
+
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{BufWriter, Write},
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::{game_packets::ItemStack, world::BlockPos};
+
+/// Unix time in seconds, the clock of the log
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LogEntry {
+    /// Unix time in seconds
+    pub time: u64,
+    pub player: String,
+    pub pos: BlockPos,
+    pub change: Change,
+    /// Undoes an earlier change, rollbacks never undo these themselves
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub rollback: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Change {
+    /// Id and aux before and after
+    Block {
+        old: (u8, u8),
+        new: (u8, u8),
+        /// Items of a container the change destroyed, restored with it on a rollback
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        contents: Vec<ItemStack>,
+        /// Text of a sign the change destroyed, restored with it on a rollback
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        lines: Option<[String; 4]>,
+    },
+    Slot {
+        slot: u16,
+        old: ItemStack,
+        new: ItemStack,
+    },
+}
+
+impl LogEntry {
+    pub fn new(player: &str, pos: BlockPos, change: Change) -> Self {
+        Self {
+            time: now(),
+            player: player.to_owned(),
+            pos,
+            change,
+            rollback: false,
+        }
+    }
+
+    /// Whether the entry lies within the sphere of `radius` around `center`
+    pub fn is_near(&self, (x, y, z): BlockPos, radius: i32) -> bool {
+        let (dx, dy, dz) = (
+            i64::from(self.pos.0 - x),
+            i64::from(self.pos.1 - y),
+            i64::from(self.pos.2 - z),
+        );
+        dx * dx + dy * dy + dz * dz <= i64::from(radius) * i64::from(radius)
+    }
+}
+
+/// Entries older than the retention are only pruned once they are this much older, so the file
+/// isn't rewritten on every save
+const PRUNE_SLACK: u64 = 24 * 60 * 60;
+
+/// All entries in the order they happened, the ones after `saved` are not on disk yet
+#[derive(Default, Debug)]
+pub struct BlockLog {
+    entries: Vec<LogEntry>,
+    saved: usize,
+    /// Indices of the entries by position
+    by_pos: HashMap<BlockPos, Vec<usize>>,
+    /// The file no longer matches the saved entries and is replaced on the next save
+    rewrite: bool,
+}
+
+impl BlockLog {
+    /// Reads a log written by `save`, one JSON entry per line. Entries that can't be read, like
+    /// the last one after a crash while saving, are skipped with a warning.
+    pub fn load(path: &Path) -> Result<Self> {
+        let mut text = fs::read_to_string(path)?;
+        if !text.is_empty() && !text.ends_with('\n') {
+            // Appending to a cut off line would spoil the next entry as well
+            eprintln!("Dropping the incomplete last entry of {:?}", path);
+            text.truncate(text.rfind('\n').map_or(0, |end| end + 1));
+            fs::write(path, &text).with_context(|| format!("Truncating {:?}", path))?;
+        }
+        let mut log = Self::default();
+        for (index, line) in text.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            match serde_json::from_str(line) {
+                Ok(entry) => log.record(entry),
+                Err(err) => eprintln!("Skipping line {} of {:?}: {}", index + 1, path, err),
+            }
+        }
+        log.saved = log.entries.len();
+        Ok(log)
+    }
+
+    /// Takes the entries recorded since the last save, to be appended to the file. Entries older
+    /// than `retention` seconds are dropped, which rewrites the whole file.
+    pub fn take_unsaved(&mut self, retention: Option<u64>) -> BlockLogSave {
+        if let Some(retention) = retention {
+            let cutoff = now().saturating_sub(retention);
+            if self
+                .entries
+                .first()
+                .is_some_and(|entry| entry.time.saturating_add(PRUNE_SLACK) < cutoff)
+            {
+                self.prune(cutoff);
+                self.rewrite = true;
+            }
+        }
+        let rewrite = std::mem::take(&mut self.rewrite);
+        let unsaved = if rewrite { 0 } else { self.saved };
+        let entries = self.entries[unsaved..].to_vec();
+        self.saved = self.entries.len();
+        BlockLogSave { entries, rewrite }
+    }
+
+    /// Writes the whole file on the next save, after writing the last one failed
+    pub fn rewrite_on_save(&mut self) {
+        self.rewrite = true;
+    }
+
+    pub fn record(&mut self, entry: LogEntry) {
+        self.by_pos
+            .entry(entry.pos)
+            .or_default()
+            .push(self.entries.len());
+        self.entries.push(entry);
+    }
+
+    /// Drops the entries from before `cutoff`
+    fn prune(&mut self, cutoff: u64) {
+        let entries = std::mem::take(&mut self.entries);
+        self.by_pos.clear();
+        for entry in entries.into_iter().filter(|entry| entry.time >= cutoff) {
+            self.record(entry);
+        }
+    }
+
+    /// The changes at a position, oldest first
+    pub fn history(&self, pos: BlockPos) -> Vec<LogEntry> {
+        self.by_pos.get(&pos).map_or_else(Vec::new, |indices| {
+            indices
+                .iter()
+                .map(|index| self.entries[*index].clone())
+                .collect()
+        })
+    }
+
+    /// The changes of a player near `center` since `since`, newest first as they are undone
+    pub fn changes_by(
+        &self,
+        player: &str,
+        center: BlockPos,
+        radius: i32,
+        since: u64,
+    ) -> Vec<LogEntry> {
+        self.entries
+            .iter()
+            .rev()
+            .take_while(|entry| entry.time >= since)
+            .filter(|entry| {
+                entry.player == player && !entry.rollback && entry.is_near(center, radius)
+            })
+            .cloned()
+            .collect()
+    }
+}
+
+/// Entries taken out of the log by `take_unsaved`, to be written without holding on to it
+#[derive(Debug, Default)]
+pub struct BlockLogSave {
+    entries: Vec<LogEntry>,
+    rewrite: bool,
+}
+
+impl BlockLogSave {
+    pub fn write(&self, path: &Path) -> Result<()> {
+        if self.entries.is_empty() && !self.rewrite {
+            return Ok(());
+        }
+        write_entries(path, &self.entries, !self.rewrite)
+    }
+}
+
+/// Writes entries as JSON lines, either appending them or replacing the file through a
+/// temporary one
+fn write_entries(path: &Path, entries: &[LogEntry], append: bool) -> Result<()> {
+    let temp_path = path.with_extension("tmp");
+    let file = if append {
+        OpenOptions::new().create(true).append(true).open(path)?
+    } else {
+        File::create(&temp_path)?
+    };
+    let mut writer = BufWriter::new(file);
+    for entry in entries {
+        serde_json::to_writer(&mut writer, entry)?;
+        writer.write_all(b"\n")?;
+    }
+    writer.into_inner()?.sync_all()?;
+    if !append {
+        fs::rename(temp_path, path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(time: u64, player: &str, pos: BlockPos, rollback: bool) -> LogEntry {
+        LogEntry {
+            time,
+            player: player.to_owned(),
+            pos,
+            change: Change::Block {
+                old: (0, 0),
+                new: (1, 0),
+                contents: Vec::new(),
+                lines: None,
+            },
+            rollback,
+        }
+    }
+
+    fn test_log() -> BlockLog {
+        let mut log = BlockLog::default();
+        log.record(entry(100, "alex", (0, 0, 0), false));
+        log.record(entry(200, "alex", (1, 0, 0), false));
+        log.record(entry(300, "sam", (2, 0, 0), false));
+        log.record(entry(400, "alex", (50, 0, 0), false));
+        log.record(entry(500, "alex", (1, 0, 0), true));
+        log.record(entry(600, "alex", (0, 3, 4), false));
+        log
+    }
+
+    fn times(entries: &[LogEntry]) -> Vec<u64> {
+        entries.iter().map(|entry| entry.time).collect()
+    }
+
+    #[test]
+    fn changes_by_player_newest_first() {
+        let log = test_log();
+        assert_eq!(
+            times(&log.changes_by("alex", (0, 0, 0), 5, 0)),
+            [600, 200, 100]
+        );
+        assert_eq!(times(&log.changes_by("sam", (0, 0, 0), 5, 0)), [300]);
+        assert!(log.changes_by("robin", (0, 0, 0), 5, 0).is_empty());
+    }
+
+    #[test]
+    fn changes_by_within_radius_and_time() {
+        let log = test_log();
+        assert_eq!(times(&log.changes_by("alex", (0, 0, 0), 4, 0)), [200, 100]);
+        assert_eq!(
+            times(&log.changes_by("alex", (0, 0, 0), 100, 0)),
+            [600, 400, 200, 100]
+        );
+        assert_eq!(
+            times(&log.changes_by("alex", (0, 0, 0), 5, 200)),
+            [600, 200]
+        );
+        assert!(log.changes_by("alex", (0, 0, 0), 5, 700).is_empty());
+    }
+
+    #[test]
+    fn history_by_position() {
+        let log = test_log();
+        assert_eq!(times(&log.history((1, 0, 0))), [200, 500]);
+        assert!(log.history((9, 9, 9)).is_empty());
+    }
+
+    #[test]
+    fn unsaved_entries_are_taken_once() {
+        let mut log = test_log();
+        assert_eq!(log.take_unsaved(None).entries.len(), 6);
+        assert!(log.take_unsaved(None).entries.is_empty());
+        log.record(entry(700, "sam", (0, 0, 0), false));
+        log.rewrite_on_save();
+        let save = log.take_unsaved(None);
+        assert!(save.rewrite);
+        assert_eq!(save.entries.len(), 7);
+    }
+
+    #[test]
+    fn sign_text_is_kept() {
+        let lines = ["a".to_owned(), String::new(), "c".to_owned(), String::new()];
+        let removed = LogEntry::new(
+            "alex",
+            (1, 2, 3),
+            Change::Block {
+                old: (63, 4),
+                new: (0, 0),
+                contents: Vec::new(),
+                lines: Some(lines.clone()),
+            },
+        );
+        let json = serde_json::to_string(&removed).unwrap();
+        let read: LogEntry = serde_json::from_str(&json).unwrap();
+        assert!(matches!(read.change, Change::Block { lines: Some(read), .. } if read == lines));
+
+        // Entries written before signs were logged
+        let json = r#"{"time":1,"player":"alex","pos":[1,2,3],"change":{"type":"block","old":[63,4],"new":[0,0]}}"#;
+        let read: LogEntry = serde_json::from_str(json).unwrap();
+        assert!(matches!(read.change, Change::Block { lines: None, .. }));
+    }
+}
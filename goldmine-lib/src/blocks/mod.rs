@@ -1,2 +1,260 @@
-#[derive(Clone, Copy)]
-pub enum Blocks {}
+use anyhow::Result;
+use mlua::{Lua, LuaSerdeExt, Table, Value};
+
+use self::properties::{item, items, BlockProperties, Drops};
+
+pub mod properties;
+
+/// Declares the block ids with their properties, see `BlockProperties` for the builders
+macro_rules! blocks {
+    (
+        $(
+            $(#[$variant_meta:meta])*
+            $variant:ident = $id:literal => $properties:expr,
+        )*
+    ) => {
+        /// Every block id known to the clients
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        #[repr(u8)]
+        pub enum Blocks {
+            $(
+                $(#[$variant_meta])*
+                $variant = $id,
+            )*
+        }
+
+        impl Blocks {
+            pub const ALL: &'static [Blocks] = &[$(Blocks::$variant,)*];
+
+            pub fn from_id(id: u8) -> Option<Self> {
+                match id {
+                    $($id => Some(Self::$variant),)*
+                    _ => None,
+                }
+            }
+
+            pub fn from_name(name: &str) -> Option<Self> {
+                match name {
+                    $(stringify!($variant) => Some(Self::$variant),)*
+                    _ => None,
+                }
+            }
+
+            pub fn name(self) -> &'static str {
+                match self {
+                    $(Self::$variant => stringify!($variant),)*
+                }
+            }
+
+            pub fn properties(self) -> &'static BlockProperties {
+                match self {
+                    $(Self::$variant => {
+                        const PROPERTIES: BlockProperties = $properties;
+                        &PROPERTIES
+                    })*
+                }
+            }
+        }
+    };
+}
+
+blocks! {
+    Air = 0 => BlockProperties::fluid().blast_resistance(0.0),
+    Stone = 1 => BlockProperties::opaque(1.5).blast_resistance(30.0).drops(item(4, 1)),
+    Grass = 2 => BlockProperties::opaque(0.6).drops(item(3, 1)),
+    Dirt = 3 => BlockProperties::opaque(0.5),
+    Cobblestone = 4 => BlockProperties::opaque(2.0).blast_resistance(30.0),
+    Planks = 5 => BlockProperties::opaque(2.0),
+    Sapling = 6 => BlockProperties::plant(0.0).variants(15, 3),
+    Bedrock = 7 => BlockProperties::opaque(-1.0).drops(Drops::Nothing),
+    Water = 8 => BlockProperties::fluid().variants(15, 0).opacity(3),
+    StillWater = 9 => BlockProperties::fluid().variants(15, 0).opacity(3),
+    Lava = 10 => BlockProperties::fluid().variants(15, 0).light(15),
+    StillLava = 11 => BlockProperties::fluid().variants(15, 0).light(15),
+    Sand = 12 => BlockProperties::opaque(0.5),
+    Gravel = 13 => BlockProperties::opaque(0.6),
+    GoldOre = 14 => BlockProperties::opaque(3.0),
+    IronOre = 15 => BlockProperties::opaque(3.0),
+    CoalOre = 16 => BlockProperties::opaque(3.0).drops(item(263, 1)),
+    /// Oak, spruce and birch
+    Log = 17 => BlockProperties::opaque(2.0).variants(15, 3),
+    Leaves = 18 => BlockProperties::see_through(0.2)
+        .opacity(1)
+        .drops(Drops::Chance { id: 6, aux_mask: 3, one_in: 20 })
+        .variants(15, 3),
+    Sponge = 19 => BlockProperties::opaque(0.6),
+    Glass = 20 => BlockProperties::see_through(0.3).drops(Drops::Nothing),
+    /// Drops lapis lazuli, which is blue dye
+    LapisOre = 21 => BlockProperties::opaque(3.0)
+        .drops(Drops::Item { id: 351, aux: 4, aux_mask: 0, min: 4, max: 8 }),
+    LapisBlock = 22 => BlockProperties::opaque(3.0),
+    Sandstone = 24 => BlockProperties::opaque(0.8).variants(2, 3),
+    Bed = 26 => BlockProperties::see_through(0.2).drops(item(355, 1)).variants(15, 0),
+    PoweredRail = 27 => BlockProperties::plant(0.7).variants(15, 0),
+    Cobweb = 30 => BlockProperties::plant(4.0).opacity(1).drops(item(287, 1)),
+    TallGrass = 31 => BlockProperties::plant(0.0)
+        .replaceable()
+        .drops(Drops::Chance { id: 295, aux_mask: 0, one_in: 8 })
+        .variants(2, 0),
+    DeadBush = 32 => BlockProperties::plant(0.0).drops(Drops::Nothing).replaceable(),
+    Wool = 35 => BlockProperties::opaque(0.8).variants(15, 15),
+    Dandelion = 37 => BlockProperties::plant(0.0),
+    Rose = 38 => BlockProperties::plant(0.0),
+    BrownMushroom = 39 => BlockProperties::plant(0.0).light(1),
+    RedMushroom = 40 => BlockProperties::plant(0.0),
+    GoldBlock = 41 => BlockProperties::opaque(3.0).blast_resistance(30.0),
+    IronBlock = 42 => BlockProperties::opaque(5.0).blast_resistance(30.0),
+    DoubleSlab = 43 => BlockProperties::opaque(2.0)
+        .blast_resistance(30.0)
+        .drops(Drops::Item { id: 44, aux: 0, aux_mask: 7, min: 2, max: 2 })
+        .variants(7, 7),
+    /// Aux bit 8 puts the slab in the upper half
+    Slab = 44 => BlockProperties::see_through(2.0).blast_resistance(30.0).variants(15, 7),
+    Bricks = 45 => BlockProperties::opaque(2.0).blast_resistance(30.0),
+    Tnt = 46 => BlockProperties::opaque(0.0),
+    Bookshelf = 47 => BlockProperties::opaque(1.5).drops(item(340, 3)),
+    MossyCobblestone = 48 => BlockProperties::opaque(2.0).blast_resistance(30.0),
+    Obsidian = 49 => BlockProperties::opaque(50.0).blast_resistance(6000.0),
+    Torch = 50 => BlockProperties::plant(0.0).light(14).variants(5, 0),
+    Fire = 51 => BlockProperties::plant(0.0)
+        .light(15)
+        .drops(Drops::Nothing)
+        .variants(15, 0)
+        .replaceable(),
+    WoodenStairs = 53 => BlockProperties::see_through(2.0).variants(7, 0),
+    Chest = 54 => BlockProperties::see_through(2.5).variants(5, 0),
+    DiamondOre = 56 => BlockProperties::opaque(3.0).drops(item(264, 1)),
+    DiamondBlock = 57 => BlockProperties::opaque(5.0).blast_resistance(30.0),
+    CraftingTable = 58 => BlockProperties::opaque(2.5),
+    Crops = 59 => BlockProperties::plant(0.0).drops(item(295, 1)).variants(7, 0),
+    Farmland = 60 => BlockProperties::opaque(0.6).drops(item(3, 1)).variants(7, 0),
+    Furnace = 61 => BlockProperties::opaque(3.5).variants(5, 0),
+    LitFurnace = 62 => BlockProperties::opaque(3.5).light(13).drops(item(61, 1)).variants(5, 0),
+    SignPost = 63 => BlockProperties::plant(1.0).drops(item(323, 1)).variants(15, 0),
+    WoodenDoor = 64 => BlockProperties::see_through(3.0).drops(item(324, 1)).variants(15, 0),
+    Ladder = 65 => BlockProperties::see_through(0.4).variants(5, 0),
+    Rail = 66 => BlockProperties::plant(0.7).variants(9, 0),
+    CobblestoneStairs = 67 => BlockProperties::see_through(2.0)
+        .blast_resistance(30.0)
+        .variants(7, 0),
+    WallSign = 68 => BlockProperties::plant(1.0).drops(item(323, 1)).variants(5, 0),
+    IronDoor = 71 => BlockProperties::see_through(5.0).drops(item(330, 1)).variants(15, 0),
+    RedstoneOre = 73 => BlockProperties::opaque(3.0).drops(items(331, 4, 5)),
+    GlowingRedstoneOre = 74 => BlockProperties::opaque(3.0).light(9).drops(items(331, 4, 5)),
+    SnowLayer = 78 => BlockProperties::plant(0.1)
+        .drops(item(332, 1))
+        .variants(7, 0)
+        .replaceable(),
+    Ice = 79 => BlockProperties::see_through(0.5).opacity(3).drops(Drops::Nothing),
+    SnowBlock = 80 => BlockProperties::opaque(0.2).drops(item(332, 4)),
+    Cactus = 81 => BlockProperties::see_through(0.4).variants(15, 0),
+    Clay = 82 => BlockProperties::opaque(0.6).drops(item(337, 4)),
+    SugarCane = 83 => BlockProperties::plant(0.0).drops(item(338, 1)).variants(15, 0),
+    Fence = 85 => BlockProperties::see_through(2.0),
+    Pumpkin = 86 => BlockProperties::opaque(1.0).variants(3, 0),
+    Netherrack = 87 => BlockProperties::opaque(0.4),
+    Glowstone = 89 => BlockProperties::opaque(0.3).light(15).drops(items(348, 2, 4)),
+    JackOLantern = 91 => BlockProperties::opaque(1.0).light(15).variants(3, 0),
+    Cake = 92 => BlockProperties::see_through(0.5).drops(Drops::Nothing).variants(5, 0),
+    /// Keeps players inside of the world border
+    InvisibleBedrock = 95 => BlockProperties::see_through(-1.0).drops(Drops::Nothing),
+    Trapdoor = 96 => BlockProperties::see_through(3.0).variants(7, 0),
+    StoneBricks = 98 => BlockProperties::opaque(1.5).blast_resistance(30.0).variants(3, 3),
+    IronBars = 101 => BlockProperties::see_through(5.0),
+    GlassPane = 102 => BlockProperties::see_through(0.3).drops(Drops::Nothing),
+    Melon = 103 => BlockProperties::opaque(1.0).drops(items(360, 3, 7)),
+    PumpkinStem = 104 => BlockProperties::plant(0.0).drops(item(361, 1)).variants(7, 0),
+    MelonStem = 105 => BlockProperties::plant(0.0).drops(item(362, 1)).variants(7, 0),
+    FenceGate = 107 => BlockProperties::see_through(2.0).variants(7, 0),
+    BrickStairs = 108 => BlockProperties::see_through(2.0).blast_resistance(30.0).variants(7, 0),
+    StoneBrickStairs = 109 => BlockProperties::see_through(1.5)
+        .blast_resistance(30.0)
+        .variants(7, 0),
+    NetherBrick = 112 => BlockProperties::opaque(2.0).blast_resistance(30.0),
+    NetherBrickStairs = 114 => BlockProperties::see_through(2.0)
+        .blast_resistance(30.0)
+        .variants(7, 0),
+    SandstoneStairs = 128 => BlockProperties::see_through(0.8).variants(7, 0),
+    SpruceStairs = 134 => BlockProperties::see_through(2.0).variants(7, 0),
+    BirchStairs = 135 => BlockProperties::see_through(2.0).variants(7, 0),
+    JungleStairs = 136 => BlockProperties::see_through(2.0).variants(7, 0),
+    CobblestoneWall = 139 => BlockProperties::see_through(2.0)
+        .blast_resistance(30.0)
+        .variants(1, 1),
+    Carrots = 141 => BlockProperties::plant(0.0).drops(item(391, 1)).variants(7, 0),
+    Potatoes = 142 => BlockProperties::plant(0.0).drops(item(392, 1)).variants(7, 0),
+    QuartzBlock = 155 => BlockProperties::opaque(0.8).variants(4, 3),
+    QuartzStairs = 156 => BlockProperties::see_through(0.8).variants(7, 0),
+    HayBale = 170 => BlockProperties::opaque(0.5).variants(11, 0),
+    Carpet = 171 => BlockProperties::see_through(0.1).variants(15, 15),
+    HardenedClay = 172 => BlockProperties::opaque(1.25),
+    CoalBlock = 173 => BlockProperties::opaque(5.0),
+    Beetroot = 244 => BlockProperties::plant(0.0).drops(item(458, 1)).variants(7, 0),
+    Stonecutter = 245 => BlockProperties::opaque(3.5),
+    GlowingObsidian = 246 => BlockProperties::opaque(10.0).blast_resistance(6000.0).light(12),
+    /// Aux 1 while active, 2 once finished
+    NetherReactor = 247 => BlockProperties::opaque(3.0).variants(2, 0),
+    /// Placeholder shown for unknown blocks
+    InfoUpdate = 248 => BlockProperties::opaque(1.0),
+    InfoUpdate2 = 249 => BlockProperties::opaque(1.0),
+    /// Only used for rendering grass in the inventory
+    GrassCarried = 253 => BlockProperties::opaque(0.6).drops(item(3, 1)),
+    /// Only used for rendering leaves in the inventory
+    LeavesCarried = 254 => BlockProperties::see_through(0.2).opacity(1).drops(Drops::Nothing),
+    InfoReserved6 = 255 => BlockProperties::opaque(-1.0).drops(Drops::Nothing),
+}
+
+impl Blocks {
+    pub fn id(self) -> u8 {
+        self as u8
+    }
+}
+
+/// Properties of a block id, `None` for ids the clients don't know
+pub fn block_properties(id: u8) -> Option<&'static BlockProperties> {
+    Blocks::from_id(id).map(Blocks::properties)
+}
+
+fn block_table(lua: &Lua, block: Blocks) -> mlua::Result<Value> {
+    let value = lua.to_value(block.properties())?;
+    if let Value::Table(table) = &value {
+        table.set("id", block.id())?;
+        table.set("name", block.name())?;
+    }
+    Ok(value)
+}
+
+/// Block properties exposed to Lua as `goldmine.blocks`
+pub fn blocks_module(lua: &Lua) -> Result<Table> {
+    let blocks_module = lua.create_table()?;
+
+    let ids = lua.create_table()?;
+    for block in Blocks::ALL {
+        ids.set(block.name(), block.id())?;
+    }
+    blocks_module.set("ids", ids)?;
+
+    let get = lua.create_function(|lua, block: Value| {
+        let block = match block {
+            Value::String(name) => Blocks::from_name(&name.to_str()?),
+            Value::Integer(id) => u8::try_from(id).ok().and_then(Blocks::from_id),
+            Value::Number(id) => Blocks::from_id(id as u8).filter(|block| block.id() as f64 == id),
+            _ => None,
+        };
+        match block {
+            Some(block) => block_table(lua, block),
+            None => Ok(Value::Nil),
+        }
+    })?;
+    blocks_module.set("get", get)?;
+
+    let all = lua.create_function(|lua, ()| {
+        Blocks::ALL
+            .iter()
+            .map(|block| block_table(lua, *block))
+            .collect::<mlua::Result<Vec<Value>>>()
+    })?;
+    blocks_module.set("all", all)?;
+
+    Ok(blocks_module)
+}
@@ -0,0 +1,184 @@
+use rand::Rng;
+use serde::Serialize;
+
+/// What breaking a block leaves behind
+#[derive(Serialize, Clone, Copy, Debug)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Drops {
+    Nothing,
+    /// The block itself, keeping the aux bits in the mask, e.g. the wool colour but not the
+    /// torch facing
+    Itself {
+        aux_mask: u8,
+    },
+    /// Some other item or block, keeping the aux bits in the mask
+    Item {
+        id: u16,
+        aux: u16,
+        aux_mask: u8,
+        min: u8,
+        max: u8,
+    },
+    /// Some other item with a chance of one in `one_in`, keeping the aux bits in the mask
+    Chance {
+        id: u16,
+        aux_mask: u8,
+        one_in: u8,
+    },
+}
+
+/// Static properties of a block id, shared by all aux values
+#[derive(Serialize, Clone, Copy, Debug)]
+pub struct BlockProperties {
+    /// Entities collide with it
+    pub solid: bool,
+    /// Not a full opaque cube, the neighbouring faces are visible through it
+    pub transparent: bool,
+    pub light_emission: u8,
+    /// Light levels lost passing through the block
+    pub light_opacity: u8,
+    /// Time to break it by hand, negative for unbreakable blocks
+    pub hardness: f32,
+    /// Strength against explosions, unbreakable blocks withstand any explosion
+    pub blast_resistance: f32,
+    pub drops: Drops,
+    /// The highest valid aux value
+    pub max_aux: u8,
+    /// Placing a block into it replaces it, like air, liquids or tall grass
+    pub replaceable: bool,
+}
+
+impl BlockProperties {
+    /// An opaque full cube dropping itself
+    pub const fn opaque(hardness: f32) -> Self {
+        Self {
+            solid: true,
+            transparent: false,
+            light_emission: 0,
+            light_opacity: 15,
+            hardness,
+            blast_resistance: hardness * 5.0,
+            drops: Drops::Itself { aux_mask: 0 },
+            max_aux: 0,
+            replaceable: false,
+        }
+    }
+
+    /// A solid block that isn't a full opaque cube, like glass or stairs
+    pub const fn see_through(hardness: f32) -> Self {
+        Self {
+            transparent: true,
+            light_opacity: 0,
+            ..Self::opaque(hardness)
+        }
+    }
+
+    /// A block without collision, like flowers or torches
+    pub const fn plant(hardness: f32) -> Self {
+        Self {
+            solid: false,
+            ..Self::see_through(hardness)
+        }
+    }
+
+    /// A liquid or gas that can't be broken
+    pub const fn fluid() -> Self {
+        Self {
+            blast_resistance: 500.0,
+            drops: Drops::Nothing,
+            replaceable: true,
+            ..Self::plant(-1.0)
+        }
+    }
+
+    pub const fn replaceable(mut self) -> Self {
+        self.replaceable = true;
+        self
+    }
+
+    pub const fn light(mut self, emission: u8) -> Self {
+        self.light_emission = emission;
+        self
+    }
+
+    pub const fn opacity(mut self, opacity: u8) -> Self {
+        self.light_opacity = opacity;
+        self
+    }
+
+    pub const fn blast_resistance(mut self, resistance: f32) -> Self {
+        self.blast_resistance = resistance;
+        self
+    }
+
+    pub const fn drops(mut self, drops: Drops) -> Self {
+        self.drops = drops;
+        self
+    }
+
+    /// Valid aux values up to `max_aux`, dropping the block with the aux bits in the mask
+    pub const fn variants(mut self, max_aux: u8, aux_mask: u8) -> Self {
+        self.max_aux = max_aux;
+        if let Drops::Itself { .. } = self.drops {
+            self.drops = Drops::Itself { aux_mask };
+        }
+        self
+    }
+
+    pub fn is_breakable(&self) -> bool {
+        self.hardness >= 0.0
+    }
+
+    pub fn is_valid_aux(&self, aux: u8) -> bool {
+        aux <= self.max_aux
+    }
+}
+
+/// `count` of some other item
+pub const fn item(id: u16, count: u8) -> Drops {
+    Drops::Item {
+        id,
+        aux: 0,
+        aux_mask: 0,
+        min: count,
+        max: count,
+    }
+}
+
+/// Between `min` and `max` of some other item
+pub const fn items(id: u16, min: u8, max: u8) -> Drops {
+    Drops::Item {
+        id,
+        aux: 0,
+        aux_mask: 0,
+        min,
+        max,
+    }
+}
+
+impl Drops {
+    /// Rolls the item id, aux and count dropped by breaking the block `id` with `aux`
+    pub fn roll(&self, id: u8, aux: u8, rng: &mut impl Rng) -> Option<(u16, u16, u8)> {
+        match *self {
+            Drops::Nothing => None,
+            Drops::Itself { aux_mask } => Some((id.into(), (aux & aux_mask).into(), 1)),
+            Drops::Item {
+                id,
+                aux: item_aux,
+                aux_mask,
+                min,
+                max,
+            } => {
+                let count = rng.gen_range(min..=max);
+                (count > 0).then_some((id, item_aux | u16::from(aux & aux_mask), count))
+            }
+            Drops::Chance {
+                id,
+                aux_mask,
+                one_in,
+            } => rng
+                .gen_ratio(1, one_in.into())
+                .then_some((id, (aux & aux_mask).into(), 1)),
+        }
+    }
+}
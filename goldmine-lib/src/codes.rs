@@ -0,0 +1,364 @@
+use std::fmt;
+
+use anyhow::Result;
+use declio::{ctx::Endian, Decode, Encode};
+use mlua::{Lua, Table};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// Declares a wire code enum with an `Unknown` fallback, so decoding never fails on codes we
+/// haven't reverse engineered yet. Known codes (de)serialize by name, unknown ones as numbers.
+macro_rules! code_enum {
+    (
+        $(#[$meta:meta])*
+        pub enum $name:ident: $repr:ty {
+            $(
+                $(#[$variant_meta:meta])*
+                $variant:ident = $value:literal,
+            )*
+        }
+    ) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $(
+                $(#[$variant_meta])*
+                $variant,
+            )*
+            /// A code that is not (yet) known to goldmine
+            Unknown($repr),
+        }
+
+        impl $name {
+            pub const NAMES: &'static [(&'static str, $repr)] = &[$((stringify!($variant), $value),)*];
+
+            pub fn name(&self) -> Option<&'static str> {
+                match self {
+                    $(Self::$variant => Some(stringify!($variant)),)*
+                    Self::Unknown(_) => None,
+                }
+            }
+
+            pub fn from_name(name: &str) -> Option<Self> {
+                match name {
+                    $(stringify!($variant) => Some(Self::$variant),)*
+                    _ => None,
+                }
+            }
+        }
+
+        impl From<$repr> for $name {
+            fn from(value: $repr) -> Self {
+                match value {
+                    $($value => Self::$variant,)*
+                    other => Self::Unknown(other),
+                }
+            }
+        }
+
+        impl From<$name> for $repr {
+            fn from(value: $name) -> Self {
+                match value {
+                    $($name::$variant => $value,)*
+                    $name::Unknown(other) => other,
+                }
+            }
+        }
+
+        impl Encode<Endian> for $name {
+            fn encode<W>(&self, ctx: Endian, writer: &mut W) -> Result<(), declio::Error>
+            where
+                W: std::io::Write,
+            {
+                <$repr>::from(*self).encode(ctx, writer)
+            }
+        }
+
+        impl Decode<Endian> for $name {
+            fn decode<R>(ctx: Endian, reader: &mut R) -> Result<Self, declio::Error>
+            where
+                R: std::io::Read,
+            {
+                Ok(<$repr>::decode(ctx, reader)?.into())
+            }
+        }
+
+        impl Serialize for $name {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                match self.name() {
+                    Some(name) => serializer.serialize_str(name),
+                    None => <$repr>::from(*self).serialize(serializer),
+                }
+            }
+        }
+
+        impl<'de> Deserialize<'de> for $name {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let code = deserializer.deserialize_any(CodeVisitor(stringify!($name)))?;
+                match code {
+                    Code::Name(name) => Self::from_name(&name).ok_or_else(|| {
+                        de::Error::custom(format!("Unknown {} {}", stringify!($name), name))
+                    }),
+                    Code::Number(number) => <$repr>::try_from(number)
+                        .map(Self::from)
+                        .map_err(de::Error::custom),
+                }
+            }
+        }
+    };
+}
+
+code_enum! {
+    /// `Interact::action`
+    pub enum InteractAction: u8 {
+        /// Right click / long tap on an entity
+        Interact = 1,
+        /// Left click / short tap on an entity
+        Attack = 2,
+    }
+}
+
+code_enum! {
+    /// `EntityEvent::event`
+    pub enum EntityEventKind: u8 {
+        HurtAnimation = 2,
+        DeathAnimation = 3,
+        TameFail = 6,
+        TameSuccess = 7,
+        ShakeWet = 8,
+        /// Sent by the client when it finished eating or drinking
+        UseItem = 9,
+        EatGrassAnimation = 10,
+    }
+}
+
+code_enum! {
+    /// `CSPlayerAction::action`
+    pub enum PlayerActionKind: u32 {
+        StartBreak = 0,
+        AbortBreak = 1,
+        StopBreak = 2,
+        /// Releasing a charged item, e.g. shooting a bow
+        ReleaseItem = 5,
+        StopSleeping = 6,
+        Respawn = 7,
+    }
+}
+
+code_enum! {
+    /// `Animate::action`
+    pub enum AnimateAction: u8 {
+        SwingArm = 1,
+        WakeUp = 3,
+    }
+}
+
+code_enum! {
+    /// `SCLevelEvent::event_id`
+    pub enum LevelEventKind: u32 {
+        SoundClick = 1000,
+        SoundClickFail = 1001,
+        SoundShoot = 1002,
+        SoundDoor = 1003,
+        SoundFizz = 1004,
+        SoundGhastWarning = 1007,
+        SoundGhastShoot = 1008,
+        SoundZombieWoodenDoor = 1010,
+        SoundZombieDoorCrash = 1012,
+        ParticleShoot = 2000,
+        ParticleDestroy = 2001,
+        ParticleSplash = 2002,
+        ParticleEyeDespawn = 2003,
+        ParticleSpawn = 2004,
+        StartRain = 3001,
+        StartThunder = 3002,
+        StopRain = 3003,
+        StopThunder = 3004,
+        SetData = 4000,
+        PlayersSleeping = 9800,
+    }
+}
+
+code_enum! {
+    /// The `face` fields of block interactions
+    pub enum BlockFace: u32 {
+        /// -Y
+        Down = 0,
+        /// +Y
+        Up = 1,
+        /// -Z
+        North = 2,
+        /// +Z
+        South = 3,
+        /// -X
+        West = 4,
+        /// +X
+        East = 5,
+        /// Used when the client interacts with the air instead of a block
+        NoFace = 255,
+    }
+}
+
+impl BlockFace {
+    /// The offset of the block adjacent to this face, `None` if this is not a real face.
+    pub fn offset(&self) -> Option<(i32, i32, i32)> {
+        match self {
+            BlockFace::Down => Some((0, -1, 0)),
+            BlockFace::Up => Some((0, 1, 0)),
+            BlockFace::North => Some((0, 0, -1)),
+            BlockFace::South => Some((0, 0, 1)),
+            BlockFace::West => Some((-1, 0, 0)),
+            BlockFace::East => Some((1, 0, 0)),
+            _ => None,
+        }
+    }
+}
+
+/// Some packets send the face as an u8 instead of an u32
+pub mod narrow_face {
+    use declio::{ctx::Endian, Decode, Encode};
+
+    use super::BlockFace;
+
+    pub fn encode<W>(face: &BlockFace, ctx: Endian, writer: &mut W) -> Result<(), declio::Error>
+    where
+        W: std::io::Write,
+    {
+        u8::try_from(u32::from(*face))
+            .map_err(|_| declio::Error::new(format!("{:?} doesn't fit into a byte", face)))?
+            .encode(ctx, writer)
+    }
+
+    pub fn decode<R>(ctx: Endian, reader: &mut R) -> Result<BlockFace, declio::Error>
+    where
+        R: std::io::Read,
+    {
+        Ok(BlockFace::from(u32::from(u8::decode(ctx, reader)?)))
+    }
+}
+
+enum Code {
+    Name(String),
+    Number(i64),
+}
+
+struct CodeVisitor(&'static str);
+
+impl de::Visitor<'_> for CodeVisitor {
+    type Value = Code;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "a {} name or number", self.0)
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Self::Value, E> {
+        Ok(Code::Name(v.to_owned()))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Self::Value, E> {
+        Ok(Code::Number(v))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Self::Value, E> {
+        i64::try_from(v).map(Code::Number).map_err(E::custom)
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Self::Value, E> {
+        if v.fract() == 0.0 {
+            Ok(Code::Number(v as i64))
+        } else {
+            Err(E::custom(format!("{} is not a valid {}", v, self.0)))
+        }
+    }
+}
+
+fn names_table<R: Copy + mlua::IntoLua>(lua: &Lua, names: &[(&str, R)]) -> Result<Table> {
+    let table = lua.create_table()?;
+    for (name, value) in names {
+        table.set(*name, *value)?;
+    }
+    Ok(table)
+}
+
+/// Name to number tables for all codes, exposed to Lua as `goldmine.codes`
+pub fn codes_module(lua: &Lua) -> Result<Table> {
+    let codes_module = lua.create_table()?;
+
+    codes_module.set("InteractAction", names_table(lua, InteractAction::NAMES)?)?;
+    codes_module.set("EntityEventKind", names_table(lua, EntityEventKind::NAMES)?)?;
+    codes_module.set(
+        "PlayerActionKind",
+        names_table(lua, PlayerActionKind::NAMES)?,
+    )?;
+    codes_module.set("AnimateAction", names_table(lua, AnimateAction::NAMES)?)?;
+    codes_module.set("LevelEventKind", names_table(lua, LevelEventKind::NAMES)?)?;
+    codes_module.set("BlockFace", names_table(lua, BlockFace::NAMES)?)?;
+
+    Ok(codes_module)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip<T: Encode<Endian> + Decode<Endian>>(value: &T) -> (Vec<u8>, T) {
+        let mut bytes = Vec::new();
+        value.encode(Endian::Big, &mut bytes).unwrap();
+        let decoded = T::decode(Endian::Big, &mut bytes.as_slice()).unwrap();
+        (bytes, decoded)
+    }
+
+    #[test]
+    fn known_codes_round_trip() {
+        assert_eq!(
+            round_trip(&InteractAction::Attack),
+            (vec![2], InteractAction::Attack)
+        );
+        assert_eq!(
+            round_trip(&LevelEventKind::StartRain),
+            (3001u32.to_be_bytes().to_vec(), LevelEventKind::StartRain)
+        );
+        assert_eq!(BlockFace::from(5), BlockFace::East);
+        assert_eq!(u32::from(BlockFace::NoFace), 255);
+    }
+
+    #[test]
+    fn unknown_codes_round_trip() {
+        assert_eq!(
+            round_trip(&InteractAction::from(9)),
+            (vec![9], InteractAction::Unknown(9))
+        );
+        let face = BlockFace::from(0x1234);
+        assert_eq!(face, BlockFace::Unknown(0x1234));
+        assert_eq!(round_trip(&face), (0x1234u32.to_be_bytes().to_vec(), face));
+    }
+
+    #[test]
+    fn narrow_faces() {
+        let mut bytes = Vec::new();
+        narrow_face::encode(&BlockFace::Up, Endian::Big, &mut bytes).unwrap();
+        narrow_face::encode(&BlockFace::Unknown(7), Endian::Big, &mut bytes).unwrap();
+        assert_eq!(bytes, [1, 7]);
+        let mut reader = bytes.as_slice();
+        assert_eq!(
+            narrow_face::decode(Endian::Big, &mut reader).unwrap(),
+            BlockFace::Up
+        );
+        assert_eq!(
+            narrow_face::decode(Endian::Big, &mut reader).unwrap(),
+            BlockFace::Unknown(7)
+        );
+        assert!(
+            narrow_face::encode(&BlockFace::Unknown(256), Endian::Big, &mut Vec::new()).is_err()
+        );
+    }
+
+    #[test]
+    fn codes_serialize_by_name_or_number() {
+        let json = serde_json::to_string(&[BlockFace::North, BlockFace::Unknown(300)]).unwrap();
+        assert_eq!(json, r#"["North",300]"#);
+        let faces: Vec<BlockFace> = serde_json::from_str(&json).unwrap();
+        assert_eq!(faces, [BlockFace::North, BlockFace::Unknown(300)]);
+        assert!(serde_json::from_str::<BlockFace>(r#""Sideways""#).is_err());
+        assert!(serde_json::from_str::<InteractAction>("256").is_err());
+    }
+}
@@ -0,0 +1,126 @@
+use anyhow::{bail, Result};
+
+use crate::{blocks::Blocks, Server};
+
+use super::{require_op, spawn::target_pos, UsageError};
+
+/// A block given as its id or name, like `1` or `stone`, optionally followed by `:aux`
+fn parse_block(arg: &str) -> Result<(u8, Option<u8>)> {
+    let (name, aux) = match arg.split_once(':') {
+        Some((name, aux)) => {
+            let Ok(aux) = aux.parse() else {
+                bail!("{} is not an aux value", aux);
+            };
+            (name, Some(aux))
+        }
+        None => (arg, None),
+    };
+    let block = match name.parse() {
+        Ok(id) => Blocks::from_id(id),
+        Err(_) => Blocks::ALL
+            .iter()
+            .copied()
+            .find(|block| block.name().eq_ignore_ascii_case(&name.replace('_', ""))),
+    };
+    let Some(block) = block else {
+        bail!("There is no block {}", name);
+    };
+    if aux.is_some_and(|aux| !block.properties().is_valid_aux(aux)) {
+        bail!("Invalid aux value for {}", block.name());
+    }
+    Ok((block.id(), aux))
+}
+
+pub fn pos1(server: &Server, connection_id: u64, args: &[&str]) -> Result<String> {
+    select_corner(server, connection_id, 0, args)
+}
+
+pub fn pos2(server: &Server, connection_id: u64, args: &[&str]) -> Result<String> {
+    select_corner(server, connection_id, 1, args)
+}
+
+fn select_corner(
+    server: &Server,
+    connection_id: u64,
+    corner: usize,
+    args: &[&str],
+) -> Result<String> {
+    require_op(server, connection_id)?;
+    let (x, y, z) = target_pos(server, connection_id, args)?;
+    server.set_selection_corner(connection_id, corner, (x, y, z));
+    let selected = match server.get_selection(connection_id) {
+        Some(cuboid) => format!(", {} blocks selected", cuboid.volume()),
+        None => String::new(),
+    };
+    Ok(format!(
+        "Set corner {} to {} {} {}{}",
+        corner + 1,
+        x,
+        y,
+        z,
+        selected
+    ))
+}
+
+pub fn set(server: &Server, connection_id: u64, args: &[&str]) -> Result<String> {
+    require_op(server, connection_id)?;
+    let [block] = args else {
+        return Err(UsageError.into());
+    };
+    let (id, aux) = parse_block(block)?;
+    let changed = server.fill_selection(connection_id, (id, aux.unwrap_or(0)), None)?;
+    Ok(format!("Changed {} blocks", changed))
+}
+
+pub fn replace(server: &Server, connection_id: u64, args: &[&str]) -> Result<String> {
+    require_op(server, connection_id)?;
+    let [from, to] = args else {
+        return Err(UsageError.into());
+    };
+    let from = parse_block(from)?;
+    let (id, aux) = parse_block(to)?;
+    let changed = server.fill_selection(connection_id, (id, aux.unwrap_or(0)), Some(from))?;
+    Ok(format!("Replaced {} blocks", changed))
+}
+
+pub fn copy(server: &Server, connection_id: u64, args: &[&str]) -> Result<String> {
+    require_op(server, connection_id)?;
+    if !args.is_empty() {
+        return Err(UsageError.into());
+    }
+    let copied = server.copy_selection(connection_id)?;
+    Ok(format!("Copied {} blocks", copied))
+}
+
+pub fn paste(server: &Server, connection_id: u64, args: &[&str]) -> Result<String> {
+    require_op(server, connection_id)?;
+    if !args.is_empty() {
+        return Err(UsageError.into());
+    }
+    let changed = server.paste_clipboard(connection_id)?;
+    Ok(format!("Pasted, {} blocks changed", changed))
+}
+
+pub fn rotate(server: &Server, connection_id: u64, args: &[&str]) -> Result<String> {
+    require_op(server, connection_id)?;
+    let quarter_turns = match args {
+        ["90"] => 1,
+        ["180"] => 2,
+        ["270"] => 3,
+        _ => return Err(UsageError.into()),
+    };
+    server.rotate_clipboard(connection_id, quarter_turns)?;
+    Ok(format!(
+        "Rotated the clipboard by {} degrees",
+        quarter_turns * 90
+    ))
+}
+
+pub fn undo(server: &Server, connection_id: u64, args: &[&str]) -> Result<String> {
+    require_op(server, connection_id)?;
+    if !args.is_empty() {
+        return Err(UsageError.into());
+    }
+    let restored = server.undo_edit(connection_id)?;
+    Ok(format!("Undid the last edit, {} blocks restored", restored))
+}
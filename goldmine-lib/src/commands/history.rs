@@ -0,0 +1,102 @@
+use anyhow::{bail, Context, Result};
+
+use crate::{
+    block_log::{self, Change, LogEntry},
+    blocks::Blocks,
+    game_packets::ItemStack,
+    Server,
+};
+
+use super::{require_op, spawn::target_pos, UsageError};
+
+/// Entries shown by `/history`, the newest ones
+const MAX_HISTORY_LINES: usize = 10;
+const MAX_ROLLBACK_RADIUS: i32 = 64;
+
+fn block_name((id, aux): (u8, u8)) -> String {
+    match Blocks::from_id(id) {
+        Some(block) if aux == 0 => block.name().to_owned(),
+        Some(block) => format!("{}:{}", block.name(), aux),
+        None => format!("{}:{}", id, aux),
+    }
+}
+
+fn item_name(item: ItemStack) -> String {
+    if item.is_empty() {
+        "nothing".to_owned()
+    } else {
+        format!("{}x {}:{}", item.count, item.id, item.aux)
+    }
+}
+
+fn age(seconds: u64) -> String {
+    match seconds {
+        0..=59 => format!("{}s", seconds),
+        60..=3599 => format!("{}m", seconds / 60),
+        3600..=86399 => format!("{}h", seconds / 3600),
+        _ => format!("{}d", seconds / 86400),
+    }
+}
+
+fn describe(entry: &LogEntry, now: u64) -> String {
+    let change = match &entry.change {
+        Change::Block { old, new, .. } => format!("{} -> {}", block_name(*old), block_name(*new)),
+        Change::Slot { slot, old, new } => {
+            format!("slot {}: {} -> {}", slot, item_name(*old), item_name(*new))
+        }
+    };
+    format!(
+        "{} ago {}{}: {}",
+        age(now.saturating_sub(entry.time)),
+        entry.player,
+        if entry.rollback { " (rollback)" } else { "" },
+        change
+    )
+}
+
+pub fn history(server: &Server, connection_id: u64, args: &[&str]) -> Result<String> {
+    require_op(server, connection_id)?;
+    let (x, y, z) = target_pos(server, connection_id, args)?;
+    let history = server.block_history((x, y, z));
+    if history.is_empty() {
+        return Ok(format!("No changes at {} {} {}", x, y, z));
+    }
+    let now = block_log::now();
+    let shown = &history[history.len().saturating_sub(MAX_HISTORY_LINES)..];
+    let mut lines = vec![format!(
+        "Changes at {} {} {}, {} in total:",
+        x,
+        y,
+        z,
+        history.len()
+    )];
+    lines.extend(shown.iter().map(|entry| describe(entry, now)));
+    Ok(lines.join("\n"))
+}
+
+pub fn rollback(server: &Server, connection_id: u64, args: &[&str]) -> Result<String> {
+    let username = require_op(server, connection_id)?;
+    let [player, radius, minutes] = args else {
+        return Err(UsageError.into());
+    };
+    let (Ok(radius), Ok(minutes)) = (radius.parse::<i32>(), minutes.parse::<u64>()) else {
+        return Err(UsageError.into());
+    };
+    if !(0..=MAX_ROLLBACK_RADIUS).contains(&radius) {
+        bail!("The radius is 0 to {} blocks", MAX_ROLLBACK_RADIUS);
+    }
+    let center = server
+        .get_player_block_pos(connection_id)
+        .context("You have no position yet")?;
+    let undone = server.rollback(
+        &username,
+        player,
+        center,
+        radius,
+        minutes.saturating_mul(60),
+    );
+    Ok(format!(
+        "Rolled back {} changes of {} within {} blocks",
+        undone, player, radius
+    ))
+}
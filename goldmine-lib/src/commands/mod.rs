@@ -0,0 +1,105 @@
+//! Chat commands, messages starting with a slash
+
+use anyhow::{bail, Context, Result};
+
+use crate::Server;
+
+mod edit;
+mod history;
+mod physics;
+mod region;
+mod spawn;
+mod time;
+
+type Command = fn(&Server, u64, &[&str]) -> Result<String>;
+
+/// Name, usage and handler of every command
+const COMMANDS: &[(&str, &str, Command)] = &[
+    ("copy", "/copy", edit::copy),
+    ("help", "/help", help),
+    ("history", "/history [x y z]", history::history),
+    ("paste", "/paste", edit::paste),
+    ("physics", "/physics [<rule> <on|off>]", physics::physics),
+    ("pos1", "/pos1 [x y z]", edit::pos1),
+    ("pos2", "/pos2 [x y z]", edit::pos2),
+    (
+        "region",
+        "/region <list|info [name]|create <name> <x1 y1 z1 x2 y2 z2>|delete <name>|<setowner|addmember|removemember> <name> <player>>",
+        region::region,
+    ),
+    ("replace", "/replace <block> <block>", edit::replace),
+    (
+        "rollback",
+        "/rollback <player> <radius> <minutes>",
+        history::rollback,
+    ),
+    ("rotate", "/rotate <90|180|270>", edit::rotate),
+    ("set", "/set <block>", edit::set),
+    (
+        "setworldspawn",
+        "/setworldspawn [x y z]",
+        spawn::set_world_spawn,
+    ),
+    (
+        "spawnpoint",
+        "/spawnpoint [x y z|reset]",
+        spawn::spawn_point,
+    ),
+    ("tickspeed", "/tickspeed [speed]", physics::tick_speed),
+    (
+        "time",
+        "/time <query|set <time>|add <ticks>|freeze|unfreeze>",
+        time::time,
+    ),
+    ("undo", "/undo", edit::undo),
+];
+
+/// Runs a command line without the leading slash, returning the reply for the sender
+pub fn execute(server: &Server, connection_id: u64, line: &str) -> Result<String> {
+    let mut words = line.split_whitespace();
+    let Some(name) = words.next() else {
+        bail!("Type /help for a list of commands");
+    };
+    let args: Vec<&str> = words.collect();
+    let Some((_, usage, command)) = COMMANDS
+        .iter()
+        .find(|(command_name, _, _)| command_name.eq_ignore_ascii_case(name))
+    else {
+        bail!(
+            "Unknown command /{}, type /help for a list of commands",
+            name
+        );
+    };
+    match command(server, connection_id, &args) {
+        Err(err) if err.is::<UsageError>() => bail!("Usage: {}", usage),
+        result => result,
+    }
+}
+
+/// Wrong arguments, answered with the usage of the command
+#[derive(Debug)]
+pub struct UsageError;
+
+impl std::fmt::Display for UsageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Invalid command arguments")
+    }
+}
+
+impl std::error::Error for UsageError {}
+
+/// Fails unless the sender is an op, returns their username
+fn require_op(server: &Server, connection_id: u64) -> Result<String> {
+    let username = server
+        .get_username(connection_id)
+        .context("You are not logged in")?;
+    if !server.is_op(&username) {
+        bail!("Only ops can do that");
+    }
+    Ok(username)
+}
+
+fn help(_server: &Server, _connection_id: u64, _args: &[&str]) -> Result<String> {
+    let usages: Vec<&str> = COMMANDS.iter().map(|(_, usage, _)| *usage).collect();
+    Ok(usages.join("\n"))
+}
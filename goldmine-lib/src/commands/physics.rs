@@ -0,0 +1,56 @@
+use anyhow::{bail, Result};
+
+use crate::{world::physics::PhysicsRules, Server};
+
+use super::{require_op, UsageError};
+
+pub fn physics(server: &Server, connection_id: u64, args: &[&str]) -> Result<String> {
+    match args {
+        [] => {
+            let mut rules = server.get_physics_rules();
+            let states: Vec<String> = PhysicsRules::NAMES
+                .iter()
+                .map(|name| {
+                    let enabled = rules.get_mut(name).is_some_and(|rule| *rule);
+                    format!("{} {}", name, if enabled { "on" } else { "off" })
+                })
+                .collect();
+            Ok(states.join(", "))
+        }
+        [name, state] => {
+            require_op(server, connection_id)?;
+            let enabled = match *state {
+                "on" => true,
+                "off" => false,
+                _ => return Err(UsageError.into()),
+            };
+            if !server.set_physics_rule(name, enabled) {
+                bail!(
+                    "Unknown rule {}, the rules are {}",
+                    name,
+                    PhysicsRules::NAMES.join(", ")
+                );
+            }
+            Ok(format!("Turned {} {}", name, state))
+        }
+        _ => Err(UsageError.into()),
+    }
+}
+
+pub fn tick_speed(server: &Server, connection_id: u64, args: &[&str]) -> Result<String> {
+    match args {
+        [] => Ok(format!(
+            "The random tick speed is {}",
+            server.get_random_tick_speed()
+        )),
+        [speed] => {
+            require_op(server, connection_id)?;
+            let Ok(speed) = speed.parse() else {
+                bail!("{} is not a number", speed);
+            };
+            server.set_random_tick_speed(speed)?;
+            Ok(format!("Set the random tick speed to {}", speed))
+        }
+        _ => Err(UsageError.into()),
+    }
+}
@@ -0,0 +1,125 @@
+use anyhow::{bail, Context, Result};
+
+use crate::{
+    regions::Region,
+    world::{BlockPos, World},
+    Server,
+};
+
+use super::{require_op, UsageError};
+
+fn parse_pos(x: &str, y: &str, z: &str) -> Result<BlockPos> {
+    let (Ok(x), Ok(y), Ok(z)) = (x.parse(), y.parse(), z.parse()) else {
+        bail!("{} {} {} is not a block position", x, y, z);
+    };
+    if !World::in_bounds((x, y, z)) {
+        bail!("{} {} {} is outside of the world", x, y, z);
+    }
+    Ok((x, y, z))
+}
+
+fn describe(region: &Region) -> String {
+    let (x1, y1, z1) = region.min;
+    let (x2, y2, z2) = region.max;
+    let members: Vec<&str> = region.members.iter().map(String::as_str).collect();
+    format!(
+        "{}: {} {} {} to {} {} {}, owner {}, members: {}",
+        region.name,
+        x1,
+        y1,
+        z1,
+        x2,
+        y2,
+        z2,
+        region.owner,
+        if members.is_empty() {
+            "none".to_owned()
+        } else {
+            members.join(", ")
+        }
+    )
+}
+
+pub fn region(server: &Server, connection_id: u64, args: &[&str]) -> Result<String> {
+    let username = server
+        .get_username(connection_id)
+        .context("You are not logged in")?;
+    let is_op = server.is_op(&username);
+    // Owners manage their own members
+    let require_owner = |name: &str| -> Result<()> {
+        let region = server
+            .get_region(name)
+            .with_context(|| format!("There is no region {}", name))?;
+        if !is_op && region.owner != username {
+            bail!("Only the owner of {} and ops can do that", name);
+        }
+        Ok(())
+    };
+
+    match args {
+        ["list"] => {
+            let names: Vec<String> = server
+                .get_regions()
+                .into_iter()
+                .map(|region| region.name)
+                .collect();
+            if names.is_empty() {
+                return Ok("There are no regions".to_owned());
+            }
+            Ok(format!("Regions: {}", names.join(", ")))
+        }
+        ["info"] => {
+            let pos = server
+                .get_player_block_pos(connection_id)
+                .context("You have no position yet")?;
+            let regions = server.regions_at(pos);
+            if regions.is_empty() {
+                return Ok("You are not in a region".to_owned());
+            }
+            let descriptions: Vec<String> = regions.iter().map(describe).collect();
+            Ok(descriptions.join("\n"))
+        }
+        ["info", name] => {
+            let region = server
+                .get_region(name)
+                .with_context(|| format!("There is no region {}", name))?;
+            Ok(describe(&region))
+        }
+        ["create", name, x1, y1, z1, x2, y2, z2] => {
+            require_op(server, connection_id)?;
+            let first = parse_pos(x1, y1, z1)?;
+            let second = parse_pos(x2, y2, z2)?;
+            server.create_region(Region::new(name, first, second, &username))?;
+            Ok(format!("Created region {}", name))
+        }
+        ["delete", name] => {
+            require_op(server, connection_id)?;
+            if !server.remove_region(name) {
+                bail!("There is no region {}", name);
+            }
+            Ok(format!("Deleted region {}", name))
+        }
+        ["setowner", name, owner] => {
+            require_op(server, connection_id)?;
+            server.update_region(name, |region| region.owner = (*owner).to_owned())?;
+            Ok(format!("{} now owns region {}", owner, name))
+        }
+        ["addmember", name, member] => {
+            require_owner(name)?;
+            server.update_region(name, |region| {
+                region.members.insert((*member).to_owned());
+            })?;
+            Ok(format!("Added {} to region {}", member, name))
+        }
+        ["removemember", name, member] => {
+            require_owner(name)?;
+            let mut removed = false;
+            server.update_region(name, |region| removed = region.members.remove(*member))?;
+            if !removed {
+                bail!("{} is not a member of region {}", member, name);
+            }
+            Ok(format!("Removed {} from region {}", member, name))
+        }
+        _ => Err(UsageError.into()),
+    }
+}
@@ -0,0 +1,53 @@
+use anyhow::{bail, Context, Result};
+
+use crate::{
+    world::{BlockPos, World},
+    Server,
+};
+
+use super::{require_op, UsageError};
+
+/// The position given as arguments, or the position of the player without arguments
+pub(super) fn target_pos(server: &Server, connection_id: u64, args: &[&str]) -> Result<BlockPos> {
+    let pos = match args {
+        [] => server
+            .get_player_block_pos(connection_id)
+            .context("You have no position yet")?,
+        [x, y, z] => {
+            let (Ok(x), Ok(y), Ok(z)) = (x.parse(), y.parse(), z.parse()) else {
+                bail!("{} {} {} is not a block position", x, y, z);
+            };
+            (x, y, z)
+        }
+        _ => return Err(UsageError.into()),
+    };
+    if !World::in_bounds(pos) {
+        bail!("{} {} {} is outside of the world", pos.0, pos.1, pos.2);
+    }
+    Ok(pos)
+}
+
+pub fn set_world_spawn(server: &Server, connection_id: u64, args: &[&str]) -> Result<String> {
+    require_op(server, connection_id)?;
+    let (x, y, z) = target_pos(server, connection_id, args)?;
+    server.set_spawn((x, y, z));
+    Ok(format!("Set the world spawn to {} {} {}", x, y, z))
+}
+
+pub fn spawn_point(server: &Server, connection_id: u64, args: &[&str]) -> Result<String> {
+    let username = server
+        .get_username(connection_id)
+        .context("You are not logged in")?;
+    if let ["reset"] = args {
+        server.set_player_spawn(&username, None);
+        server.send_game_packet(
+            connection_id,
+            Server::spawn_packet(server.get_player_spawn(&username)),
+        );
+        return Ok("Your spawn point is the world spawn again".to_owned());
+    }
+    let (x, y, z) = target_pos(server, connection_id, args)?;
+    server.set_player_spawn(&username, Some((x, y, z)));
+    server.send_game_packet(connection_id, Server::spawn_packet((x, y, z)));
+    Ok(format!("Set your spawn point to {} {} {}", x, y, z))
+}
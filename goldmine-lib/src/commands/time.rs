@@ -0,0 +1,46 @@
+use anyhow::{bail, Result};
+
+use crate::{time::named_time, Server};
+
+use super::{require_op, UsageError};
+
+pub fn time(server: &Server, connection_id: u64, args: &[&str]) -> Result<String> {
+    match args {
+        ["query"] | [] => {
+            let time = server.get_time();
+            let frozen = if server.is_time_frozen() {
+                ", frozen"
+            } else {
+                ""
+            };
+            Ok(format!("The time is {}{}", time, frozen))
+        }
+        ["set", time] => {
+            require_op(server, connection_id)?;
+            let Some(time) = named_time(time).or_else(|| time.parse().ok()) else {
+                bail!("{} is neither a number nor a time of day", time);
+            };
+            server.set_time(time);
+            Ok(format!("Set the time to {}", server.get_time()))
+        }
+        ["add", ticks] => {
+            require_op(server, connection_id)?;
+            let Ok(ticks) = ticks.parse::<i64>() else {
+                bail!("{} is not a number", ticks);
+            };
+            server.set_time(server.get_time().saturating_add(ticks));
+            Ok(format!("Set the time to {}", server.get_time()))
+        }
+        ["freeze"] => {
+            require_op(server, connection_id)?;
+            server.set_time_frozen(true);
+            Ok("Stopped the day-night cycle".to_owned())
+        }
+        ["unfreeze"] => {
+            require_op(server, connection_id)?;
+            server.set_time_frozen(false);
+            Ok("Resumed the day-night cycle".to_owned())
+        }
+        _ => Err(UsageError.into()),
+    }
+}
@@ -0,0 +1,115 @@
+use std::{fs::File, path::Path};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::world::{random_tick::DEFAULT_RANDOM_TICK_SPEED, BlockPos};
+
+/// Settings read from the JSON config file, missing keys fall back to the defaults
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ServerConfig {
+    pub address: String,
+    pub mod_path: String,
+    pub server_name: String,
+    /// Directory of the world in the Pi on-disk format, created if it doesn't exist
+    pub world_path: String,
+    /// Seconds between saves of the world, 0 to only save on shutdown
+    pub autosave_interval: u64,
+    /// Game mode of new worlds, existing worlds keep theirs
+    pub gamemode: u32,
+    /// Seed of new worlds, a random one is picked if not set
+    pub seed: Option<u32>,
+    /// Spawn point of new worlds as `[x, y, z]`, the world centre if not set. Players spawn at the
+    /// nearest safe spot on the surface.
+    pub spawn: Option<BlockPos>,
+    /// Random tick speed of new worlds, existing worlds keep theirs. At most
+    /// `MAX_RANDOM_TICK_SPEED`.
+    pub random_tick_speed: u32,
+    /// Generator filling chunks that don't exist yet
+    pub generator: GeneratorConfig,
+    /// Usernames allowed to run the admin commands and to edit every region. The clients don't
+    /// authenticate their names, anybody joining with the name of an op who isn't online is an op.
+    pub ops: Vec<String>,
+    /// Most blocks a player may change with one world edit command
+    pub edit_budget: usize,
+    /// Days the block log keeps changes, 0 to keep them forever
+    pub block_log_days: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum GeneratorConfig {
+    #[default]
+    Natural,
+    Flat {
+        /// Bottom to top
+        #[serde(default = "default_flat_layers")]
+        layers: Vec<FlatLayer>,
+    },
+    Void,
+    /// A generator registered by a mod in `wg_registry`
+    Lua {
+        name: String,
+    },
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct FlatLayer {
+    pub block: u8,
+    #[serde(default)]
+    pub aux: u8,
+    pub height: usize,
+}
+
+fn default_flat_layers() -> Vec<FlatLayer> {
+    vec![
+        FlatLayer {
+            block: 7,
+            aux: 0,
+            height: 1,
+        },
+        FlatLayer {
+            block: 3,
+            aux: 0,
+            height: 2,
+        },
+        FlatLayer {
+            block: 2,
+            aux: 0,
+            height: 1,
+        },
+    ]
+}
+
+impl ServerConfig {
+    /// Loads the config file, or the defaults if it doesn't exist
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let file = File::open(path)?;
+        serde_json::from_reader(file).with_context(|| format!("Invalid config {:?}", path))
+    }
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            address: "0.0.0.0:19132".to_owned(),
+            mod_path: "example_mod/mod.lua".to_owned(),
+            server_name: "A GoldMineMC server!".to_owned(),
+            world_path: "world".to_owned(),
+            autosave_interval: 300,
+            gamemode: 1,
+            seed: None,
+            spawn: None,
+            random_tick_speed: DEFAULT_RANDOM_TICK_SPEED,
+            generator: GeneratorConfig::default(),
+            ops: Vec::new(),
+            edit_budget: 32768,
+            block_log_days: 30,
+        }
+    }
+}
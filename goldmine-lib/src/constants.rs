@@ -2,26 +2,37 @@
 use declio::magic_bytes;
 use serde::{Deserialize, Serialize};
 
+/// The protocol version whose layout `GamePacket` uses internally
 pub const SERVER_VERSION: u32 = 9;
 
+/// Size of the block data in `SCChunkDataPacket`, 256 columns of a flag byte and 8 sections
+pub const CHUNK_DATA_LEN: usize = 256 * (1 + 8 * (16 + 8));
+
+/// MTU used until the client tells us its own in `CSConnectionRequest2`
+pub const DEFAULT_MTU: u16 = 1447;
+
+/// Bytes of a datagram not available to encapsulated game packets: IP and UDP headers, the
+/// datagram header and the biggest encapsulation header
+pub const DATAGRAM_OVERHEAD: usize = 28 + 4 + 20;
+
 magic_bytes! {
-    #[derive(Serialize, Deserialize, Debug)]
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy)]
     pub MAGIC(&0x00ffff00fefefefefdfdfdfd12345678_u128.to_be_bytes());
-    #[derive(Serialize, Deserialize, Debug)]
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy)]
     pub NULL_BYTE(&[0_u8]);
-    #[derive(Serialize, Deserialize, Debug)]
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy)]
     pub HANDSHAKE_COOKIE(&0x043f57fe_u32.to_be_bytes());
-    #[derive(Serialize, Deserialize, Debug)]
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy)]
     pub HANDSHAKE_FLAGS(&[0xcd_u8]);
-    #[derive(Serialize, Deserialize, Debug)]
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy)]
     pub HANDSHAKE_DATA(&([
         0, 0, 4, 245, 255, 255, 245, 0, 0, 4, 255, 255, 255, 255, 0, 0, 4, 255, 255, 255, 255, 0, 0, 4,
         255, 255, 255, 255, 0, 0, 4, 255, 255, 255, 255, 0, 0, 4, 255, 255, 255, 255, 0, 0, 4, 255,
         255, 255, 255, 0, 0, 4, 255, 255, 255, 255, 0, 0, 4, 255, 255, 255, 255, 0, 0, 4, 255, 255,
         255, 255,
     ] as [u8; 70]));
-    #[derive(Serialize, Deserialize, Debug)]
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy)]
     pub HANDSHAKE_DOUBLE_NULL(&0_u16.to_ne_bytes());
-    #[derive(Serialize, Deserialize, Debug)]
+    #[derive(Serialize, Deserialize, Debug, Clone, Copy)]
     pub HANDSHAKE_UNKNOWN(&([0x00, 0x00, 0x00, 0x00, 0x04, 0x44, 0x0b, 0xa9] as [u8; 8]));
 }
@@ -1,20 +1,372 @@
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
+use crate::{
+    block_log::BlockLog,
+    game_packets::{GamePacket, ItemStack},
+    nbt::{self, Compound, Tag},
+    regions::Region,
+    world::{
+        physics::PhysicsRules,
+        random_tick::{DEFAULT_RANDOM_TICK_SPEED, MAX_RANDOM_TICK_SPEED},
+        storage::LEVEL_STORAGE_VERSION,
+        tile_entity::TileEntity,
+        BlockPos, World,
+    },
+};
+
+/// Entity type id of players
+pub const PLAYER_TYPE_ID: i32 = 63;
+pub const ITEM_TYPE_ID: i32 = 64;
+pub const PRIMED_TNT_TYPE_ID: i32 = 65;
+/// Health of players, in half hearts
+pub const MAX_HEALTH: i16 = 20;
+
 #[derive(Serialize, Deserialize, Default)]
 pub struct ServerData {
+    pub level_name: String,
     pub seed: u32,
     pub gamemode: u32,
+    pub spawn: BlockPos,
+    /// Spawn points of players that set their own, by username
+    #[serde(default)]
+    pub player_spawns: HashMap<String, BlockPos>,
+    pub time: i64,
+    /// The day-night cycle is stopped at `time`
+    #[serde(default)]
+    pub time_frozen: bool,
+    #[serde(default)]
+    pub physics: PhysicsRules,
+    /// Blocks picked per chunk section and tick for random ticks
+    #[serde(default = "default_random_tick_speed")]
+    pub random_tick_speed: u32,
+    #[serde(default)]
+    pub regions: Vec<Region>,
     pub entities: Vec<EntityData>,
+    /// Lit TNT, saved with the other entities
+    #[serde(skip)]
+    pub primed_tnt: Vec<PrimedTnt>,
     pub inventories: HashMap<u32, Inventory>,
+    #[serde(skip)]
+    pub world: World,
+    /// Stored in its own file next to the world
+    #[serde(skip)]
+    pub block_log: BlockLog,
+    /// Keys of `level.dat` goldmine doesn't use, kept so they survive a save
+    #[serde(skip)]
+    pub level_extra: Compound,
+}
+
+/// The settings of `level.dat` goldmine uses
+#[derive(Serialize, Deserialize, Default)]
+#[serde(rename_all = "PascalCase", default)]
+struct LevelDat {
+    level_name: String,
+    random_seed: i64,
+    game_type: i32,
+    spawn_x: i32,
+    spawn_y: i32,
+    spawn_z: i32,
+    time: i64,
+    /// The time the day-night cycle is stopped at, -1 while it runs
+    #[serde(rename = "dayCycleStopTime", default = "day_cycle_running")]
+    day_cycle_stop_time: i64,
+    last_played: i64,
+    storage_version: i32,
+    platform: i32,
+    /// Only used by goldmine, the clients ignore it
+    player_spawns: HashMap<String, BlockPos>,
+    physics: PhysicsRules,
+    #[serde(default = "default_random_tick_speed")]
+    random_tick_speed: u32,
+    /// Only used by goldmine
+    regions: Vec<Region>,
+}
+
+fn day_cycle_running() -> i64 {
+    -1
+}
+
+fn default_random_tick_speed() -> u32 {
+    DEFAULT_RANDOM_TICK_SPEED
+}
+
+fn get_int(compound: &Compound, key: &str) -> Option<i64> {
+    compound.get(key).and_then(Tag::as_i64)
+}
+
+impl ServerData {
+    /// Takes over the settings of a `level.dat`
+    pub fn apply_level(&mut self, level: Compound) -> Result<()> {
+        let settings: LevelDat = nbt::from_tag(Tag::Compound(level.clone()))?;
+        self.level_name = settings.level_name;
+        self.seed = settings.random_seed as u32;
+        self.gamemode = settings.game_type as u32;
+        self.spawn = (settings.spawn_x, settings.spawn_y, settings.spawn_z);
+        self.player_spawns = settings.player_spawns;
+        self.physics = settings.physics;
+        self.random_tick_speed = settings.random_tick_speed.min(MAX_RANDOM_TICK_SPEED);
+        self.regions = settings.regions;
+        self.time = settings.time;
+        self.time_frozen = settings.day_cycle_stop_time >= 0;
+        if self.time_frozen {
+            self.time = settings.day_cycle_stop_time;
+        }
+        self.level_extra = level;
+        Ok(())
+    }
+
+    pub fn to_level(&self) -> Result<Compound> {
+        let last_played = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs() as i64);
+        let settings = LevelDat {
+            level_name: self.level_name.clone(),
+            random_seed: self.seed.into(),
+            game_type: self.gamemode as i32,
+            spawn_x: self.spawn.0,
+            spawn_y: self.spawn.1,
+            spawn_z: self.spawn.2,
+            time: self.time,
+            day_cycle_stop_time: if self.time_frozen { self.time } else { -1 },
+            last_played,
+            storage_version: LEVEL_STORAGE_VERSION,
+            player_spawns: self.player_spawns.clone(),
+            physics: self.physics.clone(),
+            random_tick_speed: self.random_tick_speed,
+            regions: self.regions.clone(),
+            platform: get_int(&self.level_extra, "Platform").map_or(2, |platform| platform as i32),
+        };
+        let mut level = self.level_extra.clone();
+        let Tag::Compound(settings) = nbt::to_tag(&settings)? else {
+            unreachable!("structs serialize into compounds");
+        };
+        level.extend(settings);
+        Ok(level)
+    }
+
+    /// Takes over the entities and tile entities of an `entities.dat`, the tile entities go into
+    /// `world`. Entities with an unknown format are skipped.
+    pub fn apply_entities(&mut self, mut entities: Compound) {
+        let mut list = |key: &str| match entities.remove(key) {
+            Some(Tag::List(values)) => values
+                .into_iter()
+                .filter_map(|value| match value {
+                    Tag::Compound(compound) => Some(compound),
+                    _ => None,
+                })
+                .collect(),
+            _ => Vec::new(),
+        };
+        let (primed_tnt, entities): (Vec<EntityData>, Vec<EntityData>) = list("Entities")
+            .into_iter()
+            .filter_map(EntityData::from_nbt)
+            .partition(|entity| entity.type_id == PRIMED_TNT_TYPE_ID);
+        self.entities = entities;
+        self.primed_tnt = primed_tnt.into_iter().map(PrimedTnt::from_entity).collect();
+        for (pos, tile_entity) in list("TileEntities")
+            .into_iter()
+            .filter_map(TileEntity::from_nbt)
+        {
+            self.world.set_tile_entity(pos, tile_entity);
+        }
+    }
+
+    /// Players are not stored with the other entities
+    pub fn to_entities(&self) -> Compound {
+        let entities = self
+            .entities
+            .iter()
+            .filter(|entity| entity.type_id != PLAYER_TYPE_ID)
+            .map(|entity| Tag::Compound(entity.to_nbt()))
+            .chain(
+                self.primed_tnt
+                    .iter()
+                    .map(|tnt| Tag::Compound(tnt.to_entity().to_nbt())),
+            )
+            .collect();
+        let tile_entities = self
+            .world
+            .tile_entities()
+            .map(|(pos, tile_entity)| Tag::Compound(tile_entity.to_nbt(pos)))
+            .collect();
+        Compound::from([
+            ("Entities".to_owned(), Tag::List(entities)),
+            ("TileEntities".to_owned(), Tag::List(tile_entities)),
+        ])
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone)]
 pub struct EntityData {
     pub id: u32,
+    #[serde(default)]
+    pub type_id: i32,
     pub pos: Vec3,
     pub rot: Vec3,
+    #[serde(default = "max_health")]
+    pub health: i16,
+    /// NBT of a loaded entity goldmine doesn't use, kept so it survives a save
+    #[serde(skip)]
+    pub extra: Compound,
+}
+
+impl EntityData {
+    pub fn from_nbt(nbt: Compound) -> Option<Self> {
+        let floats = |key: &str| -> Option<Vec<f32>> {
+            nbt.get(key)?
+                .as_list()?
+                .iter()
+                .map(|value| value.as_f64().map(|value| value as f32))
+                .collect()
+        };
+        let pos = floats("Pos")?;
+        let rot = floats("Rotation").unwrap_or_default();
+        Some(Self {
+            id: rand::random(),
+            type_id: get_int(&nbt, "id")? as i32,
+            pos: (*pos.first()?, *pos.get(1)?, *pos.get(2)?),
+            // Stored as yaw and pitch
+            rot: (
+                rot.get(1).copied().unwrap_or(0.0),
+                rot.first().copied().unwrap_or(0.0),
+                0.0,
+            ),
+            health: get_int(&nbt, "Health").map_or(MAX_HEALTH, |health| health as i16),
+            extra: nbt,
+        })
+    }
+
+    /// A dropped item lying at `pos`
+    pub fn item(pos: Vec3, item: ItemStack) -> Self {
+        let item_nbt = Compound::from([
+            ("id".to_owned(), Tag::Short(item.id as i16)),
+            ("Count".to_owned(), Tag::Byte(item.count as i8)),
+            ("Damage".to_owned(), Tag::Short(item.aux as i16)),
+        ]);
+        Self {
+            id: rand::random(),
+            type_id: ITEM_TYPE_ID,
+            pos,
+            rot: (0.0, 0.0, 0.0),
+            health: 5,
+            extra: Compound::from([("Item".to_owned(), Tag::Compound(item_nbt))]),
+        }
+    }
+
+    /// The `SCAddItemEntity` showing a dropped item, `None` for other entities
+    pub fn add_item_packet(&self) -> Option<GamePacket> {
+        if self.type_id != ITEM_TYPE_ID {
+            return None;
+        }
+        let item = self.extra.get("Item")?.as_compound()?;
+        let int = |key: &str| get_int(item, key).unwrap_or_default();
+        Some(GamePacket::SCAddItemEntity {
+            entity_id: self.id,
+            item_id: int("id") as u16,
+            item_amount: int("Count") as u8,
+            item_data: int("Damage") as u16,
+            pos_x: self.pos.0,
+            pos_y: self.pos.1,
+            pos_z: self.pos.2,
+            speed_x: 0,
+            speed_y: 0,
+            speed_z: 0,
+        })
+    }
+
+    pub fn to_nbt(&self) -> Compound {
+        let mut nbt = self.extra.clone();
+        nbt.insert("id".to_owned(), Tag::Int(self.type_id));
+        nbt.insert(
+            "Pos".to_owned(),
+            Tag::List(vec![
+                Tag::Float(self.pos.0),
+                Tag::Float(self.pos.1),
+                Tag::Float(self.pos.2),
+            ]),
+        );
+        nbt.insert(
+            "Rotation".to_owned(),
+            Tag::List(vec![Tag::Float(self.rot.1), Tag::Float(self.rot.0)]),
+        );
+        nbt.insert("Health".to_owned(), Tag::Short(self.health));
+        nbt
+    }
+}
+
+fn max_health() -> i16 {
+    MAX_HEALTH
+}
+
+/// Lit TNT waiting to explode
+#[derive(Clone)]
+pub struct PrimedTnt {
+    pub id: u32,
+    pub pos: Vec3,
+    /// Ticks until it explodes
+    pub fuse: u32,
+    /// The player who lit it or the TNT that set it off, the destroyed blocks are logged under
+    /// their name
+    pub igniter: Option<String>,
+}
+
+impl PrimedTnt {
+    pub fn new(pos: Vec3, fuse: u32, igniter: Option<String>) -> Self {
+        Self {
+            id: rand::random(),
+            pos,
+            fuse,
+            igniter,
+        }
+    }
+
+    fn from_entity(entity: EntityData) -> Self {
+        Self {
+            id: entity.id,
+            pos: entity.pos,
+            fuse: get_int(&entity.extra, "Fuse").map_or(0, |fuse| fuse.max(0) as u32),
+            igniter: entity
+                .extra
+                .get("Igniter")
+                .and_then(Tag::as_str)
+                .map(str::to_owned),
+        }
+    }
+
+    fn to_entity(&self) -> EntityData {
+        let mut extra = Compound::from([("Fuse".to_owned(), Tag::Byte(self.fuse.min(127) as i8))]);
+        if let Some(igniter) = &self.igniter {
+            extra.insert("Igniter".to_owned(), Tag::String(igniter.clone()));
+        }
+        EntityData {
+            id: self.id,
+            type_id: PRIMED_TNT_TYPE_ID,
+            pos: self.pos,
+            rot: (0.0, 0.0, 0.0),
+            health: MAX_HEALTH,
+            extra,
+        }
+    }
+
+    pub fn add_packet(&self) -> GamePacket {
+        GamePacket::SCAddEntity {
+            entity_id: self.id,
+            entity_type: PRIMED_TNT_TYPE_ID as u8,
+            pos_x: self.pos.0,
+            pos_y: self.pos.1,
+            pos_z: self.pos.2,
+            has_motion: 0,
+            speed_x: 0,
+            speed_y: 0,
+            speed_z: 0,
+        }
+    }
 }
 
 pub type Vec3 = (f32, f32, f32);
@@ -1,9 +1,17 @@
+use anyhow::Result;
 use declio::{ctx, util, Decode, Encode};
+use mlua::{
+    FromLua, Lua, LuaSerdeExt, MetaMethod, Table, UserData, UserDataFields, UserDataMethods, Value,
+};
 use serde::{Deserialize, Serialize};
 
 use crate::{
+    codes::{
+        narrow_face, AnimateAction, BlockFace, EntityEventKind, InteractAction, LevelEventKind,
+        PlayerActionKind,
+    },
     constants::{
-        HANDSHAKE_COOKIE, HANDSHAKE_DATA, HANDSHAKE_DOUBLE_NULL, HANDSHAKE_FLAGS, HANDSHAKE_UNKNOWN,
+        CHUNK_DATA_LEN, HANDSHAKE_COOKIE, HANDSHAKE_DATA, HANDSHAKE_DOUBLE_NULL, HANDSHAKE_FLAGS, HANDSHAKE_UNKNOWN,
     },
     u24::u24,
 };
@@ -37,8 +45,26 @@ pub enum Encapsulation {
         #[declio(ctx = "ctx::Len((length/8).into())")]
         game_packet: Vec<u8>,
     },
+    #[declio(id = "0x50")]
+    ExtendedCountSplit {
+        #[declio(ctx = "ctx::Endian::Big")]
+        length: u16,
+        #[declio(ctx = "ctx::Endian::Little")]
+        count: u24,
+        #[declio(ctx = "ctx::Endian::Big")]
+        split_count: u32,
+        #[declio(ctx = "ctx::Endian::Big")]
+        split_id: u16,
+        #[declio(ctx = "ctx::Endian::Big")]
+        split_index: u32,
+        #[declio(ctx = "ctx::Len((length/8).into())")]
+        game_packet: Vec<u8>,
+    },
 }
 
+/// Set in the encapsulation id if the game packet is a fragment of a bigger one
+pub const SPLIT_FLAG: u8 = 0x10;
+
 impl Encapsulation {
     pub fn to_game_packet(self) -> Vec<u8> {
         match self {
@@ -57,11 +83,95 @@ impl Encapsulation {
                 unknown: _,
                 game_packet,
             } => game_packet,
+            Encapsulation::ExtendedCountSplit {
+                length: _,
+                count: _,
+                split_count: _,
+                split_id: _,
+                split_index: _,
+                game_packet,
+            } => game_packet,
+        }
+    }
+
+    pub fn game_packet(&self) -> &[u8] {
+        match self {
+            Encapsulation::Simple { game_packet, .. }
+            | Encapsulation::ExtendedCount { game_packet, .. }
+            | Encapsulation::ExtendedFull { game_packet, .. }
+            | Encapsulation::ExtendedCountSplit { game_packet, .. } => game_packet,
         }
     }
 }
 
-#[derive(Serialize, Deserialize, Debug, Encode, Decode)]
+/// Iterates over the game packets encapsulated in the body of a `Packet::Custom` without copying
+/// them out of the receive buffer. Like the owned decoder it stops at the first malformed
+/// encapsulation. Clients never send packets big enough to be split, so fragments are skipped
+/// instead of reassembled.
+pub struct Encapsulations<'a> {
+    remaining: &'a [u8],
+}
+
+impl<'a> Encapsulations<'a> {
+    pub fn new(body: &'a [u8]) -> Self {
+        Self { remaining: body }
+    }
+}
+
+impl<'a> Iterator for Encapsulations<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (&id, rest) = self.remaining.split_first()?;
+            // Bytes between the length and the game packet, see the `Encapsulation` variants
+            let mut skip = match id & !SPLIT_FLAG {
+                0x00 => 0,
+                0x40 => 3,
+                0x60 => 7,
+                _ => return None,
+            };
+            if id & SPLIT_FLAG != 0 {
+                skip += 10;
+            }
+            let length = u16::from_be_bytes([*rest.first()?, *rest.get(1)?]) / 8;
+            let start = 2 + skip;
+            let end = start + usize::from(length);
+            let game_packet = rest.get(start..end)?;
+            self.remaining = &rest[end..];
+            if id & SPLIT_FLAG == 0 {
+                return Some(game_packet);
+            }
+        }
+    }
+}
+
+/// An item or block in an inventory slot, id 0 for an empty slot
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default, Encode, Decode)]
+pub struct ItemStack {
+    #[declio(ctx = "ctx::Endian::Big")]
+    pub id: u16,
+    #[declio(ctx = "ctx::Endian::Big")]
+    pub count: u8,
+    #[declio(ctx = "ctx::Endian::Big")]
+    pub aux: u16,
+}
+
+impl ItemStack {
+    pub fn is_empty(&self) -> bool {
+        self.id == 0 || self.count == 0
+    }
+}
+
+/// A block destroyed by an explosion, relative to its center
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Encode, Decode)]
+pub struct ExplodeRecord {
+    pub x: i8,
+    pub y: i8,
+    pub z: i8,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Encode, Decode)]
 #[declio(id_type = "u8")]
 pub enum GamePacket {
     #[declio(id = "0x00")]
@@ -315,8 +425,8 @@ pub enum GamePacket {
         block_id: u8,
         #[declio(ctx = "ctx::Endian::Big")]
         block_aux: u8,
-        #[declio(ctx = "ctx::Endian::Big")]
-        face: u8,
+        #[declio(with = "narrow_face", ctx = "ctx::Endian::Big")]
+        face: BlockFace,
     },
     #[declio(id = "0x96")]
     RemoveBlock {
@@ -359,11 +469,25 @@ pub enum GamePacket {
         #[declio(with = "util::utf8", ctx = "ctx::Len((*title_len).into())")]
         title: String,
     },
-    // ExplodePacket
+    #[declio(id = "0x99")]
+    SCExplode {
+        #[declio(ctx = "ctx::Endian::Big")]
+        pos_x: f32,
+        #[declio(ctx = "ctx::Endian::Big")]
+        pos_y: f32,
+        #[declio(ctx = "ctx::Endian::Big")]
+        pos_z: f32,
+        #[declio(ctx = "ctx::Endian::Big")]
+        radius: f32,
+        #[declio(ctx = "ctx::Endian::Big")]
+        records_len: u32,
+        #[declio(ctx = "ctx::Len(*records_len as usize)")]
+        records: Vec<ExplodeRecord>,
+    },
     #[declio(id = "0x9a")]
     SCLevelEvent {
         #[declio(ctx = "ctx::Endian::Big")]
-        event_id: u32,
+        event_id: LevelEventKind,
         #[declio(ctx = "ctx::Endian::Big")]
         pos_x: u32,
         #[declio(ctx = "ctx::Endian::Big")]
@@ -391,7 +515,7 @@ pub enum GamePacket {
         #[declio(ctx = "ctx::Endian::Big")]
         entity_id: u32,
         #[declio(ctx = "ctx::Endian::Big")]
-        event: u8, // TODO: Reverse engineer what this is
+        event: EntityEventKind,
     },
     #[declio(id = "0x9d")]
     CSRequestChunk {
@@ -406,9 +530,8 @@ pub enum GamePacket {
         index_x: u32,
         #[declio(ctx = "ctx::Endian::Big")]
         index_z: u32,
-        #[declio(ctx = "ctx::Endian::Big")]
-        chunk_data: u8, // The format is a bit confusing, probably needs a custom datatype to en/decode
-        // Rust doesnt let me compile [u8; 49408], so this is u8 for now
+        #[declio(ctx = "ctx::Len(CHUNK_DATA_LEN)")]
+        chunk_data: Vec<u8>, // See Chunk::to_network_bytes for the format
     },
     // PlayerEquipmentPacket
     #[declio(id = "0xa0")]
@@ -427,17 +550,45 @@ pub enum GamePacket {
     #[declio(id = "0xa1")]
     Interact {
         #[declio(ctx = "ctx::Endian::Big")]
-        action: u8, // TODO: Reverse engineer what this is
+        action: InteractAction,
         #[declio(ctx = "ctx::Endian::Big")]
         entity_id: u32,
         #[declio(ctx = "ctx::Endian::Big")]
         target: u32,
     },
-    // UseItemPacket
+    #[declio(id = "0xa2")]
+    CSUseItem {
+        #[declio(ctx = "ctx::Endian::Big")]
+        pos_x: u32,
+        #[declio(ctx = "ctx::Endian::Big")]
+        pos_y: u32,
+        #[declio(ctx = "ctx::Endian::Big")]
+        pos_z: u32,
+        #[declio(ctx = "ctx::Endian::Big")]
+        face: BlockFace,
+        #[declio(ctx = "ctx::Endian::Big")]
+        item_id: u16,
+        #[declio(ctx = "ctx::Endian::Big")]
+        item_aux: u8,
+        #[declio(ctx = "ctx::Endian::Big")]
+        entity_id: u32,
+        #[declio(ctx = "ctx::Endian::Big")]
+        click_x: f32,
+        #[declio(ctx = "ctx::Endian::Big")]
+        click_y: f32,
+        #[declio(ctx = "ctx::Endian::Big")]
+        click_z: f32,
+        #[declio(ctx = "ctx::Endian::Big")]
+        player_x: f32,
+        #[declio(ctx = "ctx::Endian::Big")]
+        player_y: f32,
+        #[declio(ctx = "ctx::Endian::Big")]
+        player_z: f32,
+    },
     #[declio(id = "0xa3")]
     CSPlayerAction {
         #[declio(ctx = "ctx::Endian::Big")]
-        action: u32,
+        action: PlayerActionKind,
         #[declio(ctx = "ctx::Endian::Big")]
         pos_x: u32,
         #[declio(ctx = "ctx::Endian::Big")]
@@ -445,7 +596,7 @@ pub enum GamePacket {
         #[declio(ctx = "ctx::Endian::Big")]
         pos_z: u32,
         #[declio(ctx = "ctx::Endian::Big")]
-        face: u32, // A byte could have been enough here since there are only 6 faces, damn you mojank!
+        face: BlockFace, // A byte could have been enough here since there are only 6 faces, damn you mojank!
         #[declio(ctx = "ctx::Endian::Big")]
         entity_id: u32,
     },
@@ -465,11 +616,11 @@ pub enum GamePacket {
         #[declio(ctx = "ctx::Endian::Big")]
         entity_id: u32,
         #[declio(ctx = "ctx::Endian::Big")]
-        speed_x: u32,
+        speed_x: i16, // Blocks per tick times 8000
         #[declio(ctx = "ctx::Endian::Big")]
-        speed_y: u32,
+        speed_y: i16,
         #[declio(ctx = "ctx::Endian::Big")]
-        speed_z: u32,
+        speed_z: i16,
     },
     #[declio(id = "0xa8")]
     SCSetHealth {
@@ -488,7 +639,7 @@ pub enum GamePacket {
     #[declio(id = "0xaa")]
     Animate {
         #[declio(ctx = "ctx::Endian::Big")]
-        action: u8,
+        action: AnimateAction,
         #[declio(ctx = "ctx::Endian::Big")]
         entity_id: u32,
     },
@@ -535,17 +686,32 @@ pub enum GamePacket {
         #[declio(ctx = "ctx::Endian::Big")]
         window_id: u8,
     },
-    // ContainerSetSlotPacket
+    #[declio(id = "0xb0")]
+    ContainerSetSlot {
+        #[declio(ctx = "ctx::Endian::Big")]
+        window_id: u8,
+        #[declio(ctx = "ctx::Endian::Big")]
+        slot: u16,
+        item: ItemStack,
+    },
     #[declio(id = "0xb1")]
     SCContainerSetData {
         #[declio(ctx = "ctx::Endian::Big")]
         window_id: u8,
         #[declio(ctx = "ctx::Endian::Big")]
-        property: u32,
+        property: u16,
         #[declio(ctx = "ctx::Endian::Big")]
-        value: u32,
+        value: u16,
+    },
+    #[declio(id = "0xb2")]
+    SCContainerSetContent {
+        #[declio(ctx = "ctx::Endian::Big")]
+        window_id: u8,
+        #[declio(ctx = "ctx::Endian::Big")]
+        items_len: u16,
+        #[declio(ctx = "ctx::Len((*items_len).into())")]
+        items: Vec<ItemStack>,
     },
-    // ContainerSetContentPacket
     // ContainerAckPacket
     #[declio(id = "0xb4")]
     CSChat {
@@ -581,3 +747,298 @@ pub enum GamePacket {
     },
     // AdventureSettingsPacket
 }
+
+impl GamePacket {
+    pub const KINDS: &'static [&'static str] = &[
+        "CSPing",
+        "SCPong",
+        "CSClientConnect",
+        "SCServerHandshake",
+        "CSClientHandshake",
+        "CSClientCancelConnect",
+        "CSLogin",
+        "SCLoginStatus",
+        "CSReady",
+        "SCMessage",
+        "SCSetTime",
+        "SCStartGame",
+        "SCAddMob",
+        "SCAddPlayer",
+        "SCRemovePlayer",
+        "SCAddEntity",
+        "SCRemoveEntity",
+        "SCAddItemEntity",
+        "SCTakeItemEntity",
+        "SCMoveEntity",
+        "SCMoveEntityWithRotation",
+        "MovePlayer",
+        "PlaceBlock",
+        "RemoveBlock",
+        "SCUpdateBlock",
+        "SCAddPainting",
+        "SCExplode",
+        "SCLevelEvent",
+        "SCTileEvent",
+        "EntityEvent",
+        "CSRequestChunk",
+        "SCChunkDataPacket",
+        "PlayerArmorEquipment",
+        "Interact",
+        "CSUseItem",
+        "CSPlayerAction",
+        "SCHurtArmor",
+        "SCSetEntityData",
+        "SCSetEntityMotion",
+        "SCSetHealth",
+        "SCSetSpawnPosition",
+        "Animate",
+        "Respawn",
+        "CSDropItem",
+        "SCContainerOpen",
+        "ContainerClose",
+        "ContainerSetSlot",
+        "SCContainerSetData",
+        "SCContainerSetContent",
+        "CSChat",
+        "SignUpdate",
+    ];
+
+    pub fn kind(&self) -> &'static str {
+        match self {
+            GamePacket::CSPing { .. } => "CSPing",
+            GamePacket::SCPong { .. } => "SCPong",
+            GamePacket::CSClientConnect { .. } => "CSClientConnect",
+            GamePacket::SCServerHandshake { .. } => "SCServerHandshake",
+            GamePacket::CSClientHandshake { .. } => "CSClientHandshake",
+            GamePacket::CSClientCancelConnect { .. } => "CSClientCancelConnect",
+            GamePacket::CSLogin { .. } => "CSLogin",
+            GamePacket::SCLoginStatus { .. } => "SCLoginStatus",
+            GamePacket::CSReady { .. } => "CSReady",
+            GamePacket::SCMessage { .. } => "SCMessage",
+            GamePacket::SCSetTime { .. } => "SCSetTime",
+            GamePacket::SCStartGame { .. } => "SCStartGame",
+            GamePacket::SCAddMob { .. } => "SCAddMob",
+            GamePacket::SCAddPlayer { .. } => "SCAddPlayer",
+            GamePacket::SCRemovePlayer { .. } => "SCRemovePlayer",
+            GamePacket::SCAddEntity { .. } => "SCAddEntity",
+            GamePacket::SCRemoveEntity { .. } => "SCRemoveEntity",
+            GamePacket::SCAddItemEntity { .. } => "SCAddItemEntity",
+            GamePacket::SCTakeItemEntity { .. } => "SCTakeItemEntity",
+            GamePacket::SCMoveEntity { .. } => "SCMoveEntity",
+            GamePacket::SCMoveEntityWithRotation { .. } => "SCMoveEntityWithRotation",
+            GamePacket::MovePlayer { .. } => "MovePlayer",
+            GamePacket::PlaceBlock { .. } => "PlaceBlock",
+            GamePacket::RemoveBlock { .. } => "RemoveBlock",
+            GamePacket::SCUpdateBlock { .. } => "SCUpdateBlock",
+            GamePacket::SCAddPainting { .. } => "SCAddPainting",
+            GamePacket::SCExplode { .. } => "SCExplode",
+            GamePacket::SCLevelEvent { .. } => "SCLevelEvent",
+            GamePacket::SCTileEvent { .. } => "SCTileEvent",
+            GamePacket::EntityEvent { .. } => "EntityEvent",
+            GamePacket::CSRequestChunk { .. } => "CSRequestChunk",
+            GamePacket::SCChunkDataPacket { .. } => "SCChunkDataPacket",
+            GamePacket::PlayerArmorEquipment { .. } => "PlayerArmorEquipment",
+            GamePacket::Interact { .. } => "Interact",
+            GamePacket::CSUseItem { .. } => "CSUseItem",
+            GamePacket::CSPlayerAction { .. } => "CSPlayerAction",
+            GamePacket::SCHurtArmor { .. } => "SCHurtArmor",
+            GamePacket::SCSetEntityData { .. } => "SCSetEntityData",
+            GamePacket::SCSetEntityMotion { .. } => "SCSetEntityMotion",
+            GamePacket::SCSetHealth { .. } => "SCSetHealth",
+            GamePacket::SCSetSpawnPosition { .. } => "SCSetSpawnPosition",
+            GamePacket::Animate { .. } => "Animate",
+            GamePacket::Respawn { .. } => "Respawn",
+            GamePacket::CSDropItem { .. } => "CSDropItem",
+            GamePacket::SCContainerOpen { .. } => "SCContainerOpen",
+            GamePacket::ContainerClose { .. } => "ContainerClose",
+            GamePacket::ContainerSetSlot { .. } => "ContainerSetSlot",
+            GamePacket::SCContainerSetData { .. } => "SCContainerSetData",
+            GamePacket::SCContainerSetContent { .. } => "SCContainerSetContent",
+            GamePacket::CSChat { .. } => "CSChat",
+            GamePacket::SignUpdate { .. } => "SignUpdate",
+        }
+    }
+
+    /// A chat message for the player
+    pub fn message(message: impl Into<String>) -> Self {
+        let mut packet = GamePacket::SCMessage {
+            message_len: 0,
+            message: message.into(),
+        };
+        packet.sync_lengths();
+        packet
+    }
+
+    pub fn sign_update((x, y, z): (i32, i32, i32), lines: [String; 4]) -> Self {
+        let [line_1, line_2, line_3, line_4] = lines;
+        let mut packet = GamePacket::SignUpdate {
+            pos_x: x as u16,
+            pos_y: y as u8,
+            pos_z: z as u16,
+            line_1_len: 0,
+            line_1,
+            line_2_len: 0,
+            line_2,
+            line_3_len: 0,
+            line_3,
+            line_4_len: 0,
+            line_4,
+        };
+        packet.sync_lengths();
+        packet
+    }
+
+    /// Updates the `*_len` fields to the length of the strings and lists they describe
+    pub fn sync_lengths(&mut self) {
+        fn len(string: &str) -> u16 {
+            string.len().try_into().unwrap_or(u16::MAX)
+        }
+
+        match self {
+            GamePacket::CSLogin {
+                username_len,
+                username,
+                ..
+            }
+            | GamePacket::SCAddPlayer {
+                username_len,
+                username,
+                ..
+            } => *username_len = len(username),
+            GamePacket::SCMessage {
+                message_len,
+                message,
+            }
+            | GamePacket::CSChat {
+                message_len,
+                message,
+            } => *message_len = len(message),
+            GamePacket::SCAddPainting {
+                title_len, title, ..
+            }
+            | GamePacket::SCContainerOpen {
+                title_len, title, ..
+            } => *title_len = len(title),
+            GamePacket::SCContainerSetContent {
+                items_len, items, ..
+            } => *items_len = items.len().try_into().unwrap_or(u16::MAX),
+            GamePacket::SCExplode {
+                records_len,
+                records,
+                ..
+            } => *records_len = records.len().try_into().unwrap_or(u32::MAX),
+            GamePacket::SignUpdate {
+                line_1_len,
+                line_1,
+                line_2_len,
+                line_2,
+                line_3_len,
+                line_3,
+                line_4_len,
+                line_4,
+                ..
+            } => {
+                *line_1_len = len(line_1);
+                *line_2_len = len(line_2);
+                *line_3_len = len(line_3);
+                *line_4_len = len(line_4);
+            }
+            _ => (),
+        }
+    }
+
+    /// The fields of this packet as a Lua table
+    pub fn lua_fields(&self, lua: &Lua) -> mlua::Result<Table> {
+        match lua.to_value(self)? {
+            Value::Table(packet) => packet.get(self.kind()),
+            // Variants without fields serialize to just their name
+            _ => lua.create_table(),
+        }
+    }
+
+    /// Builds a packet of the given kind from a Lua table of fields. Length fields may be
+    /// omitted, they are derived from the strings and lists they describe.
+    pub fn from_lua_fields(lua: &Lua, kind: &str, fields: Table) -> mlua::Result<GamePacket> {
+        if !GamePacket::KINDS.contains(&kind) {
+            return Err(mlua::Error::runtime(format!("Unknown game packet kind {}", kind)));
+        }
+        let mut len_fields = Vec::new();
+        for pair in fields.pairs::<String, Value>() {
+            let (key, value) = pair?;
+            if value.is_string() || value.is_table() {
+                len_fields.push(format!("{}_len", key));
+            }
+        }
+        for len_field in len_fields {
+            if !fields.contains_key(len_field.as_str())? {
+                fields.set(len_field, 0)?;
+            }
+        }
+
+        let packet = lua.create_table()?;
+        packet.set(kind, fields)?;
+        let mut packet: GamePacket = lua.from_value(Value::Table(packet))?;
+        packet.sync_lengths();
+        Ok(packet)
+    }
+}
+
+impl FromLua for GamePacket {
+    fn from_lua(value: Value, _: &Lua) -> mlua::Result<Self> {
+        match value {
+            Value::UserData(packet) => Ok(packet.borrow::<GamePacket>()?.clone()),
+            other => Err(mlua::Error::FromLuaConversionError {
+                from: other.type_name(),
+                to: "GamePacket".to_owned(),
+                message: None,
+            }),
+        }
+    }
+}
+
+impl UserData for GamePacket {
+    fn add_fields<F: UserDataFields<Self>>(fields: &mut F) {
+        fields.add_field_method_get("kind", |_, packet| Ok(packet.kind()));
+    }
+
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("toString", |_, packet, ()| Ok(format!("{:x?}", packet)));
+        methods.add_method("fields", |lua, packet, ()| packet.lua_fields(lua));
+        methods.add_meta_method(MetaMethod::Index, |lua, packet, key: String| {
+            packet.lua_fields(lua)?.get::<Value>(key)
+        });
+        methods.add_meta_method_mut(
+            MetaMethod::NewIndex,
+            |lua, packet, (key, value): (String, Value)| {
+                let fields = packet.lua_fields(lua)?;
+                if !fields.contains_key(key.as_str())? {
+                    return Err(mlua::Error::runtime(format!(
+                        "{} has no field {}",
+                        packet.kind(),
+                        key
+                    )));
+                }
+                fields.set(key, value)?;
+                *packet = GamePacket::from_lua_fields(lua, packet.kind(), fields)?;
+                Ok(())
+            },
+        );
+    }
+}
+
+/// Constructors for game packets, exposed to Lua as `goldmine.packets`
+pub fn packets_module(lua: &Lua) -> Result<Table> {
+    let packets_module = lua.create_table()?;
+
+    let new = lua.create_function(|lua, (kind, fields): (String, Option<Table>)| {
+        let fields = match fields {
+            Some(fields) => fields,
+            None => lua.create_table()?,
+        };
+        GamePacket::from_lua_fields(lua, &kind, fields)
+    })?;
+    packets_module.set("new", new)?;
+    packets_module.set("kinds", GamePacket::KINDS)?;
+
+    Ok(packets_module)
+}
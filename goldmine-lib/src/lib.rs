@@ -1,24 +1,43 @@
-use std::{fs::File, io::Read, net::SocketAddr, sync::Arc};
+use std::{collections::HashMap, fs::File, io::Read, net::SocketAddr, path::Path, sync::Arc};
 
 use anyhow::{Ok, Result};
 use data::ServerData;
+use game_packets::GamePacket;
 use mlua::Lua;
 use modded::{install_modded_require, module::goldmine_module};
 use parking_lot::Mutex;
 use registry::Registries;
-use tokio::sync::watch;
+use session::Session;
+use tokio::sync::{watch, Notify};
+use tokio::sync::Mutex as AsyncMutex;
+use world::{
+    generator::{create_generator, WorldGenerator},
+    random_tick::MAX_RANDOM_TICK_SPEED,
+    CHUNK_HEIGHT, WORLD_SIZE,
+};
 use bimap::BiMap;
+use config::ServerConfig;
 
+pub mod block_log;
 pub mod blocks;
+pub mod codes;
+pub mod commands;
+pub mod config;
 pub mod constants;
 pub mod data;
 pub mod game_packets;
 pub mod logic;
 pub mod modded;
+pub mod nbt;
 pub mod packets;
+pub mod protocol;
+pub mod regions;
 pub mod registry;
+pub mod session;
 pub mod tasks;
+pub mod time;
 pub mod u24;
+pub mod world;
 
 #[derive(Clone)]
 pub struct Server {
@@ -30,37 +49,80 @@ pub struct Server {
     guid: u64,
     connections: Arc<Mutex<BiMap<SocketAddr, u64>>>,
     unique_connection_id: Arc<Mutex<u64>>,
+    sessions: Arc<Mutex<HashMap<u64, Session>>>,
+    outbox: Arc<Mutex<Vec<(u64, GamePacket)>>>,
+    outbox_notify: Arc<Notify>,
+    generator: Arc<dyn WorldGenerator>,
+    config: Arc<ServerConfig>,
+    /// Held while a save is written, so saves are written in the order they were taken
+    saving: Arc<AsyncMutex<()>>,
 }
 
 impl Server {
     pub fn new(addr: &str, mod_path: &str) -> Result<Server> {
-        let lua = Lua::new();
-        let registries = Arc::new(Mutex::new(Registries::default()));
-
-        registries
-            .lock()
-            .api_registry
-            .register("goldmine", goldmine_module(&lua, registries.clone())?);
-
-        install_modded_require(&lua, registries.clone())?;
-
-        let mut mod_string = String::new();
-        File::open(mod_path)?.read_to_string(&mut mod_string)?;
-        lua.load(mod_string).set_name(mod_path).exec()?;
+        Self::with_config(ServerConfig {
+            address: addr.to_owned(),
+            mod_path: mod_path.to_owned(),
+            ..Default::default()
+        })
+    }
 
+    pub fn with_config(config: ServerConfig) -> Result<Server> {
+        let mod_path = config.mod_path.as_str();
+        let lua = Arc::new(Mutex::new(Lua::new()));
+        let registries = Arc::new(Mutex::new(Registries::default()));
         let server = Server {
             data: Arc::new(Mutex::new(ServerData::default())),
-            lua: Arc::new(Mutex::new(lua)),
-            registries,
-            addr: addr.parse()?,
-            server_name: "MCCPP;Demo;A GoldMineMC server!".to_owned(),
+            lua: lua.clone(),
+            registries: registries.clone(),
+            addr: config.address.parse()?,
+            server_name: format!("MCCPP;Demo;{}", config.server_name),
             guid: rand::random(),
             connections: Arc::new(Mutex::new(BiMap::<SocketAddr, u64>::new())),
             unique_connection_id: Arc::new(Mutex::new(0)),
+            sessions: Arc::new(Mutex::new(HashMap::new())),
+            outbox: Arc::new(Mutex::new(Vec::new())),
+            outbox_notify: Arc::new(Notify::new()),
+            generator: create_generator(&config.generator, lua, registries),
+            config: Arc::new(config.clone()),
+            saving: Arc::new(AsyncMutex::new(())),
         };
 
         {
-            server.data.lock().gamemode = 1;
+            let lua = server.lua.lock();
+            let gm_module = goldmine_module(&lua, server.clone())?;
+            server
+                .registries
+                .lock()
+                .api_registry
+                .register("goldmine", gm_module);
+
+            install_modded_require(&lua, server.registries.clone())?;
+
+            let mut mod_string = String::new();
+            File::open(mod_path)?.read_to_string(&mut mod_string)?;
+            lua.load(mod_string).set_name(mod_path).exec()?;
+        }
+
+        let world_path = Path::new(&config.world_path);
+        if world_path.join("level.dat").exists() {
+            server.load_world()?;
+        } else {
+            let mut data = server.data.lock();
+            data.level_name = world_path
+                .file_name()
+                .map_or("world".to_owned(), |name| name.to_string_lossy().into_owned());
+            data.gamemode = config.gamemode;
+            data.random_tick_speed = config.random_tick_speed.min(MAX_RANDOM_TICK_SPEED);
+            data.seed = config.seed.unwrap_or_else(rand::random);
+            drop(data);
+            let spawn = config.spawn.unwrap_or((
+                WORLD_SIZE / 2,
+                CHUNK_HEIGHT as i32 / 2,
+                WORLD_SIZE / 2,
+            ));
+            let spawn = server.find_safe_spawn(spawn)?;
+            server.data.lock().spawn = spawn;
         }
 
         Ok(server)
@@ -70,9 +132,11 @@ impl Server {
         let (pl_tx, _pl_rx) = watch::channel("".to_owned());
         let pl_task =
             tokio::task::spawn(tasks::packet_listener::packet_listener(self.clone(), pl_tx));
-        let other_task = tokio::task::spawn(async {});
+        let tick_task = tokio::task::spawn(tasks::tick::tick(self.clone()));
 
-        tokio::join!(pl_task, other_task).0??;
+        let (pl_result, tick_result) = tokio::join!(pl_task, tick_task);
+        pl_result??;
+        tick_result??;
         Ok(())
     }
 }
@@ -1,14 +1,662 @@
-use crate::{data::EntityData, Server};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashSet},
+    fs,
+    path::Path,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use mlua::Function;
+use rand::Rng;
+
+use crate::{
+    block_log::{self, BlockLog, BlockLogSave, Change, LogEntry},
+    blocks::{block_properties, Blocks},
+    codes::BlockFace,
+    constants::DEFAULT_MTU,
+    data::{EntityData, PrimedTnt, Vec3, MAX_HEALTH, PLAYER_TYPE_ID},
+    game_packets::{ExplodeRecord, GamePacket, ItemStack},
+    nbt::Compound,
+    protocol::{default_protocol, Protocol},
+    regions::Region,
+    u24::u24,
+    world::{
+        chunk::Chunk,
+        edit::{BlockChanges, Cuboid, EditBlock},
+        explosion::{block_center, TNT_FUSE, TNT_POWER},
+        physics::PhysicsRules,
+        random_tick::{MAX_RANDOM_TICK_SPEED, PERSISTENT_LEAVES},
+        storage::{self, ChunkSave},
+        tile_entity::{validate_item, validate_sign_line, TileEntity},
+        BlockPos, World, CHUNK_HEIGHT, CHUNK_WIDTH,
+    },
+    Server,
+};
+
+/// How far away from the eyes players may edit blocks, a bit more than the client allows to
+/// account for movement lag
+const MAX_REACH: f32 = 8.0;
+
+/// Height of the eyes above the feet, player positions are at the eyes
+pub const PLAYER_EYE_HEIGHT: f32 = 1.62;
+
+/// How far from the spawn point a safe spot is searched
+const SPAWN_SEARCH_RADIUS: i32 = 16;
+
+/// File of the block log in the world directory
+const BLOCK_LOG_FILE: &str = "blocklog.jsonl";
+
+/// Connections not heard from for this long no longer keep their player logged in, clients that
+/// crashed don't say goodbye
+const SESSION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// World edits a player can undo
+const MAX_UNDO_STEPS: usize = 10;
+/// Chunks with more changed blocks than this are sent whole instead of block by block
+const CHUNK_RESEND_THRESHOLD: usize = 64;
+
+/// The parts of the world written by a save, taken out of the server data so the files are
+/// written without holding on to it
+struct WorldSave {
+    level: Compound,
+    entities: Compound,
+    chunks: ChunkSave,
+    block_log: BlockLogSave,
+}
+
+impl WorldSave {
+    fn write(&self, path: &Path) -> Result<()> {
+        fs::create_dir_all(path)?;
+        storage::write_level(&path.join("level.dat"), &self.level)?;
+        storage::write_entities(&path.join("entities.dat"), &self.entities)?;
+        self.chunks.write(&path.join("chunks.dat"))?;
+        self.block_log.write(&path.join(BLOCK_LOG_FILE))?;
+        Ok(())
+    }
+}
 
 impl Server {
-    pub fn add_player(&self) -> EntityData {
+    /// Adds the entity of a joining player at a safe spot near their spawn point
+    pub fn add_player(&self, username: &str) -> Result<EntityData> {
         let player = EntityData {
             id: rand::random(),
-            pos: (0.0, 0.0, 0.0),
+            type_id: PLAYER_TYPE_ID,
+            pos: self.find_player_spawn(username)?,
             rot: (0.0, 0.0, 0.0),
+            health: MAX_HEALTH,
+            extra: Default::default(),
         };
         self.data.lock().entities.push(player.clone());
-        player
+        Ok(player)
+    }
+
+    /// Runs the due block updates and sends the changed blocks to every player
+    pub fn tick_physics(&self) {
+        let changed = {
+            let mut guard = self.data.lock();
+            let data = &mut *guard;
+            let regions = &data.regions;
+            // Nothing falls or flows into a region from outside of it
+            data.world.tick_updates(&data.physics, |from, to| {
+                regions
+                    .iter()
+                    .any(|region| region.contains(to) && !region.contains(from))
+            })
+        };
+        for pos in changed {
+            self.broadcast_game_packet(self.block_update_packet(pos), None);
+        }
+    }
+
+    /// Runs the random ticks and sends the changed blocks to every player. Blocks with a random
+    /// tick handler are passed to the mods instead of the built-in rules.
+    pub fn tick_random(&self) -> Result<()> {
+        let handled: HashSet<u8> = self
+            .registries
+            .lock()
+            .rt_registry
+            .values()
+            .flat_map(|handler| handler.blocks.iter().copied())
+            .collect();
+        let (mut changed, picked) = {
+            let mut data = self.data.lock();
+            let speed = data.random_tick_speed;
+            data.world.random_tick(speed, &handled)
+        };
+        if !picked.is_empty() {
+            let replaced = self.run_random_tick_handlers(picked)?;
+            let mut data = self.data.lock();
+            for (pos, old, (id, aux)) in replaced {
+                // The block may have changed while the handlers ran
+                if data.world.get_block(pos) == Some(old) {
+                    data.world.set_block(pos, id, aux)?;
+                    changed.push(pos);
+                }
+            }
+        }
+        for pos in changed {
+            self.broadcast_game_packet(self.block_update_packet(pos), None);
+        }
+        Ok(())
+    }
+
+    /// Runs a tick of every furnace, sending the lit furnaces to every player and the slots and
+    /// progress to the players with the window open
+    pub fn tick_furnaces(&self) {
+        let mut changed = Vec::new();
+        let updates = self.data.lock().world.tick_furnaces(&mut changed);
+        for pos in changed {
+            self.broadcast_game_packet(self.block_update_packet(pos), None);
+        }
+        for update in updates {
+            for (viewer, window_id) in self.container_viewers(update.pos) {
+                if let Some(items) = &update.items {
+                    let mut content = GamePacket::SCContainerSetContent {
+                        window_id,
+                        items_len: 0,
+                        items: items.clone(),
+                    };
+                    content.sync_lengths();
+                    self.send_game_packet(viewer, content);
+                }
+                for (property, value) in &update.window_data {
+                    self.send_game_packet(
+                        viewer,
+                        GamePacket::SCContainerSetData {
+                            window_id,
+                            property: *property,
+                            value: *value,
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    /// Lights the TNT block at a position, it turns into a primed TNT entity
+    pub fn ignite_tnt(&self, connection_id: u64, pos: BlockPos) -> Result<()> {
+        self.check_reach(connection_id, pos)?;
+        self.check_protection(connection_id, pos)?;
+        let username = self.get_username(connection_id).context("Not logged in")?;
+        let tnt = {
+            let mut data = self.data.lock();
+            let Some((id, aux)) = data
+                .world
+                .get_block(pos)
+                .filter(|(id, _)| *id == Blocks::Tnt.id())
+            else {
+                bail!("Block {:?} is not TNT", pos);
+            };
+            data.world.set_block(pos, Blocks::Air.id(), 0)?;
+            data.block_log.record(LogEntry::new(
+                &username,
+                pos,
+                Change::Block {
+                    old: (id, aux),
+                    new: (Blocks::Air.id(), 0),
+                    contents: Vec::new(),
+                    lines: None,
+                },
+            ));
+            let (x, _, z) = block_center(pos);
+            let tnt = PrimedTnt::new((x, pos.1 as f32, z), TNT_FUSE, Some(username));
+            data.primed_tnt.push(tnt.clone());
+            tnt
+        };
+        self.broadcast_game_packet(self.block_update_packet(pos), None);
+        self.broadcast_game_packet(tnt.add_packet(), None);
+        Ok(())
+    }
+
+    /// Burns down the fuses of the primed TNT and explodes the TNT whose fuse ran out
+    pub fn tick_tnt(&self) {
+        let exploding: Vec<PrimedTnt> = {
+            let mut data = self.data.lock();
+            for tnt in &mut data.primed_tnt {
+                tnt.fuse = tnt.fuse.saturating_sub(1);
+            }
+            let (exploding, burning) = std::mem::take(&mut data.primed_tnt)
+                .into_iter()
+                .partition(|tnt| tnt.fuse == 0);
+            data.primed_tnt = burning;
+            exploding
+        };
+        for tnt in exploding {
+            self.broadcast_game_packet(GamePacket::SCRemoveEntity { entity_id: tnt.id }, None);
+            self.explode(tnt.pos, TNT_POWER, tnt.igniter.as_deref());
+        }
+    }
+
+    /// Runs an explosion: destroys blocks unless the `explosion_damage` rule is off, lights the
+    /// TNT caught in it, drops some of the destroyed blocks and the container contents, then
+    /// hurts and pushes away the players in range. The destroyed blocks are logged under
+    /// `igniter`.
+    pub fn explode(&self, center: Vec3, power: f32, igniter: Option<&str>) {
+        let mut changed = Vec::new();
+        let mut spawned = Vec::new();
+        let mut hurt = Vec::new();
+        let explosion = {
+            let mut guard = self.data.lock();
+            let data = &mut *guard;
+            let break_blocks = data.physics.explosion_damage;
+            let regions = &data.regions;
+            let explosion = data.world.explode(
+                center,
+                power,
+                break_blocks,
+                |pos| regions.iter().any(|region| region.contains(pos)),
+                &mut changed,
+            );
+
+            let mut rng = rand::thread_rng();
+            let mut drops: Vec<(Vec3, ItemStack)> = explosion
+                .contents
+                .iter()
+                .flat_map(|(pos, items)| {
+                    items
+                        .iter()
+                        .filter(|item| !item.is_empty())
+                        .map(|item| (block_center(*pos), *item))
+                })
+                .collect();
+            for &(pos, id, aux) in &explosion.destroyed {
+                // Bigger explosions destroy more of what they hit
+                if !rng.gen_bool(f64::from(1.0 / power.max(1.0))) {
+                    continue;
+                }
+                let drop = block_properties(id)
+                    .and_then(|properties| properties.drops.roll(id, aux, &mut rng));
+                if let Some((id, aux, count)) = drop {
+                    drops.push((block_center(pos), ItemStack { id, count, aux }));
+                }
+            }
+            for (pos, item) in drops {
+                let entity = EntityData::item(pos, item);
+                spawned.extend(entity.add_item_packet());
+                data.entities.push(entity);
+            }
+            for &pos in &explosion.tnt {
+                let (x, _, z) = block_center(pos);
+                let fuse = TNT_FUSE / 8 + rng.gen_range(0..TNT_FUSE / 4);
+                let tnt = PrimedTnt::new((x, pos.1 as f32, z), fuse, igniter.map(str::to_owned));
+                spawned.push(tnt.add_packet());
+                data.primed_tnt.push(tnt);
+            }
+            if let Some(igniter) = igniter {
+                let destroyed = explosion
+                    .destroyed
+                    .iter()
+                    .copied()
+                    .chain(explosion.tnt.iter().map(|pos| (*pos, Blocks::Tnt.id(), 0)));
+                for (pos, id, aux) in destroyed {
+                    let contents = explosion
+                        .contents
+                        .iter()
+                        .find(|(container, _)| *container == pos)
+                        .map(|(_, items)| items.clone())
+                        .unwrap_or_default();
+                    let lines = explosion
+                        .signs
+                        .iter()
+                        .find(|(sign, _)| *sign == pos)
+                        .map(|(_, lines)| lines.clone());
+                    data.block_log.record(LogEntry::new(
+                        igniter,
+                        pos,
+                        Change::Block {
+                            old: (id, aux),
+                            new: (Blocks::Air.id(), 0),
+                            contents,
+                            lines,
+                        },
+                    ));
+                }
+            }
+
+            let survival = data.gamemode == 0;
+            for player in data
+                .entities
+                .iter_mut()
+                .filter(|entity| entity.type_id == PLAYER_TYPE_ID)
+            {
+                let (x, y, z) = player.pos;
+                let feet = (x, y - PLAYER_EYE_HEIGHT, z);
+                let Some((damage, knockback)) = explosion.impact(&data.world, feet) else {
+                    continue;
+                };
+                if survival {
+                    player.health = (player.health - i16::from(damage)).max(0);
+                }
+                hurt.push((player.id, survival.then_some(player.health), knockback));
+            }
+            explosion
+        };
+
+        let (center_x, center_y, center_z) = (
+            center.0.floor() as i32,
+            center.1.floor() as i32,
+            center.2.floor() as i32,
+        );
+        let mut packet = GamePacket::SCExplode {
+            pos_x: center.0,
+            pos_y: center.1,
+            pos_z: center.2,
+            radius: power,
+            records_len: 0,
+            records: explosion
+                .destroyed
+                .iter()
+                .map(|((x, y, z), _, _)| ExplodeRecord {
+                    x: (x - center_x) as i8,
+                    y: (y - center_y) as i8,
+                    z: (z - center_z) as i8,
+                })
+                .collect(),
+        };
+        packet.sync_lengths();
+        self.broadcast_game_packet(packet, None);
+        for pos in changed {
+            self.broadcast_game_packet(self.block_update_packet(pos), None);
+        }
+        for packet in spawned {
+            self.broadcast_game_packet(packet, None);
+        }
+        for (pos, _, _) in &explosion.destroyed {
+            self.close_removed_container(*pos);
+        }
+        for (entity_id, health, (x, y, z)) in hurt {
+            let Some(connection_id) = self.connection_of_entity(entity_id) else {
+                continue;
+            };
+            if let Some(health) = health {
+                self.send_game_packet(
+                    connection_id,
+                    GamePacket::SCSetHealth {
+                        health: health as u8,
+                    },
+                );
+            }
+            // The client takes the motion in 1/8000 blocks per tick
+            let speed = |value: f32| (value * 8000.0).clamp(-32768.0, 32767.0) as i16;
+            self.send_game_packet(
+                connection_id,
+                GamePacket::SCSetEntityMotion {
+                    entity_id,
+                    speed_x: speed(x),
+                    speed_y: speed(y),
+                    speed_z: speed(z),
+                },
+            );
+        }
+    }
+
+    /// Calls the random tick handlers of the picked blocks, returning the position, the old and
+    /// the new block of every replaced one
+    #[allow(clippy::type_complexity)]
+    fn run_random_tick_handlers(
+        &self,
+        picked: Vec<(BlockPos, u8, u8)>,
+    ) -> Result<Vec<(BlockPos, (u8, u8), (u8, u8))>> {
+        // The handlers are taken out so the registries are unlocked while they run
+        let handlers: Vec<(HashSet<u8>, Function)> = {
+            let registries = self.registries.lock();
+            let lua = self.lua.lock();
+            registries
+                .rt_registry
+                .values()
+                .map(|handler| {
+                    Ok((
+                        handler.blocks.clone(),
+                        lua.registry_value(&handler.callback)?,
+                    ))
+                })
+                .collect::<Result<_>>()?
+        };
+        let _lua = self.lua.lock();
+        let mut replaced = Vec::new();
+        'blocks: for ((x, y, z), id, aux) in picked {
+            let mut block = (id, aux);
+            for (blocks, callback) in &handlers {
+                if !blocks.contains(&block.0) {
+                    continue;
+                }
+                let result: mlua::Result<(Option<u8>, Option<u8>)> =
+                    callback.call((x, y, z, block.0, block.1));
+                match result {
+                    Ok((Some(new_id), new_aux)) => block = (new_id, new_aux.unwrap_or(0)),
+                    Ok((None, _)) => (),
+                    Err(err) => {
+                        eprintln!("Random tick handler failed at {} {} {}: {}", x, y, z, err);
+                        continue 'blocks;
+                    }
+                }
+            }
+            if block != (id, aux) {
+                replaced.push(((x, y, z), (id, aux), block));
+            }
+        }
+        Ok(replaced)
+    }
+
+    pub fn get_random_tick_speed(&self) -> u32 {
+        self.data.lock().random_tick_speed
+    }
+
+    pub fn set_random_tick_speed(&self, speed: u32) -> Result<()> {
+        if speed > MAX_RANDOM_TICK_SPEED {
+            bail!("The random tick speed is 0 to {}", MAX_RANDOM_TICK_SPEED);
+        }
+        self.data.lock().random_tick_speed = speed;
+        Ok(())
+    }
+
+    pub fn get_physics_rules(&self) -> PhysicsRules {
+        self.data.lock().physics.clone()
+    }
+
+    /// Turns a physics rule on or off, returns false for unknown rules
+    pub fn set_physics_rule(&self, name: &str, enabled: bool) -> bool {
+        match self.data.lock().physics.get_mut(name) {
+            Some(rule) => {
+                *rule = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn get_spawn(&self) -> BlockPos {
+        self.data.lock().spawn
+    }
+
+    /// Moves the world spawn, players without a spawn point of their own are told about it
+    pub fn set_spawn(&self, pos: BlockPos) {
+        let own_spawns: HashSet<String> = {
+            let mut data = self.data.lock();
+            data.spawn = pos;
+            data.player_spawns.keys().cloned().collect()
+        };
+        let connection_ids: Vec<u64> = self
+            .sessions
+            .lock()
+            .iter()
+            .filter(|(_, session)| session.entity_id.is_some())
+            .filter(|(_, session)| {
+                session
+                    .username
+                    .as_ref()
+                    .is_some_and(|username| !own_spawns.contains(username))
+            })
+            .map(|(connection_id, _)| *connection_id)
+            .collect();
+        for connection_id in connection_ids {
+            self.send_game_packet(connection_id, Self::spawn_packet(pos));
+        }
+    }
+
+    /// The spawn point of a player, the world spawn if they haven't set their own
+    pub fn get_player_spawn(&self, username: &str) -> BlockPos {
+        let data = self.data.lock();
+        data.player_spawns
+            .get(username)
+            .copied()
+            .unwrap_or(data.spawn)
+    }
+
+    /// Sets or with `None` removes the spawn point of a player
+    pub fn set_player_spawn(&self, username: &str, pos: Option<BlockPos>) {
+        let mut data = self.data.lock();
+        match pos {
+            Some(pos) => data.player_spawns.insert(username.to_owned(), pos),
+            None => data.player_spawns.remove(username),
+        };
+    }
+
+    /// Where a player appears when joining or respawning, a safe spot near their spawn point
+    pub fn find_player_spawn(&self, username: &str) -> Result<Vec3> {
+        let (x, y, z) = self.find_safe_spawn(self.get_player_spawn(username))?;
+        Ok((x as f32 + 0.5, y as f32 + PLAYER_EYE_HEIGHT, z as f32 + 0.5))
+    }
+
+    pub fn spawn_packet((x, y, z): BlockPos) -> GamePacket {
+        GamePacket::SCSetSpawnPosition {
+            pos_x: x as u32,
+            pos_z: z as u32,
+            pos_y: y.clamp(0, u8::MAX.into()) as u8,
+        }
+    }
+
+    /// The nearest spot to `pos` where a player stands on solid ground with room for their
+    /// head, searched on the surface of the columns around it. Falls back to `pos` itself, e.g.
+    /// in void worlds.
+    pub fn find_safe_spawn(&self, pos: BlockPos) -> Result<BlockPos> {
+        let (center_x, _, center_z) = pos;
+        let chunk_width = CHUNK_WIDTH as i32;
+        let chunks_x = (center_x - SPAWN_SEARCH_RADIUS).div_euclid(chunk_width)
+            ..=(center_x + SPAWN_SEARCH_RADIUS).div_euclid(chunk_width);
+        for chunk_x in chunks_x {
+            let chunks_z = (center_z - SPAWN_SEARCH_RADIUS).div_euclid(chunk_width)
+                ..=(center_z + SPAWN_SEARCH_RADIUS).div_euclid(chunk_width);
+            for chunk_z in chunks_z {
+                self.load_chunk(chunk_x, chunk_z)?;
+            }
+        }
+
+        let data = self.data.lock();
+        for radius in 0..=SPAWN_SEARCH_RADIUS {
+            for dx in -radius..=radius {
+                for dz in -radius..=radius {
+                    if dx.abs() != radius && dz.abs() != radius {
+                        continue;
+                    }
+                    if let Some(spot) = safe_spot(&data.world, center_x + dx, center_z + dz) {
+                        return Ok(spot);
+                    }
+                }
+            }
+        }
+        Ok(pos)
+    }
+
+    /// Loads the world directory from the config
+    pub fn load_world(&self) -> Result<()> {
+        let path = Path::new(&self.config.world_path);
+        let level = storage::read_level(&path.join("level.dat"))
+            .with_context(|| format!("Loading {:?}", path.join("level.dat")))?;
+        let mut world = World::new();
+        let chunks_path = path.join("chunks.dat");
+        if chunks_path.exists() {
+            storage::read_chunks(&chunks_path, &mut world)
+                .with_context(|| format!("Loading {:?}", chunks_path))?;
+        }
+        let entities_path = path.join("entities.dat");
+        let entities = if entities_path.exists() {
+            storage::read_entities(&entities_path)
+                .with_context(|| format!("Loading {:?}", entities_path))?
+        } else {
+            Default::default()
+        };
+        let block_log_path = path.join(BLOCK_LOG_FILE);
+        let block_log = if block_log_path.exists() {
+            BlockLog::load(&block_log_path)
+                .with_context(|| format!("Loading {:?}", block_log_path))?
+        } else {
+            BlockLog::default()
+        };
+
+        let mut data = self.data.lock();
+        data.apply_level(level)?;
+        data.world = world;
+        data.apply_entities(entities);
+        data.block_log = block_log;
+        Ok(())
+    }
+
+    /// Saves the world into the world directory from the config, waiting for a running autosave
+    /// to finish first
+    pub async fn save_world(&self) -> Result<()> {
+        let saving = self.saving.clone().lock_owned().await;
+        let save = self.take_world_save()?;
+        let server = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let _saving = saving;
+            server.write_world_save(save)
+        })
+        .await?
+    }
+
+    /// Starts saving the world in the background, unless the last save is still being written
+    pub fn autosave(&self) {
+        let Ok(saving) = self.saving.clone().try_lock_owned() else {
+            eprintln!("Skipping the autosave, the last one is still being written");
+            return;
+        };
+        let save = match self.take_world_save() {
+            Ok(save) => save,
+            Err(err) => {
+                eprintln!("Failed to save the world: {:?}", err);
+                return;
+            }
+        };
+        let server = self.clone();
+        tokio::task::spawn_blocking(move || {
+            let _saving = saving;
+            if let Err(err) = server.write_world_save(save) {
+                eprintln!("Failed to save the world: {:?}", err);
+            }
+        });
+    }
+
+    /// Takes everything a save writes while holding the data lock: the level, the entities, the
+    /// chunks changed since the last save and the new block log entries
+    fn take_world_save(&self) -> Result<WorldSave> {
+        let retention = (self.config.block_log_days > 0)
+            .then(|| self.config.block_log_days.saturating_mul(24 * 60 * 60));
+        let mut guard = self.data.lock();
+        let data = &mut *guard;
+        Ok(WorldSave {
+            level: data.to_level()?,
+            entities: data.to_entities(),
+            chunks: ChunkSave::take_dirty(&mut data.world),
+            block_log: data.block_log.take_unsaved(retention),
+        })
+    }
+
+    /// Writes a save taken by `take_world_save`. If that fails, its chunks and block log entries
+    /// are written again by the next save.
+    fn write_world_save(&self, save: WorldSave) -> Result<()> {
+        let path = Path::new(&self.config.world_path);
+        let result = save.write(path);
+        if result.is_err() {
+            let mut data = self.data.lock();
+            for (chunk_x, chunk_z) in save.chunks.positions() {
+                if let Some(chunk) = data.world.get_chunk_mut(chunk_x, chunk_z) {
+                    chunk.mark_dirty();
+                }
+            }
+            data.block_log.rewrite_on_save();
+        }
+        result
     }
 
     pub fn get_seed(&self) -> u32 {
@@ -18,4 +666,1097 @@ impl Server {
     pub fn get_gamemode(&self) -> u32 {
         self.data.lock().gamemode
     }
+
+    pub fn get_time(&self) -> i64 {
+        self.data.lock().time
+    }
+
+    pub fn is_time_frozen(&self) -> bool {
+        self.data.lock().time_frozen
+    }
+
+    /// Sets the world time and sends it to every player
+    pub fn set_time(&self, time: i64) {
+        self.data.lock().time = time.max(0);
+        self.broadcast_game_packet(self.time_packet(), None);
+    }
+
+    /// Stops or resumes the day-night cycle
+    pub fn set_time_frozen(&self, frozen: bool) {
+        self.data.lock().time_frozen = frozen;
+        self.broadcast_game_packet(self.time_packet(), None);
+    }
+
+    /// Advances the world time by a tick unless the cycle is frozen
+    pub fn advance_time(&self) {
+        let mut data = self.data.lock();
+        if !data.time_frozen {
+            data.time += 1;
+        }
+    }
+
+    pub fn time_packet(&self) -> GamePacket {
+        GamePacket::SCSetTime {
+            time: self.get_time() as u32,
+        }
+    }
+
+    pub fn get_block(&self, pos: BlockPos) -> Option<(u8, u8)> {
+        self.data.lock().world.get_block(pos)
+    }
+
+    pub fn set_block(&self, pos: BlockPos, id: u8, aux: u8) -> Result<()> {
+        self.data.lock().world.set_block(pos, id, aux)
+    }
+
+    pub fn move_entity(&self, entity_id: u32, pos: Vec3, rot: Vec3) {
+        let mut data = self.data.lock();
+        if let Some(entity) = data
+            .entities
+            .iter_mut()
+            .find(|entity| entity.id == entity_id)
+        {
+            entity.pos = pos;
+            entity.rot = rot;
+        }
+    }
+
+    /// Refills the health of an entity, e.g. when a player respawns
+    pub fn heal_entity(&self, entity_id: u32) {
+        let mut data = self.data.lock();
+        if let Some(entity) = data
+            .entities
+            .iter_mut()
+            .find(|entity| entity.id == entity_id)
+        {
+            entity.health = MAX_HEALTH;
+        }
+    }
+
+    pub fn get_entity_pos(&self, entity_id: u32) -> Option<Vec3> {
+        let data = self.data.lock();
+        data.entities
+            .iter()
+            .find(|entity| entity.id == entity_id)
+            .map(|entity| entity.pos)
+    }
+
+    /// Generates the chunk if it doesn't exist yet. Returns false outside of the world.
+    pub fn load_chunk(&self, chunk_x: i32, chunk_z: i32) -> Result<bool> {
+        let seed = {
+            let data = self.data.lock();
+            if data.world.get_chunk(chunk_x, chunk_z).is_some() {
+                return Ok(true);
+            }
+            if !World::chunk_in_bounds(chunk_x, chunk_z) {
+                return Ok(false);
+            }
+            data.seed
+        };
+        // Generators may call into Lua, which must not happen while holding the data lock
+        let chunk = self.generator.generate_chunk(seed, chunk_x, chunk_z)?;
+        let mut data = self.data.lock();
+        data.world
+            .get_or_generate_chunk(chunk_x, chunk_z, |_, _| chunk);
+        Ok(true)
+    }
+
+    /// The chunk encoded for `SCChunkDataPacket`, generating it first if needed. `None` outside
+    /// of the world.
+    pub fn get_chunk_data(&self, chunk_x: i32, chunk_z: i32) -> Result<Option<Vec<u8>>> {
+        if !self.load_chunk(chunk_x, chunk_z)? {
+            return Ok(None);
+        }
+        let data = self.data.lock();
+        Ok(data
+            .world
+            .get_chunk(chunk_x, chunk_z)
+            .map(Chunk::to_network_bytes))
+    }
+
+    /// Remembers a chunk request, it is answered by the tick task
+    pub fn queue_chunk(&self, connection_id: u64, chunk_x: i32, chunk_z: i32) {
+        if !World::chunk_in_bounds(chunk_x, chunk_z) {
+            return;
+        }
+        let mut sessions = self.sessions.lock();
+        if let Some(session) = sessions.get_mut(&connection_id) {
+            if !session.chunk_queue.contains(&(chunk_x, chunk_z)) {
+                session.chunk_queue.push((chunk_x, chunk_z));
+            }
+        }
+    }
+
+    /// Takes up to `count` queued chunks of every connection, closest to the player first
+    pub fn take_queued_chunks(&self, count: usize) -> Vec<(u64, Vec<(i32, i32)>)> {
+        let positions: Vec<(u32, Vec3)> = self
+            .data
+            .lock()
+            .entities
+            .iter()
+            .map(|entity| (entity.id, entity.pos))
+            .collect();
+        let mut sessions = self.sessions.lock();
+        let mut taken = Vec::new();
+        for (connection_id, session) in sessions.iter_mut() {
+            if session.chunk_queue.is_empty() {
+                continue;
+            }
+            let (x, _, z) = positions
+                .iter()
+                .find(|(id, _)| Some(*id) == session.entity_id)
+                .map_or((0.0, 0.0, 0.0), |(_, pos)| *pos);
+            let distance = |(chunk_x, chunk_z): &(i32, i32)| {
+                let dx = (*chunk_x * 16 + 8) as f32 - x;
+                let dz = (*chunk_z * 16 + 8) as f32 - z;
+                dx * dx + dz * dz
+            };
+            // Farthest first, so the closest ones can be popped off the end
+            session
+                .chunk_queue
+                .sort_by(|a, b| distance(b).total_cmp(&distance(a)));
+            let split = session.chunk_queue.len().saturating_sub(count);
+            let chunks = session.chunk_queue.split_off(split);
+            taken.push((*connection_id, chunks.into_iter().rev().collect()));
+        }
+        taken
+    }
+
+    pub fn get_entity_id(&self, connection_id: u64) -> Option<u32> {
+        self.sessions.lock().get(&connection_id)?.entity_id
+    }
+
+    /// The connection of the player with an entity id
+    fn connection_of_entity(&self, entity_id: u32) -> Option<u64> {
+        self.sessions
+            .lock()
+            .iter()
+            .find(|(_, session)| session.entity_id == Some(entity_id))
+            .map(|(connection_id, _)| *connection_id)
+    }
+
+    pub fn get_username(&self, connection_id: u64) -> Option<String> {
+        self.sessions.lock().get(&connection_id)?.username.clone()
+    }
+
+    /// Whether another connection that is still active is logged in as `username`. A timed out
+    /// connection logged in with the name is ended, so the player can join again.
+    pub fn is_logged_in_elsewhere(&self, connection_id: u64, username: &str) -> bool {
+        let timed_out = {
+            let sessions = self.sessions.lock();
+            let Some((other_id, session)) = sessions.iter().find(|(other_id, session)| {
+                **other_id != connection_id && session.username.as_deref() == Some(username)
+            }) else {
+                return false;
+            };
+            if session.last_seen.elapsed() < SESSION_TIMEOUT {
+                return true;
+            }
+            *other_id
+        };
+        self.end_session(timed_out);
+        false
+    }
+
+    /// Notes that a datagram of the connection arrived
+    pub fn mark_seen(&self, connection_id: u64) {
+        if let Some(session) = self.sessions.lock().get_mut(&connection_id) {
+            session.last_seen = Instant::now();
+        }
+    }
+
+    /// Forgets a connection and removes its player, after the client disconnected
+    pub fn end_session(&self, connection_id: u64) {
+        self.connections.lock().remove_by_right(&connection_id);
+        let session = self.sessions.lock().remove(&connection_id);
+        if let Some(entity_id) = session.and_then(|session| session.entity_id) {
+            self.data
+                .lock()
+                .entities
+                .retain(|entity| entity.id != entity_id);
+        }
+    }
+
+    /// The block the feet of a player are in
+    pub fn get_player_block_pos(&self, connection_id: u64) -> Option<BlockPos> {
+        let (x, y, z) = self.get_entity_pos(self.get_entity_id(connection_id)?)?;
+        Some((
+            x.floor() as i32,
+            (y - PLAYER_EYE_HEIGHT).floor() as i32,
+            z.floor() as i32,
+        ))
+    }
+
+    /// Where a block placed against `clicked` ends up. Replaceable blocks like tall grass are
+    /// replaced themselves instead of placing next to them.
+    pub fn placement_target(&self, clicked: BlockPos, face: BlockFace) -> BlockPos {
+        let replaceable = self
+            .get_block(clicked)
+            .and_then(|(id, _)| Blocks::from_id(id))
+            .is_some_and(|block| block.properties().replaceable);
+        match face.offset() {
+            Some((dx, dy, dz)) if !replaceable => (clicked.0 + dx, clicked.1 + dy, clicked.2 + dz),
+            _ => clicked,
+        }
+    }
+
+    /// Checks that the player of a connection can reach the center of a block
+    fn check_reach(&self, connection_id: u64, (x, y, z): BlockPos) -> Result<()> {
+        let entity_id = self.get_entity_id(connection_id).context("Not logged in")?;
+        let (player_x, player_y, player_z) = self
+            .get_entity_pos(entity_id)
+            .context("Player entity doesn't exist")?;
+        let dx = x as f32 + 0.5 - player_x;
+        let dy = y as f32 + 0.5 - player_y;
+        let dz = z as f32 + 0.5 - player_z;
+        if dx * dx + dy * dy + dz * dz > MAX_REACH * MAX_REACH {
+            bail!("Block {:?} is out of reach", (x, y, z));
+        }
+        Ok(())
+    }
+
+    pub fn is_op(&self, username: &str) -> bool {
+        self.config.ops.iter().any(|op| op == username)
+    }
+
+    /// Whether a player may edit a block: ops may edit every block, the others only blocks
+    /// outside of the regions they aren't a member of
+    pub fn can_edit(&self, username: &str, pos: BlockPos) -> bool {
+        self.is_op(username)
+            || self
+                .data
+                .lock()
+                .regions
+                .iter()
+                .all(|region| !region.contains(pos) || region.can_edit(username))
+    }
+
+    fn check_protection(&self, connection_id: u64, pos: BlockPos) -> Result<()> {
+        let username = self.get_username(connection_id).context("Not logged in")?;
+        if !self.can_edit(&username, pos) {
+            let names: Vec<String> = self
+                .regions_at(pos)
+                .into_iter()
+                .filter(|region| !region.can_edit(&username))
+                .map(|region| region.name)
+                .collect();
+            bail!(
+                "Block {:?} is protected by region {}",
+                pos,
+                names.join(", ")
+            );
+        }
+        Ok(())
+    }
+
+    pub fn get_regions(&self) -> Vec<Region> {
+        self.data.lock().regions.clone()
+    }
+
+    pub fn get_region(&self, name: &str) -> Option<Region> {
+        let data = self.data.lock();
+        data.regions
+            .iter()
+            .find(|region| region.name == name)
+            .cloned()
+    }
+
+    pub fn regions_at(&self, pos: BlockPos) -> Vec<Region> {
+        let data = self.data.lock();
+        data.regions
+            .iter()
+            .filter(|region| region.contains(pos))
+            .cloned()
+            .collect()
+    }
+
+    /// Adds a region, the name must not be taken yet
+    pub fn create_region(&self, region: Region) -> Result<()> {
+        region.validate()?;
+        let mut data = self.data.lock();
+        if data.regions.iter().any(|other| other.name == region.name) {
+            bail!("Region {} already exists", region.name);
+        }
+        data.regions.push(region);
+        Ok(())
+    }
+
+    /// Returns false if there is no region with the name
+    pub fn remove_region(&self, name: &str) -> bool {
+        let mut data = self.data.lock();
+        let count = data.regions.len();
+        data.regions.retain(|region| region.name != name);
+        data.regions.len() != count
+    }
+
+    pub fn update_region(&self, name: &str, update: impl FnOnce(&mut Region)) -> Result<()> {
+        let mut data = self.data.lock();
+        let Some(region) = data.regions.iter_mut().find(|region| region.name == name) else {
+            bail!("There is no region {}", name);
+        };
+        update(region);
+        Ok(())
+    }
+
+    /// Validates and applies a block placed by a player. Placed leaves never decay.
+    pub fn place_block(&self, connection_id: u64, pos: BlockPos, id: u8, aux: u8) -> Result<()> {
+        if !World::in_bounds(pos) {
+            bail!("Block {:?} is outside of the world", pos);
+        }
+        self.check_reach(connection_id, pos)?;
+        self.check_protection(connection_id, pos)?;
+        let Some(block) = Blocks::from_id(id).filter(|block| *block != Blocks::Air) else {
+            bail!("Can't place block id {}", id);
+        };
+        if !block.properties().is_valid_aux(aux) {
+            bail!("Invalid aux value {} for {}", aux, block.name());
+        }
+        let aux = if block == Blocks::Leaves {
+            aux | PERSISTENT_LEAVES
+        } else {
+            aux
+        };
+        let username = self.get_username(connection_id).context("Not logged in")?;
+        let mut data = self.data.lock();
+        let Some(old) = data.world.get_block(pos) else {
+            bail!("Chunk of block {:?} isn't loaded", pos);
+        };
+        if !block_properties(old.0).is_some_and(|current| current.replaceable) {
+            bail!("Block {:?} is occupied", pos);
+        }
+        data.world.set_block(pos, id, aux)?;
+        data.block_log.record(LogEntry::new(
+            &username,
+            pos,
+            Change::Block {
+                old,
+                new: (id, aux),
+                contents: Vec::new(),
+                lines: None,
+            },
+        ));
+        Ok(())
+    }
+
+    /// Validates and applies a block broken by a player
+    pub fn remove_block(&self, connection_id: u64, pos: BlockPos) -> Result<()> {
+        if !World::in_bounds(pos) {
+            bail!("Block {:?} is outside of the world", pos);
+        }
+        self.check_reach(connection_id, pos)?;
+        self.check_protection(connection_id, pos)?;
+        let username = self.get_username(connection_id).context("Not logged in")?;
+        let mut data = self.data.lock();
+        let Some(old) = data.world.get_block(pos) else {
+            bail!("Chunk of block {:?} isn't loaded", pos);
+        };
+        match block_properties(old.0) {
+            Some(properties) if old.0 != 0 && properties.is_breakable() => (),
+            _ => bail!("Block {:?} can't be broken", pos),
+        }
+        let tile_entity = data.world.get_tile_entity(pos);
+        let contents = tile_entity
+            .and_then(TileEntity::items)
+            .map(<[ItemStack]>::to_vec)
+            .unwrap_or_default();
+        let lines = tile_entity.and_then(TileEntity::lines).cloned();
+        data.world.set_block(pos, Blocks::Air.id(), 0)?;
+        data.block_log.record(LogEntry::new(
+            &username,
+            pos,
+            Change::Block {
+                old,
+                new: (Blocks::Air.id(), 0),
+                contents,
+                lines,
+            },
+        ));
+        Ok(())
+    }
+
+    /// Validates and stores the text a player wrote on a sign
+    pub fn update_sign(&self, connection_id: u64, pos: BlockPos, lines: [String; 4]) -> Result<()> {
+        self.check_reach(connection_id, pos)?;
+        self.check_protection(connection_id, pos)?;
+        for line in &lines {
+            validate_sign_line(line)?;
+        }
+        let mut data = self.data.lock();
+        let block = data
+            .world
+            .get_block(pos)
+            .and_then(|(id, _)| Blocks::from_id(id));
+        if !matches!(block, Some(Blocks::SignPost | Blocks::WallSign)) {
+            bail!("Block {:?} is not a sign", pos);
+        }
+        data.world.set_tile_entity(pos, TileEntity::Sign { lines });
+        Ok(())
+    }
+
+    /// The packet sending the data of the tile entity at a position to the clients, `None` if
+    /// there is none or the clients don't know its kind
+    pub fn tile_entity_packet(&self, pos: BlockPos) -> Option<GamePacket> {
+        match self.data.lock().world.get_tile_entity(pos)? {
+            TileEntity::Sign { lines } => Some(GamePacket::sign_update(pos, lines.clone())),
+            _ => None,
+        }
+    }
+
+    /// The tile entity packets of a chunk, sent after the chunk itself
+    pub fn chunk_tile_entity_packets(&self, chunk_x: i32, chunk_z: i32) -> Vec<GamePacket> {
+        let positions: Vec<BlockPos> = self
+            .data
+            .lock()
+            .world
+            .chunk_tile_entities(chunk_x, chunk_z)
+            .map(|(pos, _)| pos)
+            .collect();
+        positions
+            .into_iter()
+            .filter_map(|pos| self.tile_entity_packet(pos))
+            .collect()
+    }
+
+    /// Opens the container at a position for a player, returning the packets opening the window
+    /// and filling it
+    pub fn open_container(&self, connection_id: u64, pos: BlockPos) -> Result<Vec<GamePacket>> {
+        self.check_reach(connection_id, pos)?;
+        self.check_protection(connection_id, pos)?;
+        let (window_type, items, window_data) = {
+            let mut data = self.data.lock();
+            let Some((id, _)) = data.world.get_block(pos) else {
+                bail!("Chunk of block {:?} isn't loaded", pos);
+            };
+            if data.world.get_tile_entity(pos).is_none() {
+                let Some(tile_entity) = TileEntity::for_block(id) else {
+                    bail!("Block {:?} is not a container", pos);
+                };
+                data.world.set_tile_entity(pos, tile_entity);
+            }
+            let tile_entity = data.world.get_tile_entity(pos);
+            match tile_entity.and_then(|tile_entity| {
+                Some((
+                    tile_entity.window_type()?,
+                    tile_entity.items()?.to_vec(),
+                    tile_entity.window_data(),
+                ))
+            }) {
+                Some(container) => container,
+                None => bail!("Block {:?} is not a container", pos),
+            }
+        };
+
+        let mut sessions = self.sessions.lock();
+        let session = sessions.get_mut(&connection_id).context("Not logged in")?;
+        // 0 is the player's own inventory
+        session.window_id = session.window_id % 100 + 1;
+        session.open_container = Some((session.window_id, pos));
+        session.taken_items.clear();
+        let mut open = GamePacket::SCContainerOpen {
+            window_id: session.window_id,
+            window_type,
+            slot: items.len() as u8,
+            title_len: 0,
+            title: String::new(),
+        };
+        open.sync_lengths();
+        let mut content = GamePacket::SCContainerSetContent {
+            window_id: session.window_id,
+            items_len: 0,
+            items,
+        };
+        content.sync_lengths();
+        let mut packets = vec![open, content];
+        packets.extend(window_data.into_iter().map(|(property, value)| {
+            GamePacket::SCContainerSetData {
+                window_id: session.window_id,
+                property,
+                value,
+            }
+        }));
+        Ok(packets)
+    }
+
+    pub fn close_container(&self, connection_id: u64, window_id: u8) {
+        if let Some(session) = self.sessions.lock().get_mut(&connection_id) {
+            if session
+                .open_container
+                .is_some_and(|(open_id, _)| open_id == window_id)
+            {
+                session.open_container = None;
+                session.taken_items.clear();
+            }
+        }
+    }
+
+    /// Connection and window ids of the players viewing the container at a position
+    fn container_viewers(&self, pos: BlockPos) -> Vec<(u64, u8)> {
+        self.sessions
+            .lock()
+            .iter()
+            .filter_map(|(connection_id, session)| match session.open_container {
+                Some((window_id, open_pos)) if open_pos == pos => Some((*connection_id, window_id)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Validates and stores an item a player put into a slot of their open container, then
+    /// shows the change to the other viewers. The server doesn't track what players carry, so
+    /// outside of creative mode they may only put in what they took out of the window.
+    pub fn set_container_slot(
+        &self,
+        connection_id: u64,
+        window_id: u8,
+        slot: u16,
+        item: ItemStack,
+    ) -> Result<()> {
+        let Some(((_, pos), mut taken_items)) = self
+            .sessions
+            .lock()
+            .get(&connection_id)
+            .and_then(|session| Some((session.open_container?, session.taken_items.clone())))
+            .filter(|((open_id, _), _)| *open_id == window_id)
+        else {
+            bail!("Window {} is not open", window_id);
+        };
+        // The region may have changed since the container was opened
+        self.check_protection(connection_id, pos)?;
+        validate_item(&item)?;
+        let item = if item.is_empty() {
+            ItemStack::default()
+        } else {
+            item
+        };
+        let username = self.get_username(connection_id).context("Not logged in")?;
+        {
+            let mut guard = self.data.lock();
+            let data = &mut *guard;
+            let creative = data.gamemode == 1;
+            let Some(items) = data
+                .world
+                .get_tile_entity_mut(pos)
+                .and_then(TileEntity::items_mut)
+            else {
+                bail!("Container {:?} is gone", pos);
+            };
+            let Some(stored) = items.get_mut(usize::from(slot)) else {
+                bail!("Container {:?} has no slot {}", pos, slot);
+            };
+            if !creative {
+                taken_items.change_slot(*stored, item)?;
+            }
+            let old = std::mem::replace(stored, item);
+            if old != item {
+                data.block_log.record(LogEntry::new(
+                    &username,
+                    pos,
+                    Change::Slot {
+                        slot,
+                        old,
+                        new: item,
+                    },
+                ));
+            }
+        }
+        if let Some(session) = self.sessions.lock().get_mut(&connection_id) {
+            session.taken_items = taken_items;
+        }
+        for (viewer, window_id) in self.container_viewers(pos) {
+            if viewer != connection_id {
+                self.send_game_packet(
+                    viewer,
+                    GamePacket::ContainerSetSlot {
+                        window_id,
+                        slot,
+                        item,
+                    },
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// A `ContainerSetSlot` with the stored item of a slot in the open container of a player, it
+    /// undoes a change the server refused
+    pub fn container_slot_packet(
+        &self,
+        connection_id: u64,
+        window_id: u8,
+        slot: u16,
+    ) -> Option<GamePacket> {
+        let (_, pos) = self
+            .sessions
+            .lock()
+            .get(&connection_id)?
+            .open_container
+            .filter(|(open_id, _)| *open_id == window_id)?;
+        let item = *self
+            .data
+            .lock()
+            .world
+            .get_tile_entity(pos)?
+            .items()?
+            .get(usize::from(slot))?;
+        Some(GamePacket::ContainerSetSlot {
+            window_id,
+            slot,
+            item,
+        })
+    }
+
+    /// Closes the windows of a container that no longer exists
+    pub fn close_removed_container(&self, pos: BlockPos) {
+        if self.data.lock().world.get_tile_entity(pos).is_some() {
+            return;
+        }
+        for (viewer, window_id) in self.container_viewers(pos) {
+            self.close_container(viewer, window_id);
+            self.send_game_packet(viewer, GamePacket::ContainerClose { window_id });
+        }
+    }
+
+    /// The logged changes at a position, oldest first
+    pub fn block_history(&self, pos: BlockPos) -> Vec<LogEntry> {
+        self.data.lock().block_log.history(pos)
+    }
+
+    /// Undoes the logged changes `player` made within `radius` blocks of `center` in the last
+    /// `seconds`, newest first. Blocks and slots changed again since are left alone. The undoing
+    /// is logged under `by`, returns the number of undone changes.
+    pub fn rollback(
+        &self,
+        by: &str,
+        player: &str,
+        center: BlockPos,
+        radius: i32,
+        seconds: u64,
+    ) -> usize {
+        let mut changed = Vec::new();
+        let mut slots = Vec::new();
+        {
+            let mut guard = self.data.lock();
+            let data = &mut *guard;
+            let since = block_log::now().saturating_sub(seconds);
+            for entry in data.block_log.changes_by(player, center, radius, since) {
+                let pos = entry.pos;
+                let undone = match entry.change {
+                    Change::Block {
+                        old,
+                        new,
+                        contents,
+                        lines,
+                    } => {
+                        if data.world.get_block(pos) != Some(new)
+                            || data.world.set_block(pos, old.0, old.1).is_err()
+                        {
+                            continue;
+                        }
+                        match TileEntity::for_block(old.0) {
+                            Some(TileEntity::Sign { .. }) => {
+                                let lines = lines.unwrap_or_default();
+                                data.world.set_tile_entity(pos, TileEntity::Sign { lines });
+                            }
+                            Some(mut tile_entity) => {
+                                if let Some(items) = tile_entity.items_mut() {
+                                    for (stored, item) in items.iter_mut().zip(contents) {
+                                        *stored = item;
+                                    }
+                                    data.world.set_tile_entity(pos, tile_entity);
+                                }
+                            }
+                            None => (),
+                        }
+                        changed.push(pos);
+                        Change::Block {
+                            old: new,
+                            new: old,
+                            contents: Vec::new(),
+                            lines: None,
+                        }
+                    }
+                    Change::Slot { slot, old, new } => {
+                        let Some(stored) = data
+                            .world
+                            .get_tile_entity_mut(pos)
+                            .and_then(TileEntity::items_mut)
+                            .and_then(|items| items.get_mut(usize::from(slot)))
+                            .filter(|stored| **stored == new)
+                        else {
+                            continue;
+                        };
+                        *stored = old;
+                        slots.push((pos, slot, old));
+                        Change::Slot {
+                            slot,
+                            old: new,
+                            new: old,
+                        }
+                    }
+                };
+                data.block_log.record(LogEntry {
+                    rollback: true,
+                    ..LogEntry::new(by, pos, undone)
+                });
+            }
+        }
+
+        for pos in &changed {
+            self.broadcast_game_packet(self.block_update_packet(*pos), None);
+            if let Some(text) = self.tile_entity_packet(*pos) {
+                self.broadcast_game_packet(text, None);
+            }
+            self.close_removed_container(*pos);
+        }
+        for (pos, slot, item) in &slots {
+            for (viewer, window_id) in self.container_viewers(*pos) {
+                self.send_game_packet(
+                    viewer,
+                    GamePacket::ContainerSetSlot {
+                        window_id,
+                        slot: *slot,
+                        item: *item,
+                    },
+                );
+            }
+        }
+        changed.len() + slots.len()
+    }
+
+    /// Sets a corner of the world edit selection of a player, `corner` is 0 or 1
+    pub fn set_selection_corner(&self, connection_id: u64, corner: usize, pos: BlockPos) {
+        if let Some(session) = self.sessions.lock().get_mut(&connection_id) {
+            session.selection[corner] = Some(pos);
+        }
+    }
+
+    /// The world edit selection of a player, once both corners are set
+    pub fn get_selection(&self, connection_id: u64) -> Option<Cuboid> {
+        let sessions = self.sessions.lock();
+        let [Some(first), Some(second)] = sessions.get(&connection_id)?.selection else {
+            return None;
+        };
+        Some(Cuboid::new(first, second))
+    }
+
+    /// The selection of a player, if it fits into the edit budget
+    fn budgeted_selection(&self, connection_id: u64) -> Result<Cuboid> {
+        let cuboid = self
+            .get_selection(connection_id)
+            .context("Select two corners first")?;
+        if cuboid.volume() > self.config.edit_budget {
+            bail!(
+                "The selection has {} blocks, more than the budget of {}",
+                cuboid.volume(),
+                self.config.edit_budget
+            );
+        }
+        Ok(cuboid)
+    }
+
+    /// Fills the selection of a player with a block. With `only` just the blocks with that id,
+    /// and aux if given, are replaced. Returns the number of changed blocks.
+    pub fn fill_selection(
+        &self,
+        connection_id: u64,
+        (id, aux): (u8, u8),
+        only: Option<(u8, Option<u8>)>,
+    ) -> Result<usize> {
+        let cuboid = self.budgeted_selection(connection_id)?;
+        self.load_edit_chunks(cuboid.positions())?;
+        let blocks: BlockChanges = {
+            let data = self.data.lock();
+            cuboid
+                .positions()
+                .filter(|pos| match (only, data.world.get_block(*pos)) {
+                    (None, _) => true,
+                    (Some((only_id, only_aux)), Some((current_id, current_aux))) => {
+                        only_id == current_id && only_aux.is_none_or(|aux| aux == current_aux)
+                    }
+                    (Some(_), None) => false,
+                })
+                .map(|pos| EditBlock::new(pos, id, aux))
+                .collect()
+        };
+        self.edit_blocks(connection_id, &blocks)
+    }
+
+    /// Copies the selection of a player relative to where they stand, returns the number of
+    /// copied blocks
+    pub fn copy_selection(&self, connection_id: u64) -> Result<usize> {
+        let cuboid = self.budgeted_selection(connection_id)?;
+        let origin = self
+            .get_player_block_pos(connection_id)
+            .context("You have no position yet")?;
+        self.load_edit_chunks(cuboid.positions())?;
+        let clipboard = self.data.lock().world.copy(cuboid, origin);
+        let copied = clipboard.blocks.len();
+        if let Some(session) = self.sessions.lock().get_mut(&connection_id) {
+            session.clipboard = Some(clipboard);
+        }
+        Ok(copied)
+    }
+
+    /// Pastes the clipboard of a player relative to where they stand, returns the number of
+    /// changed blocks
+    pub fn paste_clipboard(&self, connection_id: u64) -> Result<usize> {
+        let origin = self
+            .get_player_block_pos(connection_id)
+            .context("You have no position yet")?;
+        let blocks = self
+            .sessions
+            .lock()
+            .get(&connection_id)
+            .and_then(|session| session.clipboard.as_ref())
+            .map(|clipboard| clipboard.placed_at(origin))
+            .context("Copy something first")?;
+        self.edit_blocks(connection_id, &blocks)
+    }
+
+    /// Turns the clipboard of a player clockwise in steps of 90 degrees
+    pub fn rotate_clipboard(&self, connection_id: u64, quarter_turns: u32) -> Result<()> {
+        self.sessions
+            .lock()
+            .get_mut(&connection_id)
+            .and_then(|session| session.clipboard.as_mut())
+            .context("Copy something first")?
+            .rotate(quarter_turns);
+        Ok(())
+    }
+
+    /// Applies a world edit of a player and remembers it for `undo_edit`, edits larger than the
+    /// edit budget are refused. Returns the number of changed blocks.
+    pub fn edit_blocks(&self, connection_id: u64, blocks: &[EditBlock]) -> Result<usize> {
+        if blocks.len() > self.config.edit_budget {
+            bail!(
+                "The edit has {} blocks, more than the budget of {}",
+                blocks.len(),
+                self.config.edit_budget
+            );
+        }
+        let username = self.get_username(connection_id).context("Not logged in")?;
+        let previous = self.apply_edit(&username, blocks)?;
+        let changed = previous.len();
+        if changed == 0 {
+            return Ok(0);
+        }
+        if let Some(session) = self.sessions.lock().get_mut(&connection_id) {
+            session.edit_history.push(previous);
+            if session.edit_history.len() > MAX_UNDO_STEPS {
+                session.edit_history.remove(0);
+            }
+        }
+        Ok(changed)
+    }
+
+    /// Reverts the last world edit of a player, returns the number of restored blocks
+    pub fn undo_edit(&self, connection_id: u64) -> Result<usize> {
+        let username = self.get_username(connection_id).context("Not logged in")?;
+        let previous = self
+            .sessions
+            .lock()
+            .get_mut(&connection_id)
+            .and_then(|session| session.edit_history.pop())
+            .context("There is nothing to undo")?;
+        Ok(self.apply_edit(&username, &previous)?.len())
+    }
+
+    /// Generates the missing chunks of the edited positions, edits don't go into empty chunks
+    fn load_edit_chunks(&self, positions: impl IntoIterator<Item = BlockPos>) -> Result<()> {
+        let width = CHUNK_WIDTH as i32;
+        let chunks: BTreeSet<(i32, i32)> = positions
+            .into_iter()
+            .map(|(x, _, z)| (x.div_euclid(width), z.div_euclid(width)))
+            .collect();
+        for (chunk_x, chunk_z) in chunks {
+            self.load_chunk(chunk_x, chunk_z)?;
+        }
+        Ok(())
+    }
+
+    /// Sets the blocks of a world edit, logs them under `username` and sends them to the
+    /// players. Returns the replaced blocks.
+    fn apply_edit(&self, username: &str, blocks: &[EditBlock]) -> Result<BlockChanges> {
+        self.load_edit_chunks(blocks.iter().map(|block| block.pos))?;
+        let previous = {
+            let mut guard = self.data.lock();
+            let data = &mut *guard;
+            let previous = data.world.set_blocks(blocks);
+            for old in &previous {
+                let new = data.world.get_block(old.pos).unwrap_or((0, 0));
+                let contents = old
+                    .tile_entity
+                    .as_ref()
+                    .and_then(TileEntity::items)
+                    .map(<[ItemStack]>::to_vec)
+                    .unwrap_or_default();
+                let lines = old
+                    .tile_entity
+                    .as_ref()
+                    .and_then(TileEntity::lines)
+                    .cloned();
+                data.block_log.record(LogEntry::new(
+                    username,
+                    old.pos,
+                    Change::Block {
+                        old: (old.id, old.aux),
+                        new,
+                        contents,
+                        lines,
+                    },
+                ));
+            }
+            previous
+        };
+        for old in &previous {
+            self.close_removed_container(old.pos);
+        }
+        self.broadcast_block_changes(previous.iter().map(|old| old.pos));
+        Ok(previous)
+    }
+
+    /// Sends changed blocks to every player, chunks with many changes are queued to be sent
+    /// whole
+    fn broadcast_block_changes(&self, changed: impl IntoIterator<Item = BlockPos>) {
+        let width = CHUNK_WIDTH as i32;
+        let mut by_chunk: BTreeMap<(i32, i32), Vec<BlockPos>> = BTreeMap::new();
+        for pos in changed {
+            by_chunk
+                .entry((pos.0.div_euclid(width), pos.2.div_euclid(width)))
+                .or_default()
+                .push(pos);
+        }
+        let connection_ids: Vec<u64> = self
+            .sessions
+            .lock()
+            .iter()
+            .filter(|(_, session)| session.entity_id.is_some())
+            .map(|(connection_id, _)| *connection_id)
+            .collect();
+        for ((chunk_x, chunk_z), positions) in by_chunk {
+            if positions.len() > CHUNK_RESEND_THRESHOLD {
+                for connection_id in &connection_ids {
+                    self.queue_chunk(*connection_id, chunk_x, chunk_z);
+                }
+            } else {
+                for pos in positions {
+                    self.broadcast_game_packet(self.block_update_packet(pos), None);
+                }
+            }
+        }
+    }
+
+    /// An `SCUpdateBlock` with the current block at a position
+    pub fn block_update_packet(&self, (x, y, z): BlockPos) -> GamePacket {
+        let (block_id, block_aux) = self.get_block((x, y, z)).unwrap_or((0, 0));
+        GamePacket::SCUpdateBlock {
+            pos_x: x as u32,
+            pos_z: z as u32,
+            pos_y: y as u8,
+            block_id,
+            block_aux,
+        }
+    }
+
+    /// Queues a packet for every logged in player, except the connection `except`
+    pub fn broadcast_game_packet(&self, packet: GamePacket, except: Option<u64>) {
+        let connection_ids: Vec<u64> = self
+            .sessions
+            .lock()
+            .iter()
+            .filter(|(connection_id, session)| {
+                session.entity_id.is_some() && Some(**connection_id) != except
+            })
+            .map(|(connection_id, _)| *connection_id)
+            .collect();
+        for connection_id in connection_ids {
+            self.send_game_packet(connection_id, packet.clone());
+        }
+    }
+
+    pub fn get_protocol(&self, connection_id: u64) -> &'static dyn Protocol {
+        self.sessions
+            .lock()
+            .get(&connection_id)
+            .map_or(default_protocol(), |session| session.protocol)
+    }
+
+    pub fn get_connection_ids(&self) -> Vec<u64> {
+        self.sessions.lock().keys().copied().collect()
+    }
+
+    /// Queues a packet to be sent to a connection by the packet listener task
+    pub fn send_game_packet(&self, connection_id: u64, packet: GamePacket) {
+        self.outbox.lock().push((connection_id, packet));
+        self.outbox_notify.notify_one();
+    }
+
+    pub fn next_sequence(&self, connection_id: u64) -> u24 {
+        let mut sessions = self.sessions.lock();
+        let session = sessions.entry(connection_id).or_default();
+        let sequence = session.send_sequence;
+        session.send_sequence = sequence.wrapping_add(1);
+        sequence.into()
+    }
+
+    pub fn next_reliable_index(&self, connection_id: u64) -> u24 {
+        let mut sessions = self.sessions.lock();
+        let session = sessions.entry(connection_id).or_default();
+        let index = session.reliable_index;
+        session.reliable_index = index.wrapping_add(1);
+        index.into()
+    }
+
+    pub fn next_split_id(&self, connection_id: u64) -> u16 {
+        let mut sessions = self.sessions.lock();
+        let session = sessions.entry(connection_id).or_default();
+        let split_id = session.split_id;
+        session.split_id = split_id.wrapping_add(1);
+        split_id
+    }
+
+    pub fn get_mtu(&self, connection_id: u64) -> u16 {
+        self.sessions
+            .lock()
+            .get(&connection_id)
+            .map_or(DEFAULT_MTU, |session| session.mtu)
+    }
+
+    pub fn set_mtu(&self, connection_id: u64, mtu: u16) {
+        if let Some(session) = self.sessions.lock().get_mut(&connection_id) {
+            session.mtu = mtu;
+        }
+    }
+}
+
+/// Checks whether the surface of a column is safe to stand on
+fn safe_spot(world: &World, x: i32, z: i32) -> Option<BlockPos> {
+    let top = (0..CHUNK_HEIGHT as i32)
+        .rev()
+        .find(|y| is_solid(world.get_block((x, *y, z))))?;
+    let (floor, _) = world.get_block((x, top, z))?;
+    let is_free = |y| world.get_block((x, y, z)).is_none_or(|(id, _)| is_free(id));
+    (floor != Blocks::Cactus.id() && is_free(top + 1) && is_free(top + 2)).then_some((
+        x,
+        top + 1,
+        z,
+    ))
+}
+
+fn is_solid(block: Option<(u8, u8)>) -> bool {
+    block
+        .and_then(|(id, _)| block_properties(id))
+        .is_some_and(|properties| properties.solid)
+}
+
+/// Blocks a player can be inside of without getting hurt
+fn is_free(id: u8) -> bool {
+    let Some(block) = Blocks::from_id(id) else {
+        return false;
+    };
+    !block.properties().solid
+        && !matches!(
+            block,
+            Blocks::Water | Blocks::StillWater | Blocks::Lava | Blocks::StillLava | Blocks::Fire
+        )
 }
@@ -1,19 +1,27 @@
-use std::sync::Arc;
+use std::{collections::HashSet, sync::Arc};
 
 use anyhow::Result;
 use mlua::{
-    Lua, RegistryKey, Table,
+    Function, Lua, RegistryKey, Table,
     Value::{self, Nil},
 };
 use parking_lot::Mutex;
 
-use crate::registry::Registries;
+use crate::{
+    blocks::blocks_module,
+    codes::codes_module,
+    game_packets::{packets_module, GamePacket},
+    regions::regions_module,
+    registry::{
+        game_packet_listener::GamePacketListener, random_tick::RandomTickHandler, Registries,
+    },
+    time::time_module,
+    Server,
+};
 
-pub fn goldmine_module(
-    lua: &Lua,
-    registries: Arc<Mutex<Registries>>,
-) -> Result<RegistryKey> {
+pub fn goldmine_module(lua: &Lua, server: Server) -> Result<RegistryKey> {
     let gm_module = lua.create_table()?;
+    let registries = server.registries.clone();
 
     let registry_handle = registries.clone();
     let register_mod = lua.create_function(move |lua, lua_mod: Table| {
@@ -29,6 +37,23 @@ pub fn goldmine_module(
     gm_module.set("register_mod", register_mod)?;
 
     gm_module.set("registry", registry_module(lua, registries.clone())?)?;
+    gm_module.set("codes", codes_module(lua)?)?;
+    gm_module.set("blocks", blocks_module(lua)?)?;
+    gm_module.set("packets", packets_module(lua)?)?;
+    gm_module.set("time", time_module(lua, server.clone())?)?;
+    gm_module.set("regions", regions_module(lua, server.clone())?)?;
+
+    let server_handle = server.clone();
+    let send = lua.create_function(move |_, (connection_id, packet): (u64, GamePacket)| {
+        server_handle.send_game_packet(connection_id, packet);
+        Ok(())
+    })?;
+    gm_module.set("send", send)?;
+
+    let server_handle = server.clone();
+    let connections =
+        lua.create_function(move |_, ()| Ok(server_handle.get_connection_ids()))?;
+    gm_module.set("connections", connections)?;
 
     Ok(lua.create_registry_value(gm_module)?)
 }
@@ -40,6 +65,10 @@ fn registry_module(lua: &Lua, registries: Arc<Mutex<Registries>>) -> Result<Tabl
         "pl_registry",
         registry_functions(lua, registries.clone(), "pl_registry".to_owned())?,
     )?;
+    registry_module.set(
+        "gpl_registry",
+        game_packet_listener_functions(lua, registries.clone())?,
+    )?;
     registry_module.set(
         "lm_registry",
         registry_functions(lua, registries.clone(), "lm_registry".to_owned())?,
@@ -48,6 +77,14 @@ fn registry_module(lua: &Lua, registries: Arc<Mutex<Registries>>) -> Result<Tabl
         "api_registry",
         registry_functions(lua, registries.clone(), "api_registry".to_owned())?,
     )?;
+    registry_module.set(
+        "wg_registry",
+        registry_functions(lua, registries.clone(), "wg_registry".to_owned())?,
+    )?;
+    registry_module.set(
+        "rt_registry",
+        random_tick_functions(lua, registries.clone())?,
+    )?;
 
     Ok(registry_module)
 }
@@ -68,6 +105,7 @@ fn registry_functions(
             "pl_registry" => Some(&mut registries_handle.pl_registry),
             "lm_registry" => Some(&mut registries_handle.lm_registry),
             "api_registry" => Some(&mut registries_handle.api_registry),
+            "wg_registry" => Some(&mut registries_handle.wg_registry),
             _ => None,
         }
         .unwrap();
@@ -85,6 +123,7 @@ fn registry_functions(
             "pl_registry" => Some(&registries_handle.pl_registry),
             "lm_registry" => Some(&registries_handle.lm_registry),
             "api_registry" => Some(&registries_handle.api_registry),
+            "wg_registry" => Some(&registries_handle.wg_registry),
             _ => None,
         }
         .unwrap();
@@ -102,6 +141,7 @@ fn registry_functions(
             "pl_registry" => Some(&registries_handle.pl_registry),
             "lm_registry" => Some(&registries_handle.lm_registry),
             "api_registry" => Some(&registries_handle.api_registry),
+            "wg_registry" => Some(&registries_handle.wg_registry),
             _ => None,
         }
         .unwrap();
@@ -117,3 +157,51 @@ fn registry_functions(
 
     Ok(registry_table)
 }
+
+fn game_packet_listener_functions(lua: &Lua, registries: Arc<Mutex<Registries>>) -> Result<Table> {
+    let registry_table = lua.create_table()?;
+
+    let register_func = lua.create_function(
+        move |lua, (name, kinds, callback): (String, Option<Vec<String>>, Function)| {
+            if let Some(unknown) = kinds
+                .iter()
+                .flatten()
+                .find(|kind| !GamePacket::KINDS.contains(&kind.as_str()))
+            {
+                return Err(mlua::Error::runtime(format!(
+                    "Unknown game packet kind {}",
+                    unknown
+                )));
+            }
+            let listener = GamePacketListener {
+                kinds: kinds.map(HashSet::from_iter),
+                callback: lua.create_registry_value(callback)?,
+            };
+            registries.lock().gpl_registry.register(&name, listener);
+            Ok(())
+        },
+    )?;
+
+    registry_table.set("register", register_func)?;
+
+    Ok(registry_table)
+}
+
+fn random_tick_functions(lua: &Lua, registries: Arc<Mutex<Registries>>) -> Result<Table> {
+    let registry_table = lua.create_table()?;
+
+    let register_func = lua.create_function(
+        move |lua, (name, blocks, callback): (String, Vec<u8>, Function)| {
+            let handler = RandomTickHandler {
+                blocks: blocks.into_iter().collect(),
+                callback: lua.create_registry_value(callback)?,
+            };
+            registries.lock().rt_registry.register(&name, handler);
+            Ok(())
+        },
+    )?;
+
+    registry_table.set("register", register_func)?;
+
+    Ok(registry_table)
+}
@@ -0,0 +1,362 @@
+use std::{collections::btree_map, fmt, io::Read, vec};
+
+use serde::{
+    de::{self, value::StringDeserializer, DeserializeSeed, Error as _, IntoDeserializer, Visitor},
+    forward_to_deserialize_any, Deserialize,
+};
+
+use super::{read_root, Compound, Endian, Error, Tag};
+
+/// Converts a tag into a value. Integer tags of any width convert into each other, wrapping like
+/// `as`, and strings holding a number convert into integers so compounds can be read into maps
+/// with integer keys.
+pub fn from_tag<'de, T: Deserialize<'de>>(tag: Tag) -> Result<T, Error> {
+    T::deserialize(tag)
+}
+
+/// Reads a named root compound into a value
+pub fn from_reader<T: for<'de> Deserialize<'de>, R: Read>(
+    reader: &mut R,
+    endian: Endian,
+) -> anyhow::Result<T> {
+    let (_, compound) = read_root(reader, endian)?;
+    Ok(from_tag(Tag::Compound(compound))?)
+}
+
+impl Tag {
+    fn integer(&self) -> Result<i64, Error> {
+        match self {
+            Tag::String(value) => value.parse().map_err(Error::custom),
+            _ => self.as_i64().ok_or_else(|| {
+                Error::custom(format!("Expected an integer, found tag {}", self.id()))
+            }),
+        }
+    }
+
+    fn float(&self) -> Result<f64, Error> {
+        self.as_f64()
+            .ok_or_else(|| Error::custom(format!("Expected a number, found tag {}", self.id())))
+    }
+}
+
+macro_rules! deserialize_integer {
+    ($($method:ident => $visit:ident as $ty:ty),* $(,)?) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                visitor.$visit(self.integer()? as $ty)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Tag {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Tag::Byte(value) => visitor.visit_i8(value),
+            Tag::Short(value) => visitor.visit_i16(value),
+            Tag::Int(value) => visitor.visit_i32(value),
+            Tag::Long(value) => visitor.visit_i64(value),
+            Tag::Float(value) => visitor.visit_f32(value),
+            Tag::Double(value) => visitor.visit_f64(value),
+            Tag::ByteArray(values) => {
+                visitor.visit_byte_buf(values.into_iter().map(|value| value as u8).collect())
+            }
+            Tag::String(value) => visitor.visit_string(value),
+            Tag::List(values) => visitor.visit_seq(ListDeserializer(values.into_iter())),
+            Tag::Compound(compound) => visitor.visit_map(CompoundDeserializer::new(compound)),
+            Tag::IntArray(values) => visitor.visit_seq(ListDeserializer(
+                values
+                    .into_iter()
+                    .map(Tag::Int)
+                    .collect::<Vec<_>>()
+                    .into_iter(),
+            )),
+            Tag::LongArray(values) => visitor.visit_seq(ListDeserializer(
+                values
+                    .into_iter()
+                    .map(Tag::Long)
+                    .collect::<Vec<_>>()
+                    .into_iter(),
+            )),
+        }
+    }
+
+    deserialize_integer! {
+        deserialize_i8 => visit_i8 as i8,
+        deserialize_i16 => visit_i16 as i16,
+        deserialize_i32 => visit_i32 as i32,
+        deserialize_i64 => visit_i64 as i64,
+        deserialize_u8 => visit_u8 as u8,
+        deserialize_u16 => visit_u16 as u16,
+        deserialize_u32 => visit_u32 as u32,
+        deserialize_u64 => visit_u64 as u64,
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_bool(self.integer()? != 0)
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_f32(self.float()? as f32)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_f64(self.float()?)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self {
+            Tag::ByteArray(values) => visitor.visit_seq(ListDeserializer(
+                values
+                    .into_iter()
+                    .map(Tag::Byte)
+                    .collect::<Vec<_>>()
+                    .into_iter(),
+            )),
+            tag => tag.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self {
+            Tag::String(variant) => visitor.visit_enum(variant.into_deserializer()),
+            Tag::Compound(compound) if compound.len() == 1 => {
+                let (variant, value) = compound.into_iter().next().unwrap();
+                visitor.visit_enum(EnumDeserializer { variant, value })
+            }
+            tag => Err(Error::custom(format!(
+                "Expected a string or a compound with one key for an enum, found tag {}",
+                tag.id()
+            ))),
+        }
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        char str string bytes byte_buf tuple tuple_struct map struct identifier
+    }
+}
+
+struct ListDeserializer(vec::IntoIter<Tag>);
+
+impl<'de> de::SeqAccess<'de> for ListDeserializer {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        self.0.next().map(|tag| seed.deserialize(tag)).transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.0.len())
+    }
+}
+
+struct CompoundDeserializer {
+    entries: btree_map::IntoIter<String, Tag>,
+    value: Option<Tag>,
+}
+
+impl CompoundDeserializer {
+    fn new(compound: Compound) -> Self {
+        Self {
+            entries: compound.into_iter(),
+            value: None,
+        }
+    }
+}
+
+impl<'de> de::MapAccess<'de> for CompoundDeserializer {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        let Some((key, value)) = self.entries.next() else {
+            return Ok(None);
+        };
+        self.value = Some(value);
+        // As a string tag, so integer keys are parsed
+        seed.deserialize(Tag::String(key)).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let value = self
+            .value
+            .take()
+            .ok_or_else(|| Error::custom("Compound value without a key"))?;
+        seed.deserialize(value)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+struct EnumDeserializer {
+    variant: String,
+    value: Tag,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumDeserializer {
+    type Error = Error;
+    type Variant = Tag;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, Tag), Error> {
+        let variant: StringDeserializer<Error> = self.variant.into_deserializer();
+        Ok((seed.deserialize(variant)?, self.value))
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for Tag {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
+
+struct TagVisitor;
+
+impl<'de> Visitor<'de> for TagVisitor {
+    type Value = Tag;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a value with an NBT tag")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Tag, E> {
+        Ok(Tag::Byte(v.into()))
+    }
+
+    fn visit_i8<E: de::Error>(self, v: i8) -> Result<Tag, E> {
+        Ok(Tag::Byte(v))
+    }
+
+    fn visit_i16<E: de::Error>(self, v: i16) -> Result<Tag, E> {
+        Ok(Tag::Short(v))
+    }
+
+    fn visit_i32<E: de::Error>(self, v: i32) -> Result<Tag, E> {
+        Ok(Tag::Int(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Tag, E> {
+        Ok(Tag::Long(v))
+    }
+
+    fn visit_u8<E: de::Error>(self, v: u8) -> Result<Tag, E> {
+        Ok(Tag::Byte(v as i8))
+    }
+
+    fn visit_u16<E: de::Error>(self, v: u16) -> Result<Tag, E> {
+        Ok(Tag::Short(v as i16))
+    }
+
+    fn visit_u32<E: de::Error>(self, v: u32) -> Result<Tag, E> {
+        Ok(Tag::Int(v as i32))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Tag, E> {
+        Ok(Tag::Long(v as i64))
+    }
+
+    fn visit_f32<E: de::Error>(self, v: f32) -> Result<Tag, E> {
+        Ok(Tag::Float(v))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Tag, E> {
+        Ok(Tag::Double(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Tag, E> {
+        Ok(Tag::String(v.to_owned()))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Tag, E> {
+        Ok(Tag::String(v))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Tag, E> {
+        Ok(Tag::ByteArray(v.iter().map(|byte| *byte as i8).collect()))
+    }
+
+    fn visit_some<D: de::Deserializer<'de>>(self, deserializer: D) -> Result<Tag, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_seq<A: de::SeqAccess<'de>>(self, mut seq: A) -> Result<Tag, A::Error> {
+        let mut values = Vec::new();
+        while let Some(value) = seq.next_element()? {
+            values.push(value);
+        }
+        Ok(Tag::List(values))
+    }
+
+    fn visit_map<A: de::MapAccess<'de>>(self, mut map: A) -> Result<Tag, A::Error> {
+        let mut compound = Compound::new();
+        while let Some((key, value)) = map.next_entry()? {
+            compound.insert(key, value);
+        }
+        Ok(Tag::Compound(compound))
+    }
+}
+
+impl<'de> Deserialize<'de> for Tag {
+    fn deserialize<D: de::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_any(TagVisitor)
+    }
+}
@@ -0,0 +1,360 @@
+//! Named Binary Tag, the format of `level.dat` and the entity data. The Pi and PE 0.x clients
+//! use the little-endian variant, the Java edition the big-endian one. Serde types convert from
+//! and to tags with `to_tag` and `from_tag`.
+
+use std::{
+    collections::BTreeMap,
+    io::{Read, Write},
+};
+
+use anyhow::{bail, Result};
+use declio::{Decode, Encode};
+
+pub use declio::ctx::Endian;
+
+pub use self::{
+    de::{from_reader, from_tag},
+    error::Error,
+    ser::{to_tag, to_writer},
+};
+
+mod de;
+mod error;
+mod ser;
+
+pub type Compound = BTreeMap<String, Tag>;
+
+/// Lists and compounds nested deeper than this are rejected while reading, like the vanilla
+/// reader does, so corrupted data can't overflow the stack
+const MAX_DEPTH: usize = 512;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Tag {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    /// The element type of an empty list is lost, it is written as a list of `End` tags
+    List(Vec<Tag>),
+    Compound(Compound),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+impl Tag {
+    pub fn id(&self) -> u8 {
+        match self {
+            Tag::Byte(_) => 1,
+            Tag::Short(_) => 2,
+            Tag::Int(_) => 3,
+            Tag::Long(_) => 4,
+            Tag::Float(_) => 5,
+            Tag::Double(_) => 6,
+            Tag::ByteArray(_) => 7,
+            Tag::String(_) => 8,
+            Tag::List(_) => 9,
+            Tag::Compound(_) => 10,
+            Tag::IntArray(_) => 11,
+            Tag::LongArray(_) => 12,
+        }
+    }
+
+    /// Any integer tag widened to an i64
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Tag::Byte(value) => Some((*value).into()),
+            Tag::Short(value) => Some((*value).into()),
+            Tag::Int(value) => Some((*value).into()),
+            Tag::Long(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    /// Any number tag as an f64
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Tag::Float(value) => Some((*value).into()),
+            Tag::Double(value) => Some(*value),
+            _ => self.as_i64().map(|value| value as f64),
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Tag::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[Tag]> {
+        match self {
+            Tag::List(values) => Some(values),
+            _ => None,
+        }
+    }
+
+    pub fn as_compound(&self) -> Option<&Compound> {
+        match self {
+            Tag::Compound(compound) => Some(compound),
+            _ => None,
+        }
+    }
+
+    fn read_payload<R: Read>(id: u8, reader: &mut R, endian: Endian, depth: usize) -> Result<Tag> {
+        if depth > MAX_DEPTH {
+            bail!("NBT tags are nested deeper than {}", MAX_DEPTH);
+        }
+        let tag = match id {
+            1 => Tag::Byte(i8::decode(endian, reader)?),
+            2 => Tag::Short(i16::decode(endian, reader)?),
+            3 => Tag::Int(i32::decode(endian, reader)?),
+            4 => Tag::Long(i64::decode(endian, reader)?),
+            5 => Tag::Float(f32::decode(endian, reader)?),
+            6 => Tag::Double(f64::decode(endian, reader)?),
+            7 => Tag::ByteArray(read_array(reader, endian, |reader| {
+                Ok(i8::decode(endian, reader)?)
+            })?),
+            8 => Tag::String(read_string(reader, endian)?),
+            9 => {
+                let element_id = u8::decode(endian, reader)?;
+                Tag::List(read_array(reader, endian, |reader| {
+                    Self::read_payload(element_id, reader, endian, depth + 1)
+                })?)
+            }
+            10 => Tag::Compound(read_compound(reader, endian, depth + 1)?),
+            11 => Tag::IntArray(read_array(reader, endian, |reader| {
+                Ok(i32::decode(endian, reader)?)
+            })?),
+            12 => Tag::LongArray(read_array(reader, endian, |reader| {
+                Ok(i64::decode(endian, reader)?)
+            })?),
+            _ => bail!("Unknown NBT tag id {}", id),
+        };
+        Ok(tag)
+    }
+
+    fn write_payload<W: Write>(&self, writer: &mut W, endian: Endian) -> Result<()> {
+        match self {
+            Tag::Byte(value) => value.encode(endian, writer)?,
+            Tag::Short(value) => value.encode(endian, writer)?,
+            Tag::Int(value) => value.encode(endian, writer)?,
+            Tag::Long(value) => value.encode(endian, writer)?,
+            Tag::Float(value) => value.encode(endian, writer)?,
+            Tag::Double(value) => value.encode(endian, writer)?,
+            Tag::ByteArray(values) => {
+                write_len(values.len(), writer, endian)?;
+                for value in values {
+                    value.encode(endian, writer)?;
+                }
+            }
+            Tag::String(value) => write_string(value, writer, endian)?,
+            Tag::List(values) => {
+                let element_id = values.first().map_or(0, Tag::id);
+                if values.iter().any(|value| value.id() != element_id) {
+                    bail!("NBT lists can't mix tag types");
+                }
+                element_id.encode(endian, writer)?;
+                write_len(values.len(), writer, endian)?;
+                for value in values {
+                    value.write_payload(writer, endian)?;
+                }
+            }
+            Tag::Compound(compound) => write_compound(compound, writer, endian)?,
+            Tag::IntArray(values) => {
+                write_len(values.len(), writer, endian)?;
+                for value in values {
+                    value.encode(endian, writer)?;
+                }
+            }
+            Tag::LongArray(values) => {
+                write_len(values.len(), writer, endian)?;
+                for value in values {
+                    value.encode(endian, writer)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn read_array<R: Read, T>(
+    reader: &mut R,
+    endian: Endian,
+    mut read: impl FnMut(&mut R) -> Result<T>,
+) -> Result<Vec<T>> {
+    let len = i32::decode(endian, reader)?;
+    let Ok(len) = usize::try_from(len) else {
+        bail!("Negative NBT array length {}", len);
+    };
+    // Don't trust the length for preallocation, the data might be corrupted
+    let mut values = Vec::with_capacity(len.min(4096));
+    for _ in 0..len {
+        values.push(read(reader)?);
+    }
+    Ok(values)
+}
+
+fn write_len<W: Write>(len: usize, writer: &mut W, endian: Endian) -> Result<()> {
+    i32::try_from(len)?.encode(endian, writer)?;
+    Ok(())
+}
+
+fn read_string<R: Read>(reader: &mut R, endian: Endian) -> Result<String> {
+    let len = u16::decode(endian, reader)?;
+    let mut bytes = vec![0; len.into()];
+    reader.read_exact(&mut bytes)?;
+    Ok(String::from_utf8(bytes)?)
+}
+
+fn write_string<W: Write>(value: &str, writer: &mut W, endian: Endian) -> Result<()> {
+    u16::try_from(value.len())?.encode(endian, writer)?;
+    writer.write_all(value.as_bytes())?;
+    Ok(())
+}
+
+fn read_compound<R: Read>(reader: &mut R, endian: Endian, depth: usize) -> Result<Compound> {
+    let mut compound = Compound::new();
+    loop {
+        let id = u8::decode(endian, reader)?;
+        if id == 0 {
+            return Ok(compound);
+        }
+        let name = read_string(reader, endian)?;
+        compound.insert(name, Tag::read_payload(id, reader, endian, depth)?);
+    }
+}
+
+fn write_compound<W: Write>(compound: &Compound, writer: &mut W, endian: Endian) -> Result<()> {
+    for (name, tag) in compound {
+        tag.id().encode(endian, writer)?;
+        write_string(name, writer, endian)?;
+        tag.write_payload(writer, endian)?;
+    }
+    0u8.encode(endian, writer)?;
+    Ok(())
+}
+
+/// Reads a named root compound
+pub fn read_root<R: Read>(reader: &mut R, endian: Endian) -> Result<(String, Compound)> {
+    let id = u8::decode(endian, reader)?;
+    if id != 10 {
+        bail!("NBT root is tag {} instead of a compound", id);
+    }
+    let name = read_string(reader, endian)?;
+    Ok((name, read_compound(reader, endian, 0)?))
+}
+
+/// Writes a named root compound
+pub fn write_root<W: Write>(
+    name: &str,
+    compound: &Compound,
+    writer: &mut W,
+    endian: Endian,
+) -> Result<()> {
+    10u8.encode(endian, writer)?;
+    write_string(name, writer, endian)?;
+    write_compound(compound, writer, endian)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+
+    fn every_tag() -> Compound {
+        Compound::from([
+            ("byte".to_owned(), Tag::Byte(-5)),
+            ("short".to_owned(), Tag::Short(-300)),
+            ("int".to_owned(), Tag::Int(70000)),
+            ("long".to_owned(), Tag::Long(-(1 << 40))),
+            ("float".to_owned(), Tag::Float(1.5)),
+            ("double".to_owned(), Tag::Double(-0.25)),
+            ("bytes".to_owned(), Tag::ByteArray(vec![1, -2, 3])),
+            ("string".to_owned(), Tag::String("Grüße".to_owned())),
+            ("list".to_owned(), Tag::List(vec![Tag::Int(1), Tag::Int(2)])),
+            (
+                "compound".to_owned(),
+                Tag::Compound(Compound::from([("inner".to_owned(), Tag::Byte(1))])),
+            ),
+            ("ints".to_owned(), Tag::IntArray(vec![-1, 0, 1 << 20])),
+            ("longs".to_owned(), Tag::LongArray(vec![i64::MIN, i64::MAX])),
+        ])
+    }
+
+    #[test]
+    fn root_round_trip() {
+        for endian in [Endian::Little, Endian::Big] {
+            let mut bytes = Vec::new();
+            write_root("root", &every_tag(), &mut bytes, endian).unwrap();
+            let (name, compound) = read_root(&mut bytes.as_slice(), endian).unwrap();
+            assert_eq!(name, "root");
+            assert_eq!(compound, every_tag());
+        }
+    }
+
+    #[test]
+    fn little_endian_layout() {
+        let compound = Compound::from([("a".to_owned(), Tag::Short(1))]);
+        let mut bytes = Vec::new();
+        write_root("", &compound, &mut bytes, Endian::Little).unwrap();
+        assert_eq!(bytes, [10, 0, 0, 2, 1, 0, b'a', 1, 0, 0]);
+    }
+
+    #[test]
+    fn root_must_be_compound() {
+        let bytes = [1u8, 0, 0, 5];
+        assert!(read_root(&mut bytes.as_slice(), Endian::Little).is_err());
+    }
+
+    #[test]
+    fn deep_nesting_is_rejected() {
+        // A root compound holding lists of lists, each with a single element
+        let nested = |depth: usize| {
+            let mut bytes = vec![10, 0, 0, 9, 1, 0, b'a'];
+            for _ in 1..depth {
+                bytes.extend([9, 1, 0, 0, 0]);
+            }
+            bytes.extend([1, 1, 0, 0, 0, 7]);
+            bytes.push(0);
+            bytes
+        };
+        assert!(read_root(&mut nested(MAX_DEPTH).as_slice(), Endian::Little).is_ok());
+        assert!(read_root(&mut nested(MAX_DEPTH + 1).as_slice(), Endian::Little).is_err());
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    #[serde(rename_all = "PascalCase")]
+    struct Settings {
+        level_name: String,
+        seed: u32,
+        spawn: (i32, i32, i32),
+        flags: Vec<bool>,
+        motd: Option<String>,
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        let settings = Settings {
+            level_name: "world".to_owned(),
+            seed: u32::MAX,
+            spawn: (128, 64, -3),
+            flags: vec![true, false],
+            motd: None,
+        };
+        let tag = to_tag(&settings).unwrap();
+        let compound = tag.as_compound().unwrap();
+        assert_eq!(compound.get("Seed"), Some(&Tag::Int(-1)));
+        assert!(!compound.contains_key("Motd"));
+        assert_eq!(from_tag::<Settings>(tag).unwrap(), settings);
+
+        let mut bytes = Vec::new();
+        to_writer("", &settings, &mut bytes, Endian::Little).unwrap();
+        let read: Settings = from_reader(&mut bytes.as_slice(), Endian::Little).unwrap();
+        assert_eq!(read, settings);
+    }
+}
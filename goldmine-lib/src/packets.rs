@@ -1,10 +1,12 @@
+// The declio derive expands the `skip_if`/`with` fields below into unit expressions
+#![allow(clippy::unused_unit)]
 use declio::{ctx, util, Decode, Encode};
 use mlua::UserData;
 use serde::{Deserialize, Serialize};
 
 use crate::{
     constants::{MAGIC, NULL_BYTE},
-    game_packets::Encapsulation,
+    game_packets::{Encapsulation, Encapsulations},
     u24::u24,
 };
 
@@ -127,6 +129,20 @@ mod encapsulation {
     }
 }
 
+impl Packet {
+    /// Borrows the sequence number and encapsulated game packets of a `Packet::Custom` datagram
+    /// without decoding it, `None` for any other packet
+    pub fn borrow_custom(datagram: &[u8]) -> Option<(u24, Encapsulations<'_>)> {
+        match datagram {
+            [0x84, count_0, count_1, count_2, body @ ..] => Some((
+                u24::from_le_bytes([*count_0, *count_1, *count_2]),
+                Encapsulations::new(body),
+            )),
+            _ => None,
+        }
+    }
+}
+
 impl UserData for Packet {
     fn add_methods<M: mlua::prelude::LuaUserDataMethods<Self>>(methods: &mut M) {
         methods.add_method("toString", |_, packet, ()| Ok(format!("{:x?}", packet)))
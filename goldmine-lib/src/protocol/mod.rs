@@ -0,0 +1,189 @@
+use std::{borrow::Cow, io::Read};
+
+use anyhow::{bail, Context, Result};
+use declio::{Decode, Encode};
+
+use crate::game_packets::GamePacket;
+
+pub mod pe;
+pub mod pi;
+
+/// A client protocol version. Every protocol translates between its own wire format and the
+/// internal [`GamePacket`] layout, which is the one spoken by the Pi edition (protocol 9).
+pub trait Protocol: Send + Sync {
+    /// Human readable name of the client releases using this protocol
+    fn name(&self) -> &'static str;
+
+    /// Protocol versions (as sent in `CSLogin::proto1`) handled by this protocol
+    fn versions(&self) -> &'static [u32];
+
+    /// Maps a wire packet id to the internal one, `None` if the packet has no internal equivalent
+    fn to_internal_id(&self, wire_id: u8) -> Option<u8>;
+
+    /// Maps an internal packet id to the wire one, `None` if the client doesn't know the packet
+    fn to_wire_id(&self, internal_id: u8) -> Option<u8>;
+
+    /// Rewrites a packet body (without id) from the wire layout into the internal layout
+    fn inbound_layout<'a>(&self, _internal_id: u8, body: &'a [u8]) -> Result<Cow<'a, [u8]>> {
+        Ok(Cow::Borrowed(body))
+    }
+
+    /// Rewrites a packet body (without id) from the internal layout into the wire layout
+    fn outbound_layout<'a>(&self, _internal_id: u8, body: &'a [u8]) -> Result<Cow<'a, [u8]>> {
+        Ok(Cow::Borrowed(body))
+    }
+}
+
+pub static PROTOCOLS: &[&dyn Protocol] = &[&pi::PI, &pe::PE_07, &pe::PE_08];
+
+/// The protocol used for connections that haven't logged in yet
+pub fn default_protocol() -> &'static dyn Protocol {
+    &pi::PI
+}
+
+pub fn by_version(version: u32) -> Option<&'static dyn Protocol> {
+    PROTOCOLS
+        .iter()
+        .find(|protocol| protocol.versions().contains(&version))
+        .copied()
+}
+
+pub fn oldest_version() -> u32 {
+    PROTOCOLS
+        .iter()
+        .flat_map(|protocol| protocol.versions())
+        .copied()
+        .min()
+        .unwrap_or_default()
+}
+
+pub fn newest_version() -> u32 {
+    PROTOCOLS
+        .iter()
+        .flat_map(|protocol| protocol.versions())
+        .copied()
+        .max()
+        .unwrap_or_default()
+}
+
+pub fn decode_game_packet(protocol: &dyn Protocol, bytes: &[u8]) -> Result<GamePacket> {
+    let (&wire_id, body) = bytes.split_first().context("Empty game packet")?;
+    let internal_id = protocol.to_internal_id(wire_id).with_context(|| {
+        format!(
+            "Packet id {:#x} is not supported by {}",
+            wire_id,
+            protocol.name()
+        )
+    })?;
+    let body = protocol.inbound_layout(internal_id, body)?;
+
+    let internal_id = [internal_id];
+    let mut reader = internal_id.as_slice().chain(&*body);
+    Ok(GamePacket::decode((), &mut reader)?)
+}
+
+pub fn encode_game_packet(
+    protocol: &dyn Protocol,
+    packet: &GamePacket,
+    writer: &mut Vec<u8>,
+) -> Result<()> {
+    let start = writer.len();
+    packet.encode((), writer)?;
+    let internal_id = writer[start];
+    let Some(wire_id) = protocol.to_wire_id(internal_id) else {
+        writer.truncate(start);
+        bail!(
+            "Packet id {:#x} is not supported by {}",
+            internal_id,
+            protocol.name()
+        );
+    };
+
+    writer[start] = wire_id;
+    if let Cow::Owned(body) = protocol.outbound_layout(internal_id, &writer[start + 1..])? {
+        writer.truncate(start + 1);
+        writer.extend_from_slice(&body);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(protocol: &dyn Protocol, packet: &GamePacket) -> (Vec<u8>, GamePacket) {
+        let mut bytes = Vec::new();
+        encode_game_packet(protocol, packet, &mut bytes).unwrap();
+        let decoded = decode_game_packet(protocol, &bytes).unwrap();
+        (bytes, decoded)
+    }
+
+    #[test]
+    fn versions() {
+        assert_eq!(by_version(9).unwrap().name(), pi::PI.name());
+        assert_eq!(by_version(12).unwrap().name(), pe::PE_07.name());
+        assert_eq!(by_version(14).unwrap().name(), pe::PE_08.name());
+        // Between the Pi edition and PE 0.7
+        assert!(by_version(10).is_none());
+        assert!((oldest_version()..newest_version()).contains(&10));
+    }
+
+    #[test]
+    fn pi_ids_are_internal() {
+        let packet = GamePacket::CSRequestChunk {
+            index_x: 3,
+            index_z: 4,
+        };
+        let (bytes, decoded) = round_trip(&pi::PI, &packet);
+        assert_eq!(bytes, [0x9d, 0, 0, 0, 3, 0, 0, 0, 4]);
+        assert!(matches!(
+            decoded,
+            GamePacket::CSRequestChunk {
+                index_x: 3,
+                index_z: 4
+            }
+        ));
+    }
+
+    #[test]
+    fn pe_07_ids_are_shifted() {
+        let packet = GamePacket::CSRequestChunk {
+            index_x: 3,
+            index_z: 4,
+        };
+        let (bytes, decoded) = round_trip(&pe::PE_07, &packet);
+        assert_eq!(bytes[0], 0x9e);
+        assert!(matches!(
+            decoded,
+            GamePacket::CSRequestChunk {
+                index_x: 3,
+                index_z: 4
+            }
+        ));
+
+        // Unmoved ids pass through
+        assert_eq!(pe::PE_07.to_wire_id(0x85), Some(0x85));
+        assert_eq!(pe::PE_07.to_internal_id(0x85), Some(0x85));
+        // ContainerAck is gone and 0xa4 now belongs to CSPlayerAction
+        assert_eq!(pe::PE_07.to_wire_id(0xb3), None);
+        assert_eq!(pe::PE_07.to_wire_id(0xa4), None);
+        assert_eq!(pe::PE_07.to_internal_id(0xa4), Some(0xa3));
+        // Nothing is sent as 0xa9 anymore
+        assert_eq!(pe::PE_07.to_internal_id(0xa9), None);
+        assert!(decode_game_packet(&pe::PE_07, &[0xa9]).is_err());
+    }
+
+    #[test]
+    fn pe_08_chat_has_a_source() {
+        let (bytes, decoded) = round_trip(&pe::PE_08, &GamePacket::message("hi"));
+        assert_eq!(bytes, [0x85, 0, 0, 0, 2, b'h', b'i']);
+        assert!(matches!(decoded, GamePacket::SCMessage { message, .. } if message == "hi"));
+
+        let bytes = [0x85, 0, 5, b's', b't', b'e', b'v', b'e', 0, 2, b'h', b'i'];
+        let decoded = decode_game_packet(&pe::PE_08, &bytes).unwrap();
+        assert!(matches!(decoded, GamePacket::SCMessage { message, .. } if message == "hi"));
+
+        assert!(decode_game_packet(&pe::PE_08, &[0x85, 0]).is_err());
+        assert!(decode_game_packet(&pe::PE_08, &[0x85, 0, 5, b's', b't']).is_err());
+    }
+}
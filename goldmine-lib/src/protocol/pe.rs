@@ -0,0 +1,143 @@
+use std::borrow::Cow;
+
+use anyhow::{bail, Result};
+
+use super::Protocol;
+
+/// PE 0.7 inserted `RotateHeadPacket` and `SetEntityLinkPacket`, which shifted the ids of most
+/// gameplay packets. Pairs are (internal id, wire id), ids not listed here are unchanged.
+const PE_07_IDS: &[(u8, u8)] = &[
+    (0x94, 0x95), // MovePlayer
+    (0x95, 0x96), // PlaceBlock
+    (0x96, 0x97), // RemoveBlock
+    (0x97, 0x98), // SCUpdateBlock
+    (0x98, 0x99), // SCAddPainting
+    (0x99, 0x9a), // SCExplode
+    (0x9a, 0x9b), // SCLevelEvent
+    (0x9b, 0x9c), // SCTileEvent
+    (0x9c, 0x9d), // EntityEvent
+    (0x9d, 0x9e), // CSRequestChunk
+    (0x9e, 0x9f), // SCChunkDataPacket
+    (0x9f, 0xa0), // PlayerEquipment
+    (0xa0, 0xa1), // PlayerArmorEquipment
+    (0xa1, 0xa2), // Interact
+    (0xa2, 0xa3), // UseItem
+    (0xa3, 0xa4), // CSPlayerAction
+    (0xa5, 0xa6), // SCHurtArmor
+    (0xa6, 0xa7), // SCSetEntityData
+    (0xa7, 0xa8), // SCSetEntityMotion
+    (0xa8, 0xaa), // SCSetHealth
+    (0xa9, 0xab), // SCSetSpawnPosition
+    (0xaa, 0xac), // Animate
+    (0xab, 0xad), // Respawn
+    (0xac, 0xae), // SendInventory
+    (0xad, 0xaf), // CSDropItem
+    (0xae, 0xb0), // SCContainerOpen
+    (0xaf, 0xb1), // ContainerClose
+    (0xb0, 0xb2), // ContainerSetSlot
+    (0xb1, 0xb3), // SCContainerSetData
+    (0xb2, 0xb4), // ContainerSetContent
+    (0xb4, 0xb5), // CSChat
+];
+
+/// Internal packets these clients don't know anymore
+const PE_07_REMOVED: &[u8] = &[
+    0xb3, // ContainerAck
+    0xb5, // SignUpdate, replaced by NBT based tile entity data
+];
+
+fn to_wire_id(table: &[(u8, u8)], removed: &[u8], internal_id: u8) -> Option<u8> {
+    if removed.contains(&internal_id) {
+        return None;
+    }
+    match table.iter().find(|(internal, _)| *internal == internal_id) {
+        Some((_, wire)) => Some(*wire),
+        // Ids that were moved away are free on the wire, so an unmapped internal id that
+        // collides with a moved one must not be sent as is
+        None if table.iter().any(|(_, wire)| *wire == internal_id) => None,
+        None => Some(internal_id),
+    }
+}
+
+fn to_internal_id(table: &[(u8, u8)], wire_id: u8) -> Option<u8> {
+    match table.iter().find(|(_, wire)| *wire == wire_id) {
+        Some((internal, _)) => Some(*internal),
+        None if table.iter().any(|(internal, _)| *internal == wire_id) => None,
+        None => Some(wire_id),
+    }
+}
+
+/// Pocket Edition 0.7.x
+pub struct Pe07Protocol;
+
+pub static PE_07: Pe07Protocol = Pe07Protocol;
+
+impl Protocol for Pe07Protocol {
+    fn name(&self) -> &'static str {
+        "PE 0.7"
+    }
+
+    fn versions(&self) -> &'static [u32] {
+        &[11, 12, 13]
+    }
+
+    fn to_internal_id(&self, wire_id: u8) -> Option<u8> {
+        to_internal_id(PE_07_IDS, wire_id)
+    }
+
+    fn to_wire_id(&self, internal_id: u8) -> Option<u8> {
+        to_wire_id(PE_07_IDS, PE_07_REMOVED, internal_id)
+    }
+}
+
+/// Pocket Edition 0.8.x, same ids as 0.7 but chat messages carry a source name
+pub struct Pe08Protocol;
+
+pub static PE_08: Pe08Protocol = Pe08Protocol;
+
+impl Protocol for Pe08Protocol {
+    fn name(&self) -> &'static str {
+        "PE 0.8"
+    }
+
+    fn versions(&self) -> &'static [u32] {
+        &[14]
+    }
+
+    fn to_internal_id(&self, wire_id: u8) -> Option<u8> {
+        to_internal_id(PE_07_IDS, wire_id)
+    }
+
+    fn to_wire_id(&self, internal_id: u8) -> Option<u8> {
+        to_wire_id(PE_07_IDS, PE_07_REMOVED, internal_id)
+    }
+
+    fn outbound_layout<'a>(&self, internal_id: u8, body: &'a [u8]) -> Result<Cow<'a, [u8]>> {
+        match internal_id {
+            // SCMessage: prepend an empty source string
+            0x85 => {
+                let mut message = vec![0, 0];
+                message.extend_from_slice(body);
+                Ok(Cow::Owned(message))
+            }
+            _ => Ok(Cow::Borrowed(body)),
+        }
+    }
+
+    fn inbound_layout<'a>(&self, internal_id: u8, body: &'a [u8]) -> Result<Cow<'a, [u8]>> {
+        match internal_id {
+            // Messages sent by the client have a source as well, which we don't need
+            0x85 => {
+                let Some(&[high, low]) = body.get(..2) else {
+                    bail!("Truncated chat message");
+                };
+                let source_len = u16::from_be_bytes([high, low]) as usize;
+                match body.get(2 + source_len..) {
+                    Some(message) => Ok(Cow::Borrowed(message)),
+                    None => bail!("Truncated chat message source"),
+                }
+            }
+            _ => Ok(Cow::Borrowed(body)),
+        }
+    }
+}
@@ -0,0 +1,27 @@
+use crate::constants::SERVER_VERSION;
+
+use super::Protocol;
+
+/// Minecraft Pi edition and Pocket Edition 0.6.x. This is the internal layout, so nothing needs
+/// to be translated.
+pub struct PiProtocol;
+
+pub static PI: PiProtocol = PiProtocol;
+
+impl Protocol for PiProtocol {
+    fn name(&self) -> &'static str {
+        "Pi edition / PE 0.6"
+    }
+
+    fn versions(&self) -> &'static [u32] {
+        &[SERVER_VERSION]
+    }
+
+    fn to_internal_id(&self, wire_id: u8) -> Option<u8> {
+        Some(wire_id)
+    }
+
+    fn to_wire_id(&self, internal_id: u8) -> Option<u8> {
+        Some(internal_id)
+    }
+}
@@ -0,0 +1,190 @@
+//! Protected regions: named cuboids only their owner and members may edit. They are stored in
+//! `level.dat` and checked whenever a player changes a block or opens a container.
+
+use std::collections::BTreeSet;
+
+use anyhow::{bail, Result};
+use mlua::{Lua, LuaSerdeExt, Table, Value};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    world::{BlockPos, World},
+    Server,
+};
+
+/// Longest region name
+pub const MAX_NAME_LENGTH: usize = 32;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Region {
+    pub name: String,
+    /// Lowest corner, inclusive
+    pub min: BlockPos,
+    /// Highest corner, inclusive
+    pub max: BlockPos,
+    pub owner: String,
+    #[serde(default)]
+    pub members: BTreeSet<String>,
+}
+
+impl Region {
+    /// A region between two opposite corners, without members
+    pub fn new(name: &str, (x1, y1, z1): BlockPos, (x2, y2, z2): BlockPos, owner: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            min: (x1.min(x2), y1.min(y2), z1.min(z2)),
+            max: (x1.max(x2), y1.max(y2), z1.max(z2)),
+            owner: owner.to_owned(),
+            members: BTreeSet::new(),
+        }
+    }
+
+    pub fn contains(&self, (x, y, z): BlockPos) -> bool {
+        (self.min.0..=self.max.0).contains(&x)
+            && (self.min.1..=self.max.1).contains(&y)
+            && (self.min.2..=self.max.2).contains(&z)
+    }
+
+    pub fn can_edit(&self, username: &str) -> bool {
+        self.owner == username || self.members.contains(username)
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        validate_name(&self.name)?;
+        if !World::in_bounds(self.min) || !World::in_bounds(self.max) {
+            bail!("Region {} reaches outside of the world", self.name);
+        }
+        Ok(())
+    }
+}
+
+/// Names are used in commands, so they are a single word
+pub fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty() || name.len() > MAX_NAME_LENGTH {
+        bail!("Region names are 1 to {} characters long", MAX_NAME_LENGTH);
+    }
+    if let Some(invalid) = name
+        .chars()
+        .find(|char| !char.is_ascii_alphanumeric() && !matches!(char, '_' | '-'))
+    {
+        bail!("Region name {:?} contains {:?}", name, invalid);
+    }
+    Ok(())
+}
+
+fn runtime_error(err: anyhow::Error) -> mlua::Error {
+    mlua::Error::runtime(err.to_string())
+}
+
+/// Positions are passed as `{x, y, z}` like regions return them
+fn block_pos([x, y, z]: [i32; 3]) -> BlockPos {
+    (x, y, z)
+}
+
+pub fn regions_module(lua: &Lua, server: Server) -> Result<Table> {
+    let regions_module = lua.create_table()?;
+
+    let server_handle = server.clone();
+    let get =
+        lua.create_function(
+            move |lua, name: String| match server_handle.get_region(&name) {
+                Some(region) => lua.to_value(&region),
+                None => Ok(Value::Nil),
+            },
+        )?;
+    regions_module.set("get", get)?;
+
+    let server_handle = server.clone();
+    let all = lua.create_function(move |lua, ()| lua.to_value(&server_handle.get_regions()))?;
+    regions_module.set("all", all)?;
+
+    let server_handle = server.clone();
+    let at = lua.create_function(move |lua, pos: [i32; 3]| {
+        lua.to_value(&server_handle.regions_at(block_pos(pos)))
+    })?;
+    regions_module.set("at", at)?;
+
+    let server_handle = server.clone();
+    let create = lua.create_function(
+        move |_, (name, first, second, owner): (String, [i32; 3], [i32; 3], String)| {
+            let region = Region::new(&name, block_pos(first), block_pos(second), &owner);
+            server_handle.create_region(region).map_err(runtime_error)
+        },
+    )?;
+    regions_module.set("create", create)?;
+
+    let server_handle = server.clone();
+    let remove =
+        lua.create_function(move |_, name: String| Ok(server_handle.remove_region(&name)))?;
+    regions_module.set("remove", remove)?;
+
+    let server_handle = server.clone();
+    let add_member = lua.create_function(move |_, (name, username): (String, String)| {
+        server_handle
+            .update_region(&name, |region| {
+                region.members.insert(username);
+            })
+            .map_err(runtime_error)
+    })?;
+    regions_module.set("add_member", add_member)?;
+
+    let server_handle = server.clone();
+    let remove_member = lua.create_function(move |_, (name, username): (String, String)| {
+        server_handle
+            .update_region(&name, |region| {
+                region.members.remove(&username);
+            })
+            .map_err(runtime_error)
+    })?;
+    regions_module.set("remove_member", remove_member)?;
+
+    let server_handle = server.clone();
+    let set_owner = lua.create_function(move |_, (name, username): (String, String)| {
+        server_handle
+            .update_region(&name, |region| region.owner = username)
+            .map_err(runtime_error)
+    })?;
+    regions_module.set("set_owner", set_owner)?;
+
+    let can_edit = lua.create_function(move |_, (username, pos): (String, [i32; 3])| {
+        Ok(server.can_edit(&username, block_pos(pos)))
+    })?;
+    regions_module.set("can_edit", can_edit)?;
+
+    Ok(regions_module)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn contains_both_corners() {
+        let region = Region::new("spawn", (10, 70, -5), (0, 60, 5), "alex");
+        assert_eq!((region.min, region.max), ((0, 60, -5), (10, 70, 5)));
+        assert!(region.contains((0, 60, -5)));
+        assert!(region.contains((10, 70, 5)));
+        assert!(region.contains((5, 65, 0)));
+        assert!(!region.contains((11, 65, 0)));
+        assert!(!region.contains((5, 59, 0)));
+        assert!(!region.contains((5, 65, 6)));
+    }
+
+    #[test]
+    fn owner_and_members_can_edit() {
+        let mut region = Region::new("spawn", (0, 0, 0), (1, 1, 1), "alex");
+        region.members.insert("sam".to_owned());
+        assert!(region.can_edit("alex"));
+        assert!(region.can_edit("sam"));
+        assert!(!region.can_edit("robin"));
+        assert!(!region.can_edit("Alex"));
+    }
+
+    #[test]
+    fn names_are_single_words() {
+        assert!(validate_name("spawn_area-2").is_ok());
+        assert!(validate_name("").is_err());
+        assert!(validate_name("two words").is_err());
+        assert!(validate_name(&"a".repeat(MAX_NAME_LENGTH + 1)).is_err());
+    }
+}
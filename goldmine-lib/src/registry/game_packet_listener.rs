@@ -0,0 +1,21 @@
+use std::collections::HashSet;
+
+use crate::modded::LuaModValue;
+
+use super::Registry;
+
+pub struct GamePacketListener {
+    /// The `GamePacket` kinds this listener is called for, `None` for all of them
+    pub kinds: Option<HashSet<String>>,
+    pub callback: LuaModValue,
+}
+
+impl GamePacketListener {
+    pub fn is_subscribed(&self, kind: &str) -> bool {
+        self.kinds
+            .as_ref()
+            .is_none_or(|kinds| kinds.contains(kind))
+    }
+}
+
+pub type GamePacketListenerRegistry = Registry<GamePacketListener>;
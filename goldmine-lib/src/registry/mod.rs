@@ -3,12 +3,17 @@ use std::collections::{hash_map::Values, HashMap};
 use anyhow::{Context, Result};
 
 use self::{
-    api_module::ApiModuleRegistry, lua_mod::LuaModRegistry, packet_listener::PacketListenerRegistry,
+    api_module::ApiModuleRegistry, game_packet_listener::GamePacketListenerRegistry,
+    lua_mod::LuaModRegistry, packet_listener::PacketListenerRegistry,
+    random_tick::RandomTickRegistry, world_generator::WorldGeneratorRegistry,
 };
 
 pub mod api_module;
+pub mod game_packet_listener;
 pub mod lua_mod;
 pub mod packet_listener;
+pub mod random_tick;
+pub mod world_generator;
 
 pub struct Registry<V> {
     internal: HashMap<String, V>,
@@ -31,7 +36,11 @@ impl<V> Registry<V> {
             .context(format!("No such key {}", key))
     }
 
-    pub fn values(&self) -> Values<String, V> {
+    pub fn is_empty(&self) -> bool {
+        self.internal.is_empty()
+    }
+
+    pub fn values(&self) -> Values<'_, String, V> {
         self.internal.values()
     }
 }
@@ -44,16 +53,22 @@ impl<V> Default for Registry<V> {
 
 pub struct Registries {
     pub pl_registry: PacketListenerRegistry,
+    pub gpl_registry: GamePacketListenerRegistry,
     pub api_registry: ApiModuleRegistry,
     pub lm_registry: LuaModRegistry,
+    pub wg_registry: WorldGeneratorRegistry,
+    pub rt_registry: RandomTickRegistry,
 }
 
 impl Registries {
     pub fn new() -> Self {
         Self {
             pl_registry: PacketListenerRegistry::new(),
+            gpl_registry: GamePacketListenerRegistry::new(),
             api_registry: ApiModuleRegistry::new(),
             lm_registry: LuaModRegistry::new(),
+            wg_registry: WorldGeneratorRegistry::new(),
+            rt_registry: RandomTickRegistry::new(),
         }
     }
 }
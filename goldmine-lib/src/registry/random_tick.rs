@@ -0,0 +1,14 @@
+use std::collections::HashSet;
+
+use crate::modded::LuaModValue;
+
+use super::Registry;
+
+/// Called with the position, id and aux of a randomly ticked block instead of the built-in rules.
+/// Returning a block id and optionally an aux value replaces the block.
+pub struct RandomTickHandler {
+    pub blocks: HashSet<u8>,
+    pub callback: LuaModValue,
+}
+
+pub type RandomTickRegistry = Registry<RandomTickHandler>;
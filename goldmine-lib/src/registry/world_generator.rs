@@ -0,0 +1,6 @@
+use crate::modded::LuaModValue;
+
+use super::Registry;
+
+/// Lua functions filling chunk columns, selected with the `lua` generator in the config
+pub type WorldGeneratorRegistry = Registry<LuaModValue>;
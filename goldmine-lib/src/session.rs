@@ -0,0 +1,137 @@
+use std::{collections::BTreeMap, time::Instant};
+
+use anyhow::{bail, Result};
+
+use crate::{
+    constants::DEFAULT_MTU,
+    game_packets::ItemStack,
+    protocol::{default_protocol, Protocol},
+    world::{
+        edit::{BlockChanges, Clipboard},
+        BlockPos,
+    },
+};
+
+/// Per connection state
+pub struct Session {
+    pub protocol: &'static dyn Protocol,
+    pub username: Option<String>,
+    pub entity_id: Option<u32>,
+    /// MTU negotiated in `CSConnectionRequest2`
+    pub mtu: u16,
+    /// Sequence number of the next datagram sent to this connection
+    pub send_sequence: u32,
+    /// Message index of the next reliable encapsulation sent to this connection
+    pub reliable_index: u32,
+    /// Id of the next game packet split into fragments
+    pub split_id: u16,
+    /// Chunks requested by the client that haven't been sent yet
+    pub chunk_queue: Vec<(i32, i32)>,
+    /// Window id and position of the container the player has open
+    pub open_container: Option<(u8, BlockPos)>,
+    /// Window id of the last opened container
+    pub window_id: u8,
+    /// Items taken out of the open container, which may be put back in outside of creative mode
+    pub taken_items: TakenItems,
+    /// The two corners of the world edit selection
+    pub selection: [Option<BlockPos>; 2],
+    pub clipboard: Option<Clipboard>,
+    /// Blocks replaced by the last world edits, newest last
+    pub edit_history: Vec<BlockChanges>,
+    /// When the last datagram of the connection arrived
+    pub last_seen: Instant,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self {
+            protocol: default_protocol(),
+            username: None,
+            entity_id: None,
+            mtu: DEFAULT_MTU,
+            send_sequence: 0,
+            reliable_index: 0,
+            split_id: 0,
+            chunk_queue: Vec::new(),
+            open_container: None,
+            window_id: 0,
+            taken_items: TakenItems::default(),
+            selection: [None; 2],
+            clipboard: None,
+            edit_history: Vec::new(),
+            last_seen: Instant::now(),
+        }
+    }
+}
+
+/// Counts of the items a player took out of a container window by id and aux. The server
+/// doesn't track what players carry, so in survival only these can be put in again. The client
+/// can't use them anywhere else while the window is open, so it is cleared with the window.
+#[derive(Clone, Debug, Default)]
+pub struct TakenItems(BTreeMap<(u16, u16), u32>);
+
+impl TakenItems {
+    /// Books a slot changing from `old` to `new`, failing if more is put in than was taken out
+    pub fn change_slot(&mut self, old: ItemStack, new: ItemStack) -> Result<()> {
+        let count = |item: ItemStack| {
+            if item.is_empty() {
+                0
+            } else {
+                u32::from(item.count)
+            }
+        };
+        let (taken, put) =
+            if !old.is_empty() && !new.is_empty() && (old.id, old.aux) == (new.id, new.aux) {
+                let (old, new) = (count(old), count(new));
+                (old.saturating_sub(new), new.saturating_sub(old))
+            } else {
+                (count(old), count(new))
+            };
+        if put > 0 {
+            let carried = self.0.entry((new.id, new.aux)).or_default();
+            if *carried < put {
+                bail!(
+                    "Only items taken out of the container can be put in outside of creative mode"
+                );
+            }
+            *carried -= put;
+        }
+        if taken > 0 {
+            *self.0.entry((old.id, old.aux)).or_default() += taken;
+        }
+        Ok(())
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: u16, count: u8) -> ItemStack {
+        ItemStack { id, count, aux: 0 }
+    }
+
+    #[test]
+    fn only_taken_items_go_back_in() {
+        let mut taken = TakenItems::default();
+        assert!(taken.change_slot(item(0, 0), item(4, 1)).is_err());
+        // Take a stack out of one slot and split it over two others
+        taken
+            .change_slot(item(4, 10), ItemStack::default())
+            .unwrap();
+        taken.change_slot(ItemStack::default(), item(4, 6)).unwrap();
+        taken.change_slot(item(4, 6), item(4, 10)).unwrap();
+        assert!(taken.change_slot(ItemStack::default(), item(4, 1)).is_err());
+        // Swapping puts one stack in and takes the other out
+        assert!(taken.change_slot(item(4, 10), item(5, 3)).is_err());
+        taken.change_slot(item(5, 3), ItemStack::default()).unwrap();
+        taken.change_slot(item(4, 10), item(5, 3)).unwrap();
+        taken.change_slot(ItemStack::default(), item(4, 10)).unwrap();
+        taken.clear();
+        assert!(taken.change_slot(ItemStack::default(), item(4, 1)).is_err());
+    }
+}
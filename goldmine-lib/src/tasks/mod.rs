@@ -1 +1,2 @@
 pub mod packet_listener;
+pub mod tick;
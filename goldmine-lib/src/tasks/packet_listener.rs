@@ -1,13 +1,21 @@
+use std::collections::HashSet;
 use std::net::SocketAddr;
 use std::net::SocketAddrV4;
 
 use anyhow::Result;
 use declio::Decode;
 use declio::Encode;
+use mlua::FromLua;
 use mlua::Function;
 use mlua::LuaSerdeExt;
+use mlua::Value;
 use tokio::{net::UdpSocket, sync::watch::Sender};
 
+use crate::blocks::Blocks;
+use crate::codes::BlockFace;
+use crate::commands;
+use crate::constants::DATAGRAM_OVERHEAD;
+use crate::constants::DEFAULT_MTU;
 use crate::constants::HANDSHAKE_COOKIE;
 use crate::constants::HANDSHAKE_DATA;
 use crate::constants::HANDSHAKE_DOUBLE_NULL;
@@ -15,18 +23,34 @@ use crate::constants::HANDSHAKE_FLAGS;
 use crate::constants::HANDSHAKE_UNKNOWN;
 use crate::constants::MAGIC;
 use crate::constants::NULL_BYTE;
-use crate::constants::SERVER_VERSION;
 use crate::game_packets::Encapsulation;
 use crate::game_packets::GamePacket;
+use crate::protocol;
+use crate::u24::u24;
+use crate::world::explosion::FLINT_AND_STEEL;
+use crate::world::tile_entity::TileEntity;
 use crate::{packets::Packet, Server};
 
 pub async fn packet_listener(server: Server, _sender: Sender<String>) -> Result<()> {
     let socket = UdpSocket::bind(server.addr).await?;
     let mut buffer = Vec::with_capacity(1600);
+    let mut send_buffer = Vec::with_capacity(1600);
 
     loop {
         buffer.clear();
-        match listener_loop(&socket, &mut buffer, &server).await {
+        let result = tokio::select! {
+            received = socket.recv_buf_from(&mut buffer) => match received {
+                Ok((len, sender_addr)) => {
+                    listener_loop(&socket, &buffer[..len], &mut send_buffer, sender_addr, &server)
+                        .await
+                }
+                Err(err) => Err(err.into()),
+            },
+            _ = server.outbox_notify.notified() => {
+                flush_outbox(&socket, &mut send_buffer, &server).await
+            }
+        };
+        match result {
             Ok(_) => (),
             Err(err) => eprintln!("{:?}", err),
         }
@@ -36,14 +60,15 @@ pub async fn packet_listener(server: Server, _sender: Sender<String>) -> Result<
 fn get_connection_id(server: &Server, sender_addr: &SocketAddr) -> Result<u64> {
     let mut connections = server.connections.lock();
     let connection_id : u64;
-    if !connections.contains_left(&sender_addr) {
+    if !connections.contains_left(sender_addr) {
         let mut counter = server.unique_connection_id.lock();
         connection_id = *counter;
         *counter += 1;
         //println!("New connection id {}", connection_id);
         connections.insert(*sender_addr, connection_id);
+        server.sessions.lock().insert(connection_id, Default::default());
     } else {
-        if let Some(id) = connections.get_by_left(&sender_addr) {
+        if let Some(id) = connections.get_by_left(sender_addr) {
             connection_id = *id;
             //println!("Existing connection id {}", connection_id);
         } else {
@@ -53,34 +78,38 @@ fn get_connection_id(server: &Server, sender_addr: &SocketAddr) -> Result<u64> {
     Ok(connection_id)
 }
 
-async fn listener_loop(socket: &UdpSocket, buffer: &mut Vec<u8>, server: &Server) -> Result<()> {
-    let (len, sender_addr) = socket.recv_buf_from(buffer).await?;
+async fn listener_loop(
+    socket: &UdpSocket,
+    datagram: &[u8],
+    buffer: &mut Vec<u8>,
+    sender_addr: SocketAddr,
+    server: &Server,
+) -> Result<()> {
     if let SocketAddr::V4(socket_addr) = sender_addr {
         let connection_id : u64 = get_connection_id(server, &sender_addr)?;
-        let packet = receive_packet(&buffer[..len], server, connection_id)?;
-        if let Packet::Custom {
-            count,
-            encapsulated: _,
-        } = &packet
-        {
-            send_packet(
-                buffer,
-                socket,
-                connection_id,
-                Packet::ACK {
-                    count: 1,
-                    single_value: true,
-                    packet_num: *count,
-                    packet_num_range: Default::default(),
-                },
-                server,
-            )
-            .await?;
-        }
-        if let Some(return_packets) = handle_packet(packet, server, &socket_addr)? {
-            for return_packet in return_packets {
-                send_packet(buffer, socket, connection_id, return_packet, server).await?;
+        server.mark_seen(connection_id);
+        let has_packet_listeners = !server.registries.lock().pl_registry.is_empty();
+        let return_packets = match Packet::borrow_custom(datagram) {
+            // Nobody wants to see the raw packet, so the game packets are decoded straight out of
+            // the receive buffer
+            Some((count, encapsulated)) if !has_packet_listeners => {
+                send_ack(buffer, socket, connection_id, count, server).await?;
+                Some(handle_encapsulated(encapsulated, server, connection_id)?)
+            }
+            _ => {
+                let packet = receive_packet(datagram, server, connection_id)?;
+                if let Packet::Custom {
+                    count,
+                    encapsulated: _,
+                } = &packet
+                {
+                    send_ack(buffer, socket, connection_id, *count, server).await?;
+                }
+                handle_packet(packet, server, &socket_addr, connection_id)?
             }
+        };
+        for return_packet in return_packets.into_iter().flatten() {
+            send_packet(buffer, socket, connection_id, return_packet, server).await?;
         }
         Ok(())
     } else {
@@ -88,6 +117,50 @@ async fn listener_loop(socket: &UdpSocket, buffer: &mut Vec<u8>, server: &Server
     }
 }
 
+async fn send_ack(
+    buffer: &mut Vec<u8>,
+    socket: &UdpSocket,
+    connection_id: u64,
+    count: u24,
+    server: &Server,
+) -> Result<()> {
+    send_packet(
+        buffer,
+        socket,
+        connection_id,
+        Packet::ACK {
+            count: 1,
+            single_value: true,
+            packet_num: count,
+            packet_num_range: Default::default(),
+        },
+        server,
+    )
+    .await
+}
+
+async fn flush_outbox(socket: &UdpSocket, buffer: &mut Vec<u8>, server: &Server) -> Result<()> {
+    let queued = std::mem::take(&mut *server.outbox.lock());
+    // Keep the order but batch the packets of each connection into as few datagrams as possible
+    let mut by_connection: Vec<(u64, Vec<GamePacket>)> = Vec::new();
+    for (connection_id, game_packet) in queued {
+        match by_connection.iter_mut().find(|(id, _)| *id == connection_id) {
+            Some((_, game_packets)) => game_packets.push(game_packet),
+            None => by_connection.push((connection_id, vec![game_packet])),
+        }
+    }
+    for (connection_id, game_packets) in by_connection {
+        if !server.connections.lock().contains_right(&connection_id) {
+            eprintln!("Dropping packets for unknown connection_id {}", connection_id);
+            continue;
+        }
+        for packet in wrap_game_packets(game_packets, server, connection_id)? {
+            send_packet(buffer, socket, connection_id, packet, server).await?;
+        }
+    }
+    Ok(())
+}
+
 fn receive_packet(mut buffer: &[u8], server: &Server, connection_id: u64) -> Result<Packet> {
     //println!("IN:  {:x?}", &buffer);
     let mut packet = Packet::decode((), &mut buffer)?;
@@ -102,7 +175,8 @@ async fn send_packet(
     mut packet: Packet,
     server: &Server,
 ) -> Result<()> {
-    if let Some(addr) = server.connections.lock().get_by_right(&connection_id) {
+    let addr = server.connections.lock().get_by_right(&connection_id).copied();
+    if let Some(addr) = addr {
         packet = execute_pl_callbacks(packet, server, false, connection_id)?;
         buffer.clear();
         packet.encode((), buffer)?;
@@ -110,13 +184,18 @@ async fn send_packet(
         socket.send_to(buffer, addr).await?;
         Ok(())
     } else {
-        panic!("Unknown connection_id {}", connection_id);
+        // The connection ended while handling its datagram
+        Err(anyhow::Error::msg(format!("Unknown connection_id {}", connection_id)))
     }
     
 }
 
 fn execute_pl_callbacks(mut packet: Packet, server: &Server, inbound: bool, connection_id: u64) -> Result<Packet> {
-    for pl in server.registries.lock().pl_registry.values() {
+    let registries = server.registries.lock();
+    if registries.pl_registry.is_empty() {
+        return Ok(packet);
+    }
+    for pl in registries.pl_registry.values() {
         let lua_lock = server.lua.lock();
         let pl_callback: Function = lua_lock.registry_value(pl)?;
         packet = lua_lock.from_value(pl_callback.call((lua_lock.create_ser_userdata(packet)?, inbound, connection_id))?)?;
@@ -124,10 +203,143 @@ fn execute_pl_callbacks(mut packet: Packet, server: &Server, inbound: bool, conn
     Ok(packet)
 }
 
+/// Runs the game packet listeners subscribed to the packet's kind. Listeners may return nil to
+/// drop the packet, a replacement packet or a table of packets.
+fn execute_gpl_callbacks(
+    packet: GamePacket,
+    server: &Server,
+    inbound: bool,
+    connection_id: u64,
+) -> Result<Vec<GamePacket>> {
+    // The registries are unlocked before calling the listeners, they may register new ones
+    let listeners: Vec<(Option<HashSet<String>>, Function)> = {
+        let registries = server.registries.lock();
+        let kind = packet.kind();
+        if !registries
+            .gpl_registry
+            .values()
+            .any(|gpl| gpl.is_subscribed(kind))
+        {
+            return Ok(vec![packet]);
+        }
+        let lua_lock = server.lua.lock();
+        registries
+            .gpl_registry
+            .values()
+            .map(|gpl| Ok((gpl.kinds.clone(), lua_lock.registry_value(&gpl.callback)?)))
+            .collect::<Result<_>>()?
+    };
+    let mut packets = vec![packet];
+    for (kinds, gpl_callback) in listeners {
+        let lua_lock = server.lua.lock();
+        let mut next_packets = Vec::with_capacity(packets.len());
+        for packet in packets {
+            if kinds
+                .as_ref()
+                .is_some_and(|kinds| !kinds.contains(packet.kind()))
+            {
+                next_packets.push(packet);
+                continue;
+            }
+            match gpl_callback.call::<Value>((packet, inbound, connection_id))? {
+                Value::Nil => (),
+                Value::Table(returned) => {
+                    for packet in returned.sequence_values::<GamePacket>() {
+                        next_packets.push(packet?);
+                    }
+                }
+                other => next_packets.push(GamePacket::from_lua(other, &lua_lock)?),
+            }
+        }
+        packets = next_packets;
+    }
+    Ok(packets)
+}
+
+/// Runs the outbound game packet listeners and encapsulates the remaining packets into
+/// datagrams. Small packets share a datagram as long as it fits into the MTU, packets too big for
+/// a single datagram are split into reliable fragments.
+fn wrap_game_packets(
+    game_packets: Vec<GamePacket>,
+    server: &Server,
+    connection_id: u64,
+) -> Result<Vec<Packet>> {
+    let max_payload = usize::from(server.get_mtu(connection_id)) - DATAGRAM_OVERHEAD;
+    let mut packets = Vec::new();
+    let mut encapsulated = Vec::new();
+    let mut payload = 0;
+    for game_packet in game_packets {
+        for game_packet in execute_gpl_callbacks(game_packet, server, false, connection_id)? {
+            // The login might have switched the protocol
+            let protocol = server.get_protocol(connection_id);
+            let mut game_packet_bytes = Vec::new();
+            if protocol::encode_game_packet(protocol, &game_packet, &mut game_packet_bytes).is_err()
+            {
+                continue;
+            }
+            if payload + game_packet_bytes.len() > max_payload && !encapsulated.is_empty() {
+                packets.push(Packet::Custom {
+                    count: server.next_sequence(connection_id),
+                    encapsulated: std::mem::take(&mut encapsulated),
+                });
+                payload = 0;
+            }
+            if game_packet_bytes.len() > max_payload {
+                let fragments =
+                    split_game_packet(&game_packet_bytes, max_payload, server, connection_id);
+                for fragment in fragments {
+                    packets.push(Packet::Custom {
+                        count: server.next_sequence(connection_id),
+                        encapsulated: vec![fragment],
+                    });
+                }
+                continue;
+            }
+            payload += game_packet_bytes.len();
+            encapsulated.push(Encapsulation::Simple {
+                length: (game_packet_bytes.len() * 8) as u16,
+                game_packet: game_packet_bytes,
+            });
+        }
+    }
+    if !encapsulated.is_empty() {
+        packets.push(Packet::Custom {
+            count: server.next_sequence(connection_id),
+            encapsulated,
+        });
+    }
+    Ok(packets)
+}
+
+/// Splits an encoded game packet into fragments of at most `max_payload` bytes. Fragments have to
+/// be sent reliably, but as acknowledgements are not tracked yet they are never resent.
+fn split_game_packet(
+    game_packet: &[u8],
+    max_payload: usize,
+    server: &Server,
+    connection_id: u64,
+) -> Vec<Encapsulation> {
+    let split_id = server.next_split_id(connection_id);
+    let split_count = game_packet.len().div_ceil(max_payload) as u32;
+    game_packet
+        .chunks(max_payload)
+        .enumerate()
+        .map(|(split_index, fragment)| Encapsulation::ExtendedCountSplit {
+            length: (fragment.len() * 8) as u16,
+            count: server.next_reliable_index(connection_id),
+            split_count,
+            split_id,
+            split_index: split_index as u32,
+            game_packet: fragment.to_vec(),
+        })
+        .collect()
+}
+
 fn handle_packet(
     packet: Packet,
     server: &Server,
     sender_addr: &SocketAddrV4,
+    connection_id: u64,
 ) -> Result<Option<Vec<Packet>>> {
     let return_packet = match packet {
         Packet::CSPingConnections { ping_id, magic: _ } => Some(vec![Packet::SCPongConnections {
@@ -144,60 +356,69 @@ fn handle_packet(
             magic: MAGIC,
             server_id: server.guid,
             null_byte: NULL_BYTE,
-            mtu: 1447,
+            mtu: DEFAULT_MTU,
         }]),
         Packet::CSConnectionRequest2 {
             magic: _,
             server_addr: _,
             server_port: _,
             mtu,
-        } => Some(vec![Packet::SCConnectionReply2 {
-            magic: MAGIC,
-            server_id: server.guid,
-            client_ip_type: 0x04,
-            client_ip: sender_addr.ip().octets(),
-            client_port: sender_addr.port(),
-            mtu,
-            null_byte: NULL_BYTE,
-        }]),
-        Packet::Custom {
-            count,
-            encapsulated,
         } => {
-            let mut returns = Vec::new();
-            for encapsulation in encapsulated {
-                let encapsulated_bytes = encapsulation.to_game_packet();
-                println!("IN:  {:x?}", encapsulated_bytes);
-                let packet = GamePacket::decode((), &mut encapsulated_bytes.as_slice())?;
-                if let Some(return_packets) = handle_game_packet(packet, server)? {
-                    return_packets.iter().for_each(|x| {
-                        let mut x_bytes = Vec::new();
-                        if x.encode((), &mut x_bytes).is_ok() {
-                            returns.push(Encapsulation::Simple {
-                                length: (x_bytes.len() * 8) as u16,
-                                game_packet: x_bytes,
-                            })
-                        };
-                    });
-                }
-            }
-            if !returns.is_empty() {
-                Some(vec![Packet::Custom {
-                    count,
-                    encapsulated: returns,
-                }])
-            } else {
-                None
-            }
+            let mtu = mtu.min(DEFAULT_MTU);
+            server.set_mtu(connection_id, mtu);
+            Some(vec![Packet::SCConnectionReply2 {
+                magic: MAGIC,
+                server_id: server.guid,
+                client_ip_type: 0x04,
+                client_ip: sender_addr.ip().octets(),
+                client_port: sender_addr.port(),
+                mtu,
+                null_byte: NULL_BYTE,
+            }])
         }
+        Packet::Custom {
+            count: _,
+            encapsulated,
+        } => Some(handle_encapsulated(
+            encapsulated.iter().map(Encapsulation::game_packet),
+            server,
+            connection_id,
+        )?),
         _ => None,
     };
     Ok(return_packet)
 }
 
-fn handle_game_packet(game_packet: GamePacket, server: &Server) -> Result<Option<Vec<GamePacket>>> {
+fn handle_encapsulated<'a>(
+    encapsulated: impl Iterator<Item = &'a [u8]>,
+    server: &Server,
+    connection_id: u64,
+) -> Result<Vec<Packet>> {
+    let mut returns = Vec::new();
+    for encapsulated_bytes in encapsulated {
+        //println!("IN:  {:x?}", encapsulated_bytes);
+        let protocol = server.get_protocol(connection_id);
+        let packet = protocol::decode_game_packet(protocol, encapsulated_bytes)?;
+        for packet in execute_gpl_callbacks(packet, server, true, connection_id)? {
+            if let Some(return_packets) = handle_game_packet(packet, server, connection_id)? {
+                returns.extend(return_packets);
+            }
+        }
+    }
+    wrap_game_packets(returns, server, connection_id)
+}
+
+fn handle_game_packet(
+    game_packet: GamePacket,
+    server: &Server,
+    connection_id: u64,
+) -> Result<Option<Vec<GamePacket>>> {
     let return_packet = match game_packet {
         GamePacket::CSPing { ping_id } => Some(vec![GamePacket::SCPong { ping_id, pong_id: 0 }]),
+        GamePacket::CSClientCancelConnect {} => {
+            server.end_session(connection_id);
+            None
+        }
         GamePacket::CSClientConnect {
             client_id: _,
             session,
@@ -213,18 +434,38 @@ fn handle_game_packet(game_packet: GamePacket, server: &Server) -> Result<Option
         }]),
         GamePacket::CSLogin {
             username_len: _,
-            username: _,
+            username,
             proto1,
             proto2: _,
         } => {
-            let login_status = GamePacket::SCLoginStatus {
-                status: match proto1.cmp(&SERVER_VERSION) {
-                    std::cmp::Ordering::Less => 1,
-                    std::cmp::Ordering::Equal => 0,
-                    std::cmp::Ordering::Greater => 2,
-                },
+            let Some(protocol) = protocol::by_version(proto1) else {
+                if proto1 < protocol::oldest_version() {
+                    return Ok(Some(vec![GamePacket::SCLoginStatus { status: 1 }]));
+                }
+                if proto1 > protocol::newest_version() {
+                    return Ok(Some(vec![GamePacket::SCLoginStatus { status: 2 }]));
+                }
+                // Neither the client nor the server is outdated, and the clients have no login
+                // status for that, so they are told why and disconnected
+                eprintln!("Rejecting {}, no matching protocol for version {}", username, proto1);
+                return Ok(Some(vec![
+                    GamePacket::message(format!("No matching protocol for version {}", proto1)),
+                    GamePacket::CSClientCancelConnect {},
+                ]));
             };
-            let player = server.add_player();
+            if server.is_logged_in_elsewhere(connection_id, &username) {
+                // The clients have no login status for this, so they are disconnected
+                eprintln!("Rejecting a second login of {}", username);
+                return Ok(Some(vec![GamePacket::CSClientCancelConnect {}]));
+            }
+            let login_status = GamePacket::SCLoginStatus { status: 0 };
+            let player = server.add_player(&username)?;
+            let spawn = server.get_player_spawn(&username);
+            if let Some(session) = server.sessions.lock().get_mut(&connection_id) {
+                session.protocol = protocol;
+                session.username = Some(username);
+                session.entity_id = Some(player.id);
+            }
             let start_game = GamePacket::SCStartGame {
                 seed: server.get_seed(),
                 worldgen_version: 4,
@@ -234,7 +475,193 @@ fn handle_game_packet(game_packet: GamePacket, server: &Server) -> Result<Option
                 pos_y: player.pos.1,
                 pos_z: player.pos.2,
             };
-            Some(vec![login_status, start_game])
+            Some(vec![
+                login_status,
+                start_game,
+                server.time_packet(),
+                Server::spawn_packet(spawn),
+            ])
+        }
+        GamePacket::MovePlayer {
+            entity_id: _,
+            pos_x,
+            pos_y,
+            pos_z,
+            rot_y,
+            rot_x,
+        } => {
+            if let Some(entity_id) = server.get_entity_id(connection_id) {
+                server.move_entity(entity_id, (pos_x, pos_y, pos_z), (rot_x, rot_y, 0.0));
+            }
+            None
+        }
+        GamePacket::Respawn { .. } => {
+            let (Some(entity_id), Some(username)) = (
+                server.get_entity_id(connection_id),
+                server.get_username(connection_id),
+            ) else {
+                return Ok(None);
+            };
+            let pos = server.find_player_spawn(&username)?;
+            server.move_entity(entity_id, pos, (0.0, 0.0, 0.0));
+            server.heal_entity(entity_id);
+            // Other players would keep seeing the body where the player died
+            server.broadcast_game_packet(
+                GamePacket::MovePlayer {
+                    entity_id,
+                    pos_x: pos.0,
+                    pos_y: pos.1,
+                    pos_z: pos.2,
+                    rot_y: 0.0,
+                    rot_x: 0.0,
+                },
+                Some(connection_id),
+            );
+            Some(vec![GamePacket::Respawn {
+                entity_id,
+                pos_x: pos.0,
+                pos_y: pos.1,
+                pos_z: pos.2,
+            }])
+        }
+        GamePacket::PlaceBlock {
+            entity_id: _,
+            pos_x,
+            pos_z,
+            pos_y,
+            block_id,
+            block_aux,
+            face,
+        } => {
+            let clicked = (pos_x as i32, i32::from(pos_y), pos_z as i32);
+            let target = server.placement_target(clicked, face);
+            match server.place_block(connection_id, target, block_id, block_aux) {
+                Ok(()) => {
+                    let update = server.block_update_packet(target);
+                    server.broadcast_game_packet(update, Some(connection_id));
+                    None
+                }
+                Err(err) => {
+                    eprintln!("Rejected block placement: {:?}", err);
+                    Some(vec![server.block_update_packet(target)])
+                }
+            }
+        }
+        GamePacket::RemoveBlock {
+            entity_id: _,
+            pos_x,
+            pos_z,
+            pos_y,
+        } => {
+            let pos = (pos_x as i32, i32::from(pos_y), pos_z as i32);
+            match server.remove_block(connection_id, pos) {
+                Ok(()) => {
+                    let update = server.block_update_packet(pos);
+                    server.broadcast_game_packet(update, Some(connection_id));
+                    server.close_removed_container(pos);
+                    None
+                }
+                Err(err) => {
+                    eprintln!("Rejected block removal: {:?}", err);
+                    Some(vec![server.block_update_packet(pos)])
+                }
+            }
+        }
+        GamePacket::CSUseItem {
+            pos_x,
+            pos_y,
+            pos_z,
+            face,
+            item_id,
+            ..
+        } if face != BlockFace::NoFace => {
+            let pos = (pos_x as i32, pos_y as i32, pos_z as i32);
+            if item_id == FLINT_AND_STEEL
+                && server.get_block(pos).is_some_and(|(id, _)| id == Blocks::Tnt.id())
+            {
+                if let Err(err) = server.ignite_tnt(connection_id, pos) {
+                    eprintln!("Rejected TNT ignition: {:?}", err);
+                    return Ok(Some(vec![server.block_update_packet(pos)]));
+                }
+                return Ok(None);
+            }
+            let is_container = server
+                .get_block(pos)
+                .and_then(|(id, _)| TileEntity::for_block(id))
+                .is_some_and(|tile_entity| tile_entity.window_type().is_some());
+            // Using items on other blocks is left to the client
+            if is_container {
+                match server.open_container(connection_id, pos) {
+                    Ok(packets) => Some(packets),
+                    Err(err) => {
+                        eprintln!("Rejected container open: {:?}", err);
+                        None
+                    }
+                }
+            } else {
+                None
+            }
+        }
+        GamePacket::ContainerClose { window_id } => {
+            server.close_container(connection_id, window_id);
+            None
+        }
+        GamePacket::ContainerSetSlot {
+            window_id,
+            slot,
+            item,
+        } => {
+            match server.set_container_slot(connection_id, window_id, slot, item) {
+                Ok(()) => None,
+                Err(err) => {
+                    eprintln!("Rejected container change: {:?}", err);
+                    server
+                        .container_slot_packet(connection_id, window_id, slot)
+                        .map(|packet| vec![packet])
+                }
+            }
+        }
+        GamePacket::SignUpdate {
+            pos_x,
+            pos_y,
+            pos_z,
+            line_1,
+            line_2,
+            line_3,
+            line_4,
+            ..
+        } => {
+            let pos = (i32::from(pos_x), i32::from(pos_y), i32::from(pos_z));
+            match server.update_sign(connection_id, pos, [line_1, line_2, line_3, line_4]) {
+                Ok(()) => {
+                    if let Some(update) = server.tile_entity_packet(pos) {
+                        server.broadcast_game_packet(update, Some(connection_id));
+                    }
+                    None
+                }
+                Err(err) => {
+                    eprintln!("Rejected sign update: {:?}", err);
+                    // Without a stored text the sign is cleared for the sender as well
+                    let text = server
+                        .tile_entity_packet(pos)
+                        .unwrap_or_else(|| GamePacket::sign_update(pos, Default::default()));
+                    Some(vec![text])
+                }
+            }
+        }
+        GamePacket::CSChat {
+            message_len: _,
+            message,
+        } if message.starts_with('/') => {
+            let reply = match commands::execute(server, connection_id, &message[1..]) {
+                Ok(reply) => reply,
+                Err(err) => err.to_string(),
+            };
+            Some(vec![GamePacket::message(reply)])
+        }
+        GamePacket::CSRequestChunk { index_x, index_z } => {
+            server.queue_chunk(connection_id, index_x as i32, index_z as i32);
+            None
         }
         _ => None,
     };
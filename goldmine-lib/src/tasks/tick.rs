@@ -0,0 +1,74 @@
+use std::time::Duration;
+
+use anyhow::Result;
+
+use crate::{game_packets::GamePacket, Server};
+
+/// The game runs at 20 ticks per second
+pub const TICK_DURATION: Duration = Duration::from_millis(50);
+
+/// Chunks sent to each connection per tick, a chunk is about 35 datagrams
+const CHUNKS_PER_TICK: usize = 4;
+
+/// The clients advance the time on their own, it is resent every 10 seconds to keep them in
+/// sync. The clients can't stop their cycle, so a frozen time is resent every second.
+const TIME_SYNC_TICKS: u64 = 200;
+const FROZEN_TIME_SYNC_TICKS: u64 = 20;
+
+pub async fn tick(server: Server) -> Result<()> {
+    let mut interval = tokio::time::interval(TICK_DURATION);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+    let autosave_ticks = server.config.autosave_interval * 1000 / TICK_DURATION.as_millis() as u64;
+    let mut tick: u64 = 0;
+
+    loop {
+        interval.tick().await;
+        tick += 1;
+        server.advance_time();
+        let time_sync_ticks = if server.is_time_frozen() {
+            FROZEN_TIME_SYNC_TICKS
+        } else {
+            TIME_SYNC_TICKS
+        };
+        if tick.is_multiple_of(time_sync_ticks) {
+            server.broadcast_game_packet(server.time_packet(), None);
+        }
+        server.tick_physics();
+        server.tick_furnaces();
+        server.tick_tnt();
+        if let Err(err) = server.tick_random() {
+            eprintln!("Failed to run the random ticks: {:?}", err);
+        }
+        send_queued_chunks(&server);
+        if autosave_ticks != 0 && tick.is_multiple_of(autosave_ticks) {
+            server.autosave();
+        }
+    }
+}
+
+fn send_queued_chunks(server: &Server) {
+    for (connection_id, chunks) in server.take_queued_chunks(CHUNKS_PER_TICK) {
+        for (chunk_x, chunk_z) in chunks {
+            match server.get_chunk_data(chunk_x, chunk_z) {
+                Ok(Some(chunk_data)) => {
+                    server.send_game_packet(
+                        connection_id,
+                        GamePacket::SCChunkDataPacket {
+                            index_x: chunk_x as u32,
+                            index_z: chunk_z as u32,
+                            chunk_data,
+                        },
+                    );
+                    for packet in server.chunk_tile_entity_packets(chunk_x, chunk_z) {
+                        server.send_game_packet(connection_id, packet);
+                    }
+                }
+                Ok(None) => (),
+                Err(err) => eprintln!(
+                    "Failed to generate chunk {} {}: {:?}",
+                    chunk_x, chunk_z, err
+                ),
+            }
+        }
+    }
+}
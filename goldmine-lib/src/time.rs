@@ -0,0 +1,58 @@
+//! The world clock. A day of the Pi client lasts 19200 ticks, starting at sunrise.
+
+use anyhow::Result;
+use mlua::{Either, Lua, Table};
+
+use crate::Server;
+
+pub const DAY_LENGTH: i64 = 19200;
+
+/// Times of day that can be set by name
+pub const NAMED_TIMES: [(&str, i64); 6] = [
+    ("sunrise", 0),
+    ("day", 800),
+    ("noon", DAY_LENGTH / 4),
+    ("sunset", DAY_LENGTH / 2),
+    ("night", DAY_LENGTH / 2 + 800),
+    ("midnight", DAY_LENGTH * 3 / 4),
+];
+
+pub fn named_time(name: &str) -> Option<i64> {
+    NAMED_TIMES
+        .iter()
+        .find(|(time_name, _)| *time_name == name)
+        .map(|(_, time)| *time)
+}
+
+pub fn time_module(lua: &Lua, server: Server) -> Result<Table> {
+    let time_module = lua.create_table()?;
+    time_module.set("DAY_LENGTH", DAY_LENGTH)?;
+
+    let server_handle = server.clone();
+    let get = lua.create_function(move |_, ()| Ok(server_handle.get_time()))?;
+    time_module.set("get", get)?;
+
+    let server_handle = server.clone();
+    let set = lua.create_function(move |_, time: Either<i64, String>| {
+        let time = match time {
+            Either::Left(time) => time,
+            Either::Right(name) => named_time(&name)
+                .ok_or_else(|| mlua::Error::runtime(format!("Unknown time {}", name)))?,
+        };
+        server_handle.set_time(time);
+        Ok(())
+    })?;
+    time_module.set("set", set)?;
+
+    let server_handle = server.clone();
+    let is_frozen = lua.create_function(move |_, ()| Ok(server_handle.is_time_frozen()))?;
+    time_module.set("is_frozen", is_frozen)?;
+
+    let freeze = lua.create_function(move |_, frozen: Option<bool>| {
+        server.set_time_frozen(frozen.unwrap_or(true));
+        Ok(())
+    })?;
+    time_module.set("freeze", freeze)?;
+
+    Ok(time_module)
+}
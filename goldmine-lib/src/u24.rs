@@ -110,6 +110,19 @@ impl u24 {
     }
 }
 
+impl From<u32> for u24 {
+    /// Truncates to the lower 24 bits
+    fn from(value: u32) -> Self {
+        u24(value & 0x00ff_ffff)
+    }
+}
+
+impl From<u24> for u32 {
+    fn from(value: u24) -> Self {
+        value.0
+    }
+}
+
 impl Encode<Endian> for u24 {
     fn encode<W>(&self, ctx: Endian, writer: &mut W) -> Result<(), declio::Error>
     where
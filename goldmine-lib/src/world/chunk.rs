@@ -0,0 +1,214 @@
+use mlua::{UserData, UserDataMethods};
+
+use super::{CHUNK_HEIGHT, CHUNK_WIDTH};
+use crate::constants::CHUNK_DATA_LEN;
+
+pub const CHUNK_VOLUME: usize = CHUNK_WIDTH * CHUNK_WIDTH * CHUNK_HEIGHT;
+
+/// Packed 4 bit values, the even index in the low nibble
+#[derive(Clone)]
+pub struct NibbleArray(Box<[u8; CHUNK_VOLUME / 2]>);
+
+impl NibbleArray {
+    pub fn new(fill: u8) -> Self {
+        let fill = fill & 0x0f;
+        Self(Box::new([fill | (fill << 4); CHUNK_VOLUME / 2]))
+    }
+
+    pub fn get(&self, index: usize) -> u8 {
+        let byte = self.0[index >> 1];
+        if index & 1 == 0 {
+            byte & 0x0f
+        } else {
+            byte >> 4
+        }
+    }
+
+    pub fn set(&mut self, index: usize, value: u8) {
+        let byte = &mut self.0[index >> 1];
+        if index & 1 == 0 {
+            *byte = (*byte & 0xf0) | (value & 0x0f);
+        } else {
+            *byte = (*byte & 0x0f) | ((value & 0x0f) << 4);
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        self.0.as_slice()
+    }
+
+    pub fn as_bytes_mut(&mut self) -> &mut [u8] {
+        self.0.as_mut_slice()
+    }
+}
+
+/// A 16x16x128 column of blocks. Coordinates are local to the chunk.
+#[derive(Clone)]
+pub struct Chunk {
+    blocks: Box<[u8; CHUNK_VOLUME]>,
+    aux: NibbleArray,
+    sky_light: NibbleArray,
+    block_light: NibbleArray,
+    dirty: bool,
+}
+
+impl Chunk {
+    pub fn new() -> Self {
+        Self {
+            blocks: Box::new([0; CHUNK_VOLUME]),
+            aux: NibbleArray::new(0),
+            sky_light: NibbleArray::new(15),
+            block_light: NibbleArray::new(0),
+            dirty: false,
+        }
+    }
+
+    /// Same layout as the Pi edition uses on disk
+    pub fn index(x: usize, y: usize, z: usize) -> usize {
+        debug_assert!(x < CHUNK_WIDTH && y < CHUNK_HEIGHT && z < CHUNK_WIDTH);
+        (x << 11) | (z << 7) | y
+    }
+
+    pub fn get_block(&self, x: usize, y: usize, z: usize) -> (u8, u8) {
+        let index = Self::index(x, y, z);
+        (self.blocks[index], self.aux.get(index))
+    }
+
+    pub fn set_block(&mut self, x: usize, y: usize, z: usize, id: u8, aux: u8) {
+        let index = Self::index(x, y, z);
+        self.blocks[index] = id;
+        self.aux.set(index, aux);
+        self.dirty = true;
+    }
+
+    pub fn get_sky_light(&self, x: usize, y: usize, z: usize) -> u8 {
+        self.sky_light.get(Self::index(x, y, z))
+    }
+
+    pub fn set_sky_light(&mut self, x: usize, y: usize, z: usize, level: u8) {
+        self.sky_light.set(Self::index(x, y, z), level);
+        self.dirty = true;
+    }
+
+    pub fn get_block_light(&self, x: usize, y: usize, z: usize) -> u8 {
+        self.block_light.get(Self::index(x, y, z))
+    }
+
+    pub fn set_block_light(&mut self, x: usize, y: usize, z: usize, level: u8) {
+        self.block_light.set(Self::index(x, y, z), level);
+        self.dirty = true;
+    }
+
+    /// The y of the highest non air block, `None` for an empty column
+    pub fn get_height(&self, x: usize, z: usize) -> Option<usize> {
+        (0..CHUNK_HEIGHT)
+            .rev()
+            .find(|y| self.blocks[Self::index(x, *y, z)] != 0)
+    }
+
+    pub fn blocks(&self) -> &[u8] {
+        self.blocks.as_slice()
+    }
+
+    pub fn blocks_mut(&mut self) -> &mut [u8] {
+        self.dirty = true;
+        self.blocks.as_mut_slice()
+    }
+
+    pub fn aux(&self) -> &NibbleArray {
+        &self.aux
+    }
+
+    pub fn aux_mut(&mut self) -> &mut NibbleArray {
+        self.dirty = true;
+        &mut self.aux
+    }
+
+    pub fn sky_light(&self) -> &NibbleArray {
+        &self.sky_light
+    }
+
+    pub fn sky_light_mut(&mut self) -> &mut NibbleArray {
+        self.dirty = true;
+        &mut self.sky_light
+    }
+
+    pub fn block_light(&self) -> &NibbleArray {
+        &self.block_light
+    }
+
+    pub fn block_light_mut(&mut self) -> &mut NibbleArray {
+        self.dirty = true;
+        &mut self.block_light
+    }
+
+    /// Encodes the blocks the way `SCChunkDataPacket` expects them: for every column (x fastest)
+    /// a byte flagging which of the 8 sections follow, then per section 16 block ids and 8 bytes
+    /// of aux nibbles from bottom to top. There is no room for light, the clients calculate it
+    /// themselves when a chunk arrives.
+    pub fn to_network_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(CHUNK_DATA_LEN);
+        for column in 0..CHUNK_WIDTH * CHUNK_WIDTH {
+            let base = Self::index(column & 15, 0, column >> 4);
+            bytes.push(0xff);
+            for section in (0..CHUNK_HEIGHT).step_by(16) {
+                let start = base + section;
+                bytes.extend_from_slice(&self.blocks[start..start + 16]);
+                bytes.extend_from_slice(&self.aux.as_bytes()[start / 2..(start + 16) / 2]);
+            }
+        }
+        bytes
+    }
+
+    /// Whether the chunk changed since it was last saved
+    pub fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    pub fn mark_dirty(&mut self) {
+        self.dirty = true;
+    }
+
+    pub fn clear_dirty(&mut self) {
+        self.dirty = false;
+    }
+}
+
+impl Default for Chunk {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn local_pos(x: usize, y: usize, z: usize) -> mlua::Result<(usize, usize, usize)> {
+    if x < CHUNK_WIDTH && y < CHUNK_HEIGHT && z < CHUNK_WIDTH {
+        Ok((x, y, z))
+    } else {
+        Err(mlua::Error::runtime(format!(
+            "{} {} {} is outside of the chunk",
+            x, y, z
+        )))
+    }
+}
+
+/// Exposed to Lua generators with chunk local coordinates
+impl UserData for Chunk {
+    fn add_methods<M: UserDataMethods<Self>>(methods: &mut M) {
+        methods.add_method("get_block", |_, chunk, (x, y, z): (usize, usize, usize)| {
+            let (x, y, z) = local_pos(x, y, z)?;
+            Ok(chunk.get_block(x, y, z))
+        });
+        methods.add_method_mut(
+            "set_block",
+            |_, chunk, (x, y, z, id, aux): (usize, usize, usize, u8, Option<u8>)| {
+                let (x, y, z) = local_pos(x, y, z)?;
+                chunk.set_block(x, y, z, id, aux.unwrap_or(0));
+                Ok(())
+            },
+        );
+        methods.add_method("get_height", |_, chunk, (x, z): (usize, usize)| {
+            let (x, _, z) = local_pos(x, 0, z)?;
+            Ok(chunk.get_height(x, z))
+        });
+    }
+}
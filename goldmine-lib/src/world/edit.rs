@@ -0,0 +1,203 @@
+//! World editing for builders: filling, replacing, copying and pasting whole cuboids of blocks at
+//! once.
+
+use std::collections::BTreeSet;
+
+use super::{tile_entity::TileEntity, BlockPos, World, CHUNK_WIDTH};
+
+/// A box between two inclusive corners
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Cuboid {
+    pub min: BlockPos,
+    pub max: BlockPos,
+}
+
+impl Cuboid {
+    /// The cuboid spanned by two opposite corners
+    pub fn new((x1, y1, z1): BlockPos, (x2, y2, z2): BlockPos) -> Self {
+        Self {
+            min: (x1.min(x2), y1.min(y2), z1.min(z2)),
+            max: (x1.max(x2), y1.max(y2), z1.max(z2)),
+        }
+    }
+
+    pub fn volume(&self) -> usize {
+        let (x, y, z) = (
+            self.max.0 - self.min.0 + 1,
+            self.max.1 - self.min.1 + 1,
+            self.max.2 - self.min.2 + 1,
+        );
+        x as usize * y as usize * z as usize
+    }
+
+    pub fn positions(&self) -> impl Iterator<Item = BlockPos> {
+        let (min, max) = (self.min, self.max);
+        (min.0..=max.0).flat_map(move |x| {
+            (min.1..=max.1).flat_map(move |y| (min.2..=max.2).map(move |z| (x, y, z)))
+        })
+    }
+}
+
+/// A block set by an edit. Edits by players leave `tile_entity` empty, the undo records keep
+/// the tile entities the edit removed so containers come back with their items.
+#[derive(Clone, Debug)]
+pub struct EditBlock {
+    pub pos: BlockPos,
+    pub id: u8,
+    pub aux: u8,
+    pub tile_entity: Option<TileEntity>,
+}
+
+impl EditBlock {
+    pub fn new(pos: BlockPos, id: u8, aux: u8) -> Self {
+        Self {
+            pos,
+            id,
+            aux,
+            tile_entity: None,
+        }
+    }
+}
+
+/// The way edits and their undos are passed around
+pub type BlockChanges = Vec<EditBlock>;
+
+/// Copied blocks, positioned relative to where the player stood while copying
+#[derive(Clone, Debug, Default)]
+pub struct Clipboard {
+    pub blocks: BlockChanges,
+}
+
+impl Clipboard {
+    /// Turns the blocks clockwise around the vertical axis through the copying player, in steps
+    /// of 90 degrees
+    pub fn rotate(&mut self, quarter_turns: u32) {
+        for EditBlock { pos: (x, _, z), .. } in &mut self.blocks {
+            for _ in 0..quarter_turns % 4 {
+                (*x, *z) = (-*z, *x);
+            }
+        }
+    }
+
+    /// The blocks placed when pasting at `origin`
+    pub fn placed_at(&self, (x, y, z): BlockPos) -> BlockChanges {
+        self.blocks
+            .iter()
+            .map(|block| {
+                let (dx, dy, dz) = block.pos;
+                EditBlock {
+                    pos: (x + dx, y + dy, z + dz),
+                    ..block.clone()
+                }
+            })
+            .collect()
+    }
+}
+
+impl World {
+    /// Copies the blocks of a cuboid relative to `origin`
+    pub fn copy(&self, cuboid: Cuboid, (x, y, z): BlockPos) -> Clipboard {
+        let blocks = cuboid
+            .positions()
+            .filter_map(|pos| {
+                let (id, aux) = self.get_block(pos)?;
+                Some(EditBlock::new((pos.0 - x, pos.1 - y, pos.2 - z), id, aux))
+            })
+            .collect();
+        Clipboard { blocks }
+    }
+
+    /// Sets many blocks like `set_block`, skipping the ones outside of the world or already in
+    /// place. The light is recalculated once per changed chunk instead of around every block.
+    /// Returns the blocks that were there before with their tile entities, applying them undoes
+    /// the edit.
+    pub fn set_blocks(&mut self, blocks: &[EditBlock]) -> BlockChanges {
+        let mut previous = Vec::new();
+        let mut chunks = BTreeSet::new();
+        for block in blocks {
+            let Some((id, aux)) = self.get_block(block.pos) else {
+                continue;
+            };
+            if (id, aux) == (block.id, block.aux) && block.tile_entity.is_none() {
+                continue;
+            }
+            let tile_entity = self.get_tile_entity(block.pos).cloned();
+            if self.write_block(block.pos, block.id, block.aux).is_err() {
+                continue;
+            }
+            self.schedule_updates(block.pos);
+            let (x, _, z) = block.pos;
+            let width = CHUNK_WIDTH as i32;
+            chunks.insert((x.div_euclid(width), z.div_euclid(width)));
+            if let Some(restored) = &block.tile_entity {
+                self.set_tile_entity(block.pos, restored.clone());
+            }
+            previous.push(EditBlock {
+                pos: block.pos,
+                id,
+                aux,
+                tile_entity,
+            });
+        }
+        for (chunk_x, chunk_z) in chunks {
+            self.light_chunk(chunk_x, chunk_z);
+        }
+        previous
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clipboard(positions: &[BlockPos]) -> Clipboard {
+        Clipboard {
+            blocks: positions
+                .iter()
+                .map(|pos| EditBlock::new(*pos, 1, 0))
+                .collect(),
+        }
+    }
+
+    fn positions(clipboard: &Clipboard) -> Vec<BlockPos> {
+        clipboard.blocks.iter().map(|block| block.pos).collect()
+    }
+
+    #[test]
+    fn rotates_clockwise() {
+        let mut copied = clipboard(&[(2, 5, 1), (-1, 0, 0)]);
+        copied.rotate(1);
+        assert_eq!(positions(&copied), [(-1, 5, 2), (0, 0, -1)]);
+        copied.rotate(1);
+        assert_eq!(positions(&copied), [(-2, 5, -1), (1, 0, 0)]);
+    }
+
+    #[test]
+    fn full_turns_change_nothing() {
+        let original = [(2, 5, 1), (-3, 1, 4)];
+        let mut copied = clipboard(&original);
+        copied.rotate(4);
+        assert_eq!(positions(&copied), original);
+        copied.rotate(3);
+        copied.rotate(1);
+        assert_eq!(positions(&copied), original);
+    }
+
+    #[test]
+    fn places_relative_to_origin() {
+        let copied = clipboard(&[(2, 5, 1), (-1, 0, 0)]);
+        let placed = copied.placed_at((100, 10, 100));
+        let placed: Vec<BlockPos> = placed.iter().map(|block| block.pos).collect();
+        assert_eq!(placed, [(102, 15, 101), (99, 10, 100)]);
+    }
+
+    #[test]
+    fn cuboid_volume_and_positions() {
+        let cuboid = Cuboid::new((2, 1, 0), (0, 1, 1));
+        assert_eq!(cuboid.volume(), 6);
+        assert_eq!(cuboid.positions().count(), 6);
+        assert!(cuboid
+            .positions()
+            .all(|(x, y, z)| x <= 2 && y == 1 && z <= 1));
+    }
+}
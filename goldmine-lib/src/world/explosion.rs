@@ -0,0 +1,203 @@
+//! Explosions: rays cast from the center lose strength in every block they pass, the blocks a ray
+//! reaches with strength left are destroyed. Entities in range are hurt and pushed away, less so
+//! behind cover.
+
+use std::collections::BTreeSet;
+
+use crate::{
+    blocks::{block_properties, Blocks},
+    data::Vec3,
+    game_packets::ItemStack,
+};
+
+use super::{BlockPos, World};
+
+pub const TNT_POWER: f32 = 4.0;
+/// Ticks from lighting TNT until it explodes
+pub const TNT_FUSE: u32 = 80;
+/// The item lighting TNT
+pub const FLINT_AND_STEEL: u16 = 259;
+
+/// Rays per edge of the cube they are cast through
+const RAYS: i32 = 16;
+const RAY_STEP: f32 = 0.3;
+/// Size of the box an entity is hit with
+const ENTITY_WIDTH: f32 = 0.6;
+const ENTITY_HEIGHT: f32 = 1.8;
+
+pub fn block_center((x, y, z): BlockPos) -> Vec3 {
+    (x as f32 + 0.5, y as f32 + 0.5, z as f32 + 0.5)
+}
+
+fn block_at((x, y, z): Vec3) -> BlockPos {
+    (x.floor() as i32, y.floor() as i32, z.floor() as i32)
+}
+
+fn distance(a: Vec3, b: Vec3) -> f32 {
+    ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2) + (a.2 - b.2).powi(2)).sqrt()
+}
+
+pub struct Explosion {
+    pub center: Vec3,
+    pub power: f32,
+    /// Destroyed blocks with their id and aux
+    pub destroyed: Vec<(BlockPos, u8, u8)>,
+    /// Slots of the destroyed containers
+    pub contents: Vec<(BlockPos, Vec<ItemStack>)>,
+    /// Text of the destroyed signs
+    pub signs: Vec<(BlockPos, [String; 4])>,
+    /// TNT blocks caught in the explosion, they are lit instead of dropping
+    pub tnt: Vec<BlockPos>,
+}
+
+impl Explosion {
+    /// Damage and knockback of an entity standing at `pos`, `None` out of range
+    pub fn impact(&self, world: &World, pos: Vec3) -> Option<(u8, Vec3)> {
+        let range = self.power * 2.0;
+        let distance = distance(pos, self.center);
+        if distance >= range || distance == 0.0 {
+            return None;
+        }
+        let impact = (1.0 - distance / range) * world.exposure(self.center, pos);
+        let damage = ((impact * impact + impact) / 2.0 * 8.0 * self.power + 1.0) as u8;
+        let knockback = (
+            (pos.0 - self.center.0) / distance * impact,
+            (pos.1 - self.center.1) / distance * impact,
+            (pos.2 - self.center.2) / distance * impact,
+        );
+        Some((damage, knockback))
+    }
+}
+
+impl World {
+    /// Runs an explosion of `power` at `center`. Without `break_blocks` only the entities are
+    /// affected, blocks for which `protected` is true withstand it. The positions of the changed
+    /// blocks are added to `changed`.
+    pub fn explode(
+        &mut self,
+        center: Vec3,
+        power: f32,
+        break_blocks: bool,
+        protected: impl Fn(BlockPos) -> bool,
+        changed: &mut Vec<BlockPos>,
+    ) -> Explosion {
+        let mut explosion = Explosion {
+            center,
+            power,
+            destroyed: Vec::new(),
+            contents: Vec::new(),
+            signs: Vec::new(),
+            tnt: Vec::new(),
+        };
+        if !break_blocks {
+            return explosion;
+        }
+
+        let mut hit = BTreeSet::new();
+        let edge = (RAYS - 1) as f32;
+        for x in 0..RAYS {
+            for y in 0..RAYS {
+                for z in 0..RAYS {
+                    let on_surface = [x, y, z].iter().any(|axis| *axis == 0 || *axis == RAYS - 1);
+                    if !on_surface {
+                        continue;
+                    }
+                    let direction = (
+                        x as f32 / edge * 2.0 - 1.0,
+                        y as f32 / edge * 2.0 - 1.0,
+                        z as f32 / edge * 2.0 - 1.0,
+                    );
+                    let length = distance(direction, (0.0, 0.0, 0.0));
+                    let step = (
+                        direction.0 / length * RAY_STEP,
+                        direction.1 / length * RAY_STEP,
+                        direction.2 / length * RAY_STEP,
+                    );
+                    self.cast_ray(center, step, power, &mut hit);
+                }
+            }
+        }
+
+        for pos in hit.into_iter().filter(|pos| !protected(*pos)) {
+            let Some((id, aux)) = self.get_block(pos) else {
+                continue;
+            };
+            if id == Blocks::Tnt.id() {
+                explosion.tnt.push(pos);
+            } else {
+                explosion.destroyed.push((pos, id, aux));
+                if let Some(items) = self.get_tile_entity(pos).and_then(|tile| tile.items()) {
+                    explosion.contents.push((pos, items.to_vec()));
+                }
+                if let Some(lines) = self.get_tile_entity(pos).and_then(|tile| tile.lines()) {
+                    explosion.signs.push((pos, lines.clone()));
+                }
+            }
+            self.change(pos, Blocks::Air.id(), 0, changed);
+        }
+        explosion
+    }
+
+    /// Follows a ray until it runs out of strength, collecting the blocks it destroys
+    fn cast_ray(&mut self, center: Vec3, step: Vec3, power: f32, hit: &mut BTreeSet<BlockPos>) {
+        let mut strength = power * (0.7 + self.random.next_f32() * 0.6);
+        let mut pos = center;
+        while strength > 0.0 {
+            let block = block_at(pos);
+            if let Some((id, _)) = self
+                .get_block(block)
+                .filter(|(id, _)| *id != Blocks::Air.id())
+            {
+                let Some(properties) = block_properties(id).filter(|block| block.is_breakable())
+                else {
+                    return;
+                };
+                strength -= (properties.blast_resistance / 5.0 + 0.3) * RAY_STEP;
+                if strength > 0.0 {
+                    hit.insert(block);
+                }
+            }
+            strength -= RAY_STEP * 0.75;
+            pos = (pos.0 + step.0, pos.1 + step.1, pos.2 + step.2);
+        }
+    }
+
+    /// The share of an entity at `pos` an explosion at `center` reaches without passing through
+    /// a solid block
+    fn exposure(&self, center: Vec3, pos: Vec3) -> f32 {
+        let mut seen = 0;
+        let mut total = 0;
+        for x in 0..=2 {
+            for y in 0..=2 {
+                for z in 0..=2 {
+                    let point = (
+                        pos.0 + (x as f32 / 2.0 - 0.5) * ENTITY_WIDTH,
+                        pos.1 + y as f32 / 2.0 * ENTITY_HEIGHT,
+                        pos.2 + (z as f32 / 2.0 - 0.5) * ENTITY_WIDTH,
+                    );
+                    total += 1;
+                    if !self.is_blocked(point, center) {
+                        seen += 1;
+                    }
+                }
+            }
+        }
+        seen as f32 / total as f32
+    }
+
+    /// Whether a solid block lies on the line between two points
+    fn is_blocked(&self, from: Vec3, to: Vec3) -> bool {
+        let steps = (distance(from, to) / RAY_STEP).ceil() as i32;
+        (0..steps).any(|step| {
+            let t = step as f32 / steps as f32;
+            let point = (
+                from.0 + (to.0 - from.0) * t,
+                from.1 + (to.1 - from.1) * t,
+                from.2 + (to.2 - from.2) * t,
+            );
+            self.get_block(block_at(point))
+                .and_then(|(id, _)| block_properties(id))
+                .is_some_and(|properties| properties.solid)
+        })
+    }
+}
@@ -0,0 +1,303 @@
+//! Furnaces burn fuel to smelt the item in their input slot into the result slot. They run for
+//! every loaded chunk, whether somebody has the window open or not.
+
+use crate::{blocks::Blocks, game_packets::ItemStack};
+
+use super::{
+    tile_entity::{TileEntity, MAX_STACK},
+    BlockPos, World,
+};
+
+pub const FURNACE_SLOTS: usize = 3;
+const INPUT: usize = 0;
+const FUEL: usize = 1;
+const RESULT: usize = 2;
+
+/// Ticks to smelt one item
+const COOK_TIME: u16 = 200;
+/// The client draws the progress bars out of this
+const PROGRESS_SCALE: u16 = 200;
+/// Window properties of the progress bars
+const COOK_PROPERTY: u16 = 0;
+const BURN_PROPERTY: u16 = 1;
+
+const BUCKET: u16 = 325;
+const LAVA_BUCKET: u16 = 327;
+
+/// Input id and aux, `None` for any aux, and the result id and aux
+const RECIPES: &[(u16, Option<u16>, u16, u16)] = &[
+    (4, None, 1, 0),     // Cobblestone to stone
+    (12, None, 20, 0),   // Sand to glass
+    (14, None, 266, 0),  // Gold ore to gold ingot
+    (15, None, 265, 0),  // Iron ore to iron ingot
+    (17, None, 263, 1),  // Log to charcoal
+    (56, None, 264, 0),  // Diamond ore to diamond
+    (81, None, 351, 2),  // Cactus to green dye
+    (87, None, 405, 0),  // Netherrack to nether brick
+    (319, None, 320, 0), // Porkchop
+    (337, None, 336, 0), // Clay to brick
+    (349, None, 350, 0), // Fish
+    (363, None, 364, 0), // Beef
+    (365, None, 366, 0), // Chicken
+];
+
+/// Item id and the ticks it burns
+const FUELS: &[(u16, u16)] = &[
+    (5, 300),    // Planks
+    (6, 100),    // Sapling
+    (17, 300),   // Log
+    (47, 300),   // Bookshelf
+    (53, 300),   // Wooden stairs
+    (54, 300),   // Chest
+    (58, 300),   // Crafting table
+    (85, 300),   // Fence
+    (263, 1600), // Coal and charcoal
+    (280, 100),  // Stick
+    (LAVA_BUCKET, 20000),
+];
+
+fn smelting_result(input: &ItemStack) -> Option<ItemStack> {
+    if input.is_empty() {
+        return None;
+    }
+    RECIPES
+        .iter()
+        .find(|(id, aux, _, _)| *id == input.id && aux.is_none_or(|aux| aux == input.aux))
+        .map(|(_, _, id, aux)| ItemStack {
+            id: *id,
+            count: 1,
+            aux: *aux,
+        })
+}
+
+fn burn_time(fuel: &ItemStack) -> Option<u16> {
+    if fuel.is_empty() {
+        return None;
+    }
+    FUELS
+        .iter()
+        .find(|(id, _)| *id == fuel.id)
+        .map(|(_, time)| *time)
+}
+
+#[derive(Clone, Debug)]
+pub struct Furnace {
+    pub items: Vec<ItemStack>,
+    /// Ticks the current fuel keeps burning
+    pub burn_time: u16,
+    /// Ticks the current fuel burned in total
+    pub max_burn_time: u16,
+    /// Ticks the current input has been smelting
+    pub cook_time: u16,
+}
+
+impl Default for Furnace {
+    fn default() -> Self {
+        Self {
+            items: vec![ItemStack::default(); FURNACE_SLOTS],
+            burn_time: 0,
+            max_burn_time: 0,
+            cook_time: 0,
+        }
+    }
+}
+
+impl Furnace {
+    pub fn is_lit(&self) -> bool {
+        self.burn_time > 0
+    }
+
+    /// The result of smelting the input, if it fits into the result slot
+    fn result(&self) -> Option<ItemStack> {
+        let result = smelting_result(&self.items[INPUT])?;
+        let current = &self.items[RESULT];
+        let fits = current.is_empty()
+            || (current.id == result.id && current.aux == result.aux && current.count < MAX_STACK);
+        fits.then_some(result)
+    }
+
+    /// Advances a tick, returning whether the slots changed
+    pub fn tick(&mut self) -> bool {
+        let mut changed = false;
+        self.burn_time = self.burn_time.saturating_sub(1);
+        let result = self.result();
+        if !self.is_lit() && result.is_some() {
+            if let Some(time) = burn_time(&self.items[FUEL]) {
+                self.burn_time = time;
+                self.max_burn_time = time;
+                let fuel = &mut self.items[FUEL];
+                if fuel.id == LAVA_BUCKET {
+                    fuel.id = BUCKET;
+                } else {
+                    take_one(fuel);
+                }
+                changed = true;
+            }
+        }
+
+        match result {
+            Some(result) if self.is_lit() => {
+                self.cook_time += 1;
+                if self.cook_time >= COOK_TIME {
+                    self.cook_time = 0;
+                    take_one(&mut self.items[INPUT]);
+                    let current = &mut self.items[RESULT];
+                    if current.is_empty() {
+                        *current = result;
+                    } else {
+                        current.count += 1;
+                    }
+                    changed = true;
+                }
+            }
+            _ => self.cook_time = 0,
+        }
+        changed
+    }
+
+    /// The window properties of the cook and burn progress bars
+    pub fn window_data(&self) -> Vec<(u16, u16)> {
+        let burn = if self.max_burn_time == 0 {
+            0
+        } else {
+            (u32::from(self.burn_time) * u32::from(PROGRESS_SCALE) / u32::from(self.max_burn_time))
+                as u16
+        };
+        let cook = self.cook_time * PROGRESS_SCALE / COOK_TIME;
+        vec![(COOK_PROPERTY, cook), (BURN_PROPERTY, burn)]
+    }
+}
+
+fn take_one(item: &mut ItemStack) {
+    item.count = item.count.saturating_sub(1);
+    if item.count == 0 {
+        *item = ItemStack::default();
+    }
+}
+
+/// What changed about a furnace in a tick
+pub struct FurnaceUpdate {
+    pub pos: BlockPos,
+    /// The new slots, if they changed
+    pub items: Option<Vec<ItemStack>>,
+    /// The progress bars that changed
+    pub window_data: Vec<(u16, u16)>,
+}
+
+impl World {
+    /// Runs a tick of every furnace. A furnace turns into a lit furnace while it burns, the
+    /// positions of these block changes are added to `changed`.
+    pub fn tick_furnaces(&mut self, changed: &mut Vec<BlockPos>) -> Vec<FurnaceUpdate> {
+        let mut updates = Vec::new();
+        let mut lit_changes = Vec::new();
+        for (pos, tile_entity) in self.tile_entities.iter_mut() {
+            let TileEntity::Furnace(furnace) = tile_entity else {
+                continue;
+            };
+            let was_lit = furnace.is_lit();
+            let window_data = furnace.window_data();
+            let items_changed = furnace.tick();
+            let new_window_data = furnace.window_data();
+            if furnace.is_lit() != was_lit {
+                lit_changes.push((*pos, furnace.is_lit()));
+            }
+            let window_data: Vec<(u16, u16)> = new_window_data
+                .into_iter()
+                .filter(|property| !window_data.contains(property))
+                .collect();
+            if items_changed || !window_data.is_empty() {
+                updates.push(FurnaceUpdate {
+                    pos: *pos,
+                    items: items_changed.then(|| furnace.items.clone()),
+                    window_data,
+                });
+            }
+        }
+        for (pos, lit) in lit_changes {
+            let Some((_, aux)) = self.get_block(pos) else {
+                continue;
+            };
+            let block = if lit {
+                Blocks::LitFurnace
+            } else {
+                Blocks::Furnace
+            };
+            self.change(pos, block.id(), aux, changed);
+        }
+        updates
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(id: u16, count: u8) -> ItemStack {
+        ItemStack { id, count, aux: 0 }
+    }
+
+    fn furnace(input: ItemStack, fuel: ItemStack) -> Furnace {
+        let mut furnace = Furnace::default();
+        furnace.items[INPUT] = input;
+        furnace.items[FUEL] = fuel;
+        furnace
+    }
+
+    #[test]
+    fn smelts_with_fuel() {
+        let mut furnace = furnace(item(4, 2), item(263, 3));
+        assert!(furnace.tick());
+        assert!(furnace.is_lit());
+        assert_eq!(furnace.items[FUEL], item(263, 2));
+        for _ in 1..COOK_TIME - 1 {
+            assert!(!furnace.tick());
+        }
+        assert!(furnace.items[RESULT].is_empty());
+        assert!(furnace.tick());
+        assert_eq!(furnace.items[INPUT], item(4, 1));
+        assert_eq!(furnace.items[RESULT], item(1, 1));
+        assert_eq!(furnace.cook_time, 0);
+    }
+
+    #[test]
+    fn lava_leaves_the_bucket() {
+        let mut furnace = furnace(item(12, 1), item(LAVA_BUCKET, 1));
+        furnace.tick();
+        assert_eq!(furnace.items[FUEL], item(BUCKET, 1));
+        assert_eq!(furnace.max_burn_time, 20000);
+    }
+
+    #[test]
+    fn keeps_fuel_without_a_recipe() {
+        let mut furnace = furnace(item(3, 1), item(263, 1));
+        assert!(!furnace.tick());
+        assert!(!furnace.is_lit());
+        assert_eq!(furnace.items[FUEL], item(263, 1));
+    }
+
+    #[test]
+    fn stops_when_the_result_slot_is_taken() {
+        let mut furnace = furnace(item(4, 1), item(263, 1));
+        furnace.items[RESULT] = item(20, 1);
+        assert!(!furnace.tick());
+        assert!(!furnace.is_lit());
+
+        furnace.items[RESULT] = item(1, MAX_STACK);
+        assert!(!furnace.tick());
+        assert_eq!(furnace.cook_time, 0);
+    }
+
+    #[test]
+    fn cooking_stops_when_the_fuel_runs_out() {
+        let mut furnace = furnace(item(4, 1), item(280, 1));
+        for _ in 0..100 {
+            furnace.tick();
+        }
+        assert!(furnace.is_lit());
+        assert!(furnace.items[FUEL].is_empty());
+        furnace.tick();
+        assert!(!furnace.is_lit());
+        assert_eq!(furnace.cook_time, 0);
+        assert!(furnace.items[RESULT].is_empty());
+    }
+}
@@ -0,0 +1,38 @@
+use anyhow::Result;
+
+use crate::{
+    config::FlatLayer,
+    world::{chunk::Chunk, CHUNK_HEIGHT, CHUNK_WIDTH},
+};
+
+use super::WorldGenerator;
+
+/// The same stack of layers everywhere, starting at y 0
+pub struct FlatGenerator {
+    layers: Vec<FlatLayer>,
+}
+
+impl FlatGenerator {
+    pub fn new(layers: Vec<FlatLayer>) -> Self {
+        Self { layers }
+    }
+}
+
+impl WorldGenerator for FlatGenerator {
+    fn generate_chunk(&self, _seed: u32, _chunk_x: i32, _chunk_z: i32) -> Result<Chunk> {
+        let mut chunk = Chunk::new();
+        let blocks = self
+            .layers
+            .iter()
+            .flat_map(|layer| std::iter::repeat_n((layer.block, layer.aux), layer.height))
+            .take(CHUNK_HEIGHT);
+        for (y, (id, aux)) in blocks.enumerate() {
+            for x in 0..CHUNK_WIDTH {
+                for z in 0..CHUNK_WIDTH {
+                    chunk.set_block(x, y, z, id, aux);
+                }
+            }
+        }
+        Ok(chunk)
+    }
+}
@@ -0,0 +1,45 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use mlua::{Function, Lua};
+use parking_lot::Mutex;
+
+use crate::{registry::Registries, world::chunk::Chunk};
+
+use super::WorldGenerator;
+
+/// Calls a generator registered by a mod in `wg_registry` with an empty chunk to fill
+pub struct LuaGenerator {
+    name: String,
+    lua: Arc<Mutex<Lua>>,
+    registries: Arc<Mutex<Registries>>,
+}
+
+impl LuaGenerator {
+    pub fn new(name: String, lua: Arc<Mutex<Lua>>, registries: Arc<Mutex<Registries>>) -> Self {
+        Self {
+            name,
+            lua,
+            registries,
+        }
+    }
+}
+
+impl WorldGenerator for LuaGenerator {
+    fn generate_chunk(&self, seed: u32, chunk_x: i32, chunk_z: i32) -> Result<Chunk> {
+        // The generator may call back into functions that lock the registries
+        let (lua, generator) = {
+            let registries = self.registries.lock();
+            let lua = self.lua.lock();
+            let generator: Function =
+                lua.registry_value(registries.wg_registry.get(&self.name)?)?;
+            (lua, generator)
+        };
+        let mut chunk = Chunk::new();
+        lua.scope(|scope| {
+            let chunk = scope.create_userdata_ref_mut(&mut chunk)?;
+            generator.call::<()>((chunk, chunk_x, chunk_z, seed))
+        })?;
+        Ok(chunk)
+    }
+}
@@ -0,0 +1,37 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use mlua::Lua;
+use parking_lot::Mutex;
+
+use crate::{config::GeneratorConfig, registry::Registries};
+
+use self::{
+    flat::FlatGenerator, lua::LuaGenerator, natural::NaturalGenerator, void::VoidGenerator,
+};
+
+use super::chunk::Chunk;
+
+pub mod flat;
+pub mod lua;
+pub mod natural;
+pub mod void;
+
+/// Fills new chunk columns. Generators are called outside of the server data lock.
+pub trait WorldGenerator: Send + Sync {
+    fn generate_chunk(&self, seed: u32, chunk_x: i32, chunk_z: i32) -> Result<Chunk>;
+}
+
+/// Builds the generator selected in the config
+pub fn create_generator(
+    config: &GeneratorConfig,
+    lua: Arc<Mutex<Lua>>,
+    registries: Arc<Mutex<Registries>>,
+) -> Arc<dyn WorldGenerator> {
+    match config {
+        GeneratorConfig::Natural => Arc::new(NaturalGenerator),
+        GeneratorConfig::Flat { layers } => Arc::new(FlatGenerator::new(layers.clone())),
+        GeneratorConfig::Void => Arc::new(VoidGenerator),
+        GeneratorConfig::Lua { name } => Arc::new(LuaGenerator::new(name.clone(), lua, registries)),
+    }
+}
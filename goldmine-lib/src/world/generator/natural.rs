@@ -0,0 +1,168 @@
+use anyhow::Result;
+
+use crate::{
+    blocks::Blocks,
+    world::{
+        chunk::Chunk,
+        noise::{fractal_noise, hash, Random},
+        CHUNK_HEIGHT, CHUNK_WIDTH,
+    },
+};
+
+use super::WorldGenerator;
+
+/// Water fills every air block below this height
+pub const SEA_LEVEL: usize = 64;
+
+// Salts of the noise layers
+const HEIGHT_SALT: u32 = 0;
+const HILLS_SALT: u32 = 16;
+const FOREST_SALT: u32 = 32;
+const CHUNK_SALT: u32 = 48;
+
+/// Ore, veins per chunk, highest y and blocks per vein
+const ORES: &[(Blocks, u32, usize, u32)] = &[
+    (Blocks::CoalOre, 20, 128, 12),
+    (Blocks::IronOre, 20, 64, 8),
+    (Blocks::GoldOre, 2, 32, 8),
+    (Blocks::RedstoneOre, 8, 16, 7),
+    (Blocks::DiamondOre, 1, 16, 7),
+    (Blocks::LapisOre, 1, 32, 6),
+];
+
+/// Natural terrain: rolling hills with stone, dirt and grass, sandy beaches and sea floors, water
+/// up to the sea level, ore veins, bedrock and oak trees. The result only depends on the seed and
+/// the chunk position.
+pub struct NaturalGenerator;
+
+impl WorldGenerator for NaturalGenerator {
+    fn generate_chunk(&self, seed: u32, chunk_x: i32, chunk_z: i32) -> Result<Chunk> {
+        Ok(generate_chunk(seed, chunk_x, chunk_z))
+    }
+}
+
+pub fn generate_chunk(seed: u32, chunk_x: i32, chunk_z: i32) -> Chunk {
+    let mut chunk = Chunk::new();
+    let mut random = Random::new(hash(seed, CHUNK_SALT, chunk_x, chunk_z));
+
+    for x in 0..CHUNK_WIDTH {
+        for z in 0..CHUNK_WIDTH {
+            let height = terrain_height(
+                seed,
+                chunk_x * CHUNK_WIDTH as i32 + x as i32,
+                chunk_z * CHUNK_WIDTH as i32 + z as i32,
+            );
+            fill_column(&mut chunk, &mut random, x, z, height);
+        }
+    }
+
+    for (ore, veins, max_y, size) in ORES {
+        for _ in 0..*veins {
+            place_vein(&mut chunk, &mut random, *ore, *max_y, *size);
+        }
+    }
+
+    let forest = fractal_noise(
+        seed,
+        FOREST_SALT,
+        chunk_x as f64 / 4.0,
+        chunk_z as f64 / 4.0,
+        2,
+    );
+    let trees = ((forest + 0.1) * 8.0).max(0.0) as u32 + u32::from(random.one_in(4));
+    for _ in 0..trees {
+        // Trees stay inside the chunk so generating a chunk never touches its neighbours
+        let x = 2 + random.below(CHUNK_WIDTH as u32 - 4) as usize;
+        let z = 2 + random.below(CHUNK_WIDTH as u32 - 4) as usize;
+        place_tree(&mut chunk, &mut random, x, z);
+    }
+
+    chunk
+}
+
+/// Height of the topmost solid block
+fn terrain_height(seed: u32, x: i32, z: i32) -> usize {
+    let base = fractal_noise(seed, HEIGHT_SALT, x as f64 / 96.0, z as f64 / 96.0, 4);
+    let hills = fractal_noise(seed, HILLS_SALT, x as f64 / 48.0, z as f64 / 48.0, 3).max(0.0);
+    let height = SEA_LEVEL as f64 + base * 16.0 + hills * hills * 48.0;
+    height.clamp(8.0, (CHUNK_HEIGHT - 16) as f64) as usize
+}
+
+fn fill_column(chunk: &mut Chunk, random: &mut Random, x: usize, z: usize, height: usize) {
+    let (top, filler) = if height + 6 < SEA_LEVEL {
+        (Blocks::Gravel, Blocks::Gravel)
+    } else if height <= SEA_LEVEL {
+        (Blocks::Sand, Blocks::Sand)
+    } else {
+        (Blocks::Grass, Blocks::Dirt)
+    };
+
+    for y in 0..=height {
+        let block = if y == 0 || (y < 5 && random.below(5) >= y as u32) {
+            Blocks::Bedrock
+        } else if y == height {
+            top
+        } else if y + 3 >= height {
+            filler
+        } else if y + 5 >= height && filler == Blocks::Sand {
+            Blocks::Sandstone
+        } else {
+            Blocks::Stone
+        };
+        chunk.set_block(x, y, z, block.id(), 0);
+    }
+    for y in height + 1..SEA_LEVEL {
+        chunk.set_block(x, y, z, Blocks::StillWater.id(), 0);
+    }
+}
+
+/// A random walk through the stone of the chunk
+fn place_vein(chunk: &mut Chunk, random: &mut Random, ore: Blocks, max_y: usize, size: u32) {
+    let mut x = random.below(CHUNK_WIDTH as u32) as i32;
+    let mut y = 1 + random.below(max_y as u32 - 1) as i32;
+    let mut z = random.below(CHUNK_WIDTH as u32) as i32;
+    for _ in 0..size {
+        let inside = (0..CHUNK_WIDTH as i32).contains(&x)
+            && (0..CHUNK_HEIGHT as i32).contains(&y)
+            && (0..CHUNK_WIDTH as i32).contains(&z);
+        if inside && chunk.get_block(x as usize, y as usize, z as usize).0 == Blocks::Stone.id() {
+            chunk.set_block(x as usize, y as usize, z as usize, ore.id(), 0);
+        }
+        match random.below(3) {
+            0 => x += random.below(3) as i32 - 1,
+            1 => y += random.below(3) as i32 - 1,
+            _ => z += random.below(3) as i32 - 1,
+        }
+    }
+}
+
+/// An oak tree on the grass at x z, if there is room for it
+fn place_tree(chunk: &mut Chunk, random: &mut Random, x: usize, z: usize) {
+    let Some(ground) = chunk.get_height(x, z) else {
+        return;
+    };
+    let trunk = 4 + random.below(3) as usize;
+    let top = ground + trunk;
+    if chunk.get_block(x, ground, z).0 != Blocks::Grass.id() || top + 1 >= CHUNK_HEIGHT {
+        return;
+    }
+
+    chunk.set_block(x, ground, z, Blocks::Dirt.id(), 0);
+    for y in top - 2..=top + 1 {
+        let radius = if y >= top { 1 } else { 2 };
+        for leaf_x in x - radius..=x + radius {
+            for leaf_z in z - radius..=z + radius {
+                let corner = leaf_x.abs_diff(x) == radius && leaf_z.abs_diff(z) == radius;
+                if corner && (y > top || random.one_in(2)) {
+                    continue;
+                }
+                if chunk.get_block(leaf_x, y, leaf_z).0 == Blocks::Air.id() {
+                    chunk.set_block(leaf_x, y, leaf_z, Blocks::Leaves.id(), 0);
+                }
+            }
+        }
+    }
+    for y in ground + 1..=top {
+        chunk.set_block(x, y, z, Blocks::Log.id(), 0);
+    }
+}
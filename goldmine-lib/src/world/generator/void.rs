@@ -0,0 +1,14 @@
+use anyhow::Result;
+
+use crate::world::chunk::Chunk;
+
+use super::WorldGenerator;
+
+/// Nothing but air
+pub struct VoidGenerator;
+
+impl WorldGenerator for VoidGenerator {
+    fn generate_chunk(&self, _seed: u32, _chunk_x: i32, _chunk_z: i32) -> Result<Chunk> {
+        Ok(Chunk::new())
+    }
+}
@@ -0,0 +1,293 @@
+//! Sky light and block light. Light spreads to the six neighbours, losing one level per block or
+//! the opacity of the block it enters if that is higher. Full sky light keeps its level going
+//! straight down through blocks without opacity.
+
+use std::collections::VecDeque;
+
+use crate::blocks::block_properties;
+
+use super::{BlockPos, World, CHUNK_HEIGHT, CHUNK_WIDTH};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LightKind {
+    Sky,
+    Block,
+}
+
+const MAX_LIGHT: u8 = 15;
+
+/// Offsets of the neighbours, straight down first
+const NEIGHBOURS: [BlockPos; 6] = [
+    (0, -1, 0),
+    (0, 1, 0),
+    (-1, 0, 0),
+    (1, 0, 0),
+    (0, 0, -1),
+    (0, 0, 1),
+];
+
+fn opacity(id: u8) -> u8 {
+    block_properties(id).map_or(MAX_LIGHT, |properties| properties.light_opacity)
+}
+
+fn emission(id: u8) -> u8 {
+    block_properties(id).map_or(0, |properties| properties.light_emission)
+}
+
+/// The level light of `level` has after entering a block of `id`
+fn attenuate(kind: LightKind, level: u8, id: u8, downwards: bool) -> u8 {
+    let opacity = opacity(id);
+    if kind == LightKind::Sky && downwards && level == MAX_LIGHT && opacity == 0 {
+        MAX_LIGHT
+    } else {
+        level.saturating_sub(opacity.max(1))
+    }
+}
+
+fn offset((x, y, z): BlockPos, (dx, dy, dz): BlockPos) -> BlockPos {
+    (x + dx, y + dy, z + dz)
+}
+
+impl World {
+    pub fn get_light(&self, kind: LightKind, pos: BlockPos) -> Option<u8> {
+        match kind {
+            LightKind::Sky => self.get_sky_light(pos),
+            LightKind::Block => self.get_block_light(pos),
+        }
+    }
+
+    fn set_light(&mut self, kind: LightKind, pos: BlockPos, level: u8) {
+        let Some((index, x, y, z)) = Self::locate(pos) else {
+            return;
+        };
+        let Some(chunk) = self.chunks[index].as_mut() else {
+            return;
+        };
+        match kind {
+            LightKind::Sky => chunk.set_sky_light(x, y, z, level),
+            LightKind::Block => chunk.set_block_light(x, y, z, level),
+        }
+    }
+
+    /// The light a block has on its own: the emission for block light, the sky above the
+    /// world for sky light
+    fn light_source(&self, kind: LightKind, pos: BlockPos) -> u8 {
+        let Some((id, _)) = self.get_block(pos) else {
+            return 0;
+        };
+        match kind {
+            LightKind::Block => emission(id),
+            LightKind::Sky if pos.1 == CHUNK_HEIGHT as i32 - 1 => {
+                attenuate(kind, MAX_LIGHT, id, true)
+            }
+            LightKind::Sky => 0,
+        }
+    }
+
+    /// Spreads the light of the queued positions until it runs out
+    fn spread_light(&mut self, kind: LightKind, mut queue: VecDeque<BlockPos>) {
+        while let Some(pos) = queue.pop_front() {
+            let Some(level) = self.get_light(kind, pos) else {
+                continue;
+            };
+            if level <= 1 {
+                continue;
+            }
+            for direction in NEIGHBOURS {
+                let neighbour = offset(pos, direction);
+                let (Some((id, _)), Some(current)) =
+                    (self.get_block(neighbour), self.get_light(kind, neighbour))
+                else {
+                    continue;
+                };
+                let new = attenuate(kind, level, id, direction.1 < 0);
+                if new > current {
+                    self.set_light(kind, neighbour, new);
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+    }
+
+    /// Calculates the light of a freshly generated or heavily edited chunk from scratch,
+    /// exchanging light with the loaded chunks next to it
+    pub fn light_chunk(&mut self, chunk_x: i32, chunk_z: i32) {
+        let Some(chunk) = self.get_chunk_mut(chunk_x, chunk_z) else {
+            return;
+        };
+        let (origin_x, origin_z) = (chunk_x * CHUNK_WIDTH as i32, chunk_z * CHUNK_WIDTH as i32);
+        let mut sky_queue = VecDeque::new();
+        let mut block_queue = VecDeque::new();
+        for x in 0..CHUNK_WIDTH {
+            for z in 0..CHUNK_WIDTH {
+                let mut sky_light = MAX_LIGHT;
+                for y in (0..CHUNK_HEIGHT).rev() {
+                    let (id, _) = chunk.get_block(x, y, z);
+                    sky_light = attenuate(LightKind::Sky, sky_light, id, true);
+                    chunk.set_sky_light(x, y, z, sky_light);
+                    chunk.set_block_light(x, y, z, emission(id));
+                    let pos = (origin_x + x as i32, y as i32, origin_z + z as i32);
+                    if sky_light > 1 {
+                        sky_queue.push_back(pos);
+                    }
+                    if emission(id) > 1 {
+                        block_queue.push_back(pos);
+                    }
+                }
+            }
+        }
+
+        // Light of the neighbouring chunks shining in
+        let width = CHUNK_WIDTH as i32;
+        for i in 0..width {
+            for (x, z) in [
+                (origin_x - 1, origin_z + i),
+                (origin_x + width, origin_z + i),
+                (origin_x + i, origin_z - 1),
+                (origin_x + i, origin_z + width),
+            ] {
+                for y in 0..CHUNK_HEIGHT as i32 {
+                    if self.get_sky_light((x, y, z)).is_some_and(|level| level > 1) {
+                        sky_queue.push_back((x, y, z));
+                    }
+                    if self
+                        .get_block_light((x, y, z))
+                        .is_some_and(|level| level > 1)
+                    {
+                        block_queue.push_back((x, y, z));
+                    }
+                }
+            }
+        }
+
+        self.spread_light(LightKind::Sky, sky_queue);
+        self.spread_light(LightKind::Block, block_queue);
+    }
+
+    /// Updates the light around a changed block. The light that may have come through the block
+    /// is removed, then the light around the removed area spreads back in.
+    pub fn relight_block(&mut self, pos: BlockPos) {
+        for kind in [LightKind::Sky, LightKind::Block] {
+            let Some(old) = self.get_light(kind, pos) else {
+                continue;
+            };
+            let mut removal = VecDeque::from([(pos, old)]);
+            let mut refill = VecDeque::new();
+            self.set_light(kind, pos, 0);
+            while let Some((removed, level)) = removal.pop_front() {
+                let source = self.light_source(kind, removed);
+                if source > 0 {
+                    self.set_light(kind, removed, source);
+                    refill.push_back(removed);
+                }
+                for direction in NEIGHBOURS {
+                    let neighbour = offset(removed, direction);
+                    let Some(current) = self.get_light(kind, neighbour) else {
+                        continue;
+                    };
+                    if current == 0 {
+                        continue;
+                    }
+                    let from_here = current < level
+                        || (kind == LightKind::Sky
+                            && direction.1 < 0
+                            && level == MAX_LIGHT
+                            && current == MAX_LIGHT);
+                    if from_here {
+                        self.set_light(kind, neighbour, 0);
+                        removal.push_back((neighbour, current));
+                    } else {
+                        refill.push_back(neighbour);
+                    }
+                }
+            }
+            self.spread_light(kind, refill);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{blocks::Blocks, world::chunk::Chunk};
+
+    use super::*;
+
+    /// A lit chunk with a stone floor at y 0
+    fn floor_world() -> World {
+        let mut chunk = Chunk::new();
+        for x in 0..CHUNK_WIDTH {
+            for z in 0..CHUNK_WIDTH {
+                chunk.set_block(x, 0, z, Blocks::Stone.id(), 0);
+            }
+        }
+        let mut world = World::new();
+        world.set_chunk(0, 0, chunk).unwrap();
+        world.light_chunk(0, 0);
+        world
+    }
+
+    fn sky(world: &World, pos: BlockPos) -> u8 {
+        world.get_sky_light(pos).unwrap()
+    }
+
+    fn block(world: &World, pos: BlockPos) -> u8 {
+        world.get_block_light(pos).unwrap()
+    }
+
+    #[test]
+    fn sky_light_falls_down_columns() {
+        let mut chunk = Chunk::new();
+        chunk.set_block(8, 60, 8, Blocks::Stone.id(), 0);
+        let mut world = World::new();
+        world.set_chunk(0, 0, chunk).unwrap();
+        world.light_chunk(0, 0);
+        assert_eq!(sky(&world, (8, 127, 8)), MAX_LIGHT);
+        assert_eq!(sky(&world, (3, 0, 3)), MAX_LIGHT);
+        assert_eq!(sky(&world, (8, 61, 8)), MAX_LIGHT);
+        assert_eq!(sky(&world, (8, 60, 8)), 0);
+        // Under the stone the light only comes in from the sides
+        assert_eq!(sky(&world, (8, 59, 8)), MAX_LIGHT - 1);
+    }
+
+    #[test]
+    fn block_light_spreads_from_emitters() {
+        let mut world = floor_world();
+        world.set_block((8, 1, 8), Blocks::Torch.id(), 0).unwrap();
+        assert_eq!(block(&world, (8, 1, 8)), 14);
+        assert_eq!(block(&world, (9, 1, 8)), 13);
+        assert_eq!(block(&world, (10, 2, 9)), 10);
+        assert_eq!(block(&world, (8, 1, 15)), 7);
+        assert_eq!(block(&world, (8, 0, 8)), 0);
+        assert_eq!(block(&world, (8, 14, 8)), 1);
+        assert_eq!(block(&world, (8, 15, 8)), 0);
+    }
+
+    #[test]
+    fn placing_and_removing_blocks_relights() {
+        let mut world = floor_world();
+        world.set_block((8, 1, 8), Blocks::Torch.id(), 0).unwrap();
+        world.set_block((8, 1, 8), Blocks::Air.id(), 0).unwrap();
+        for pos in [(8, 1, 8), (9, 1, 8), (8, 5, 8), (15, 1, 15)] {
+            assert_eq!(block(&world, pos), 0, "{:?}", pos);
+        }
+
+        // A roof over a single block keeps the sky light from falling straight in
+        world.set_block((3, 2, 3), Blocks::Stone.id(), 0).unwrap();
+        assert_eq!(sky(&world, (3, 2, 3)), 0);
+        assert_eq!(sky(&world, (3, 1, 3)), MAX_LIGHT - 1);
+        world.set_block((3, 2, 3), Blocks::Air.id(), 0).unwrap();
+        assert_eq!(sky(&world, (3, 2, 3)), MAX_LIGHT);
+        assert_eq!(sky(&world, (3, 1, 3)), MAX_LIGHT);
+
+        // Walling in an emitter keeps its light inside
+        world.set_block((8, 1, 8), Blocks::Torch.id(), 0).unwrap();
+        for direction in NEIGHBOURS.into_iter().skip(1) {
+            world
+                .set_block(offset((8, 1, 8), direction), Blocks::Stone.id(), 0)
+                .unwrap();
+        }
+        assert_eq!(block(&world, (8, 1, 8)), 14);
+        assert_eq!(block(&world, (10, 1, 8)), 0);
+        assert_eq!(block(&world, (8, 3, 8)), 0);
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+
+use self::{chunk::Chunk, noise::Random, physics::BlockUpdates, tile_entity::TileEntity};
+
+pub mod chunk;
+pub mod edit;
+pub mod explosion;
+pub mod furnace;
+pub mod generator;
+pub mod light;
+pub mod noise;
+pub mod physics;
+pub mod random_tick;
+pub mod storage;
+pub mod tile_entity;
+
+pub const CHUNK_WIDTH: usize = 16;
+pub const CHUNK_HEIGHT: usize = 128;
+/// The Pi world is 16x16 chunks, 256x256 blocks
+pub const WORLD_SIZE_CHUNKS: usize = 16;
+pub const WORLD_SIZE: i32 = (WORLD_SIZE_CHUNKS * CHUNK_WIDTH) as i32;
+
+pub type BlockPos = (i32, i32, i32);
+
+/// All chunk columns of the world, created on first access
+pub struct World {
+    chunks: Vec<Option<Chunk>>,
+    updates: BlockUpdates,
+    random: Random,
+    tile_entities: HashMap<BlockPos, TileEntity>,
+}
+
+impl World {
+    pub fn new() -> Self {
+        Self {
+            chunks: vec![None; WORLD_SIZE_CHUNKS * WORLD_SIZE_CHUNKS],
+            updates: BlockUpdates::default(),
+            random: Random::new(rand::random()),
+            tile_entities: HashMap::new(),
+        }
+    }
+
+    pub fn in_bounds((x, y, z): BlockPos) -> bool {
+        (0..WORLD_SIZE).contains(&x)
+            && (0..WORLD_SIZE).contains(&z)
+            && (0..CHUNK_HEIGHT as i32).contains(&y)
+    }
+
+    pub fn chunk_in_bounds(chunk_x: i32, chunk_z: i32) -> bool {
+        Self::chunk_index(chunk_x, chunk_z).is_some()
+    }
+
+    fn chunk_index(chunk_x: i32, chunk_z: i32) -> Option<usize> {
+        let size = WORLD_SIZE_CHUNKS as i32;
+        if (0..size).contains(&chunk_x) && (0..size).contains(&chunk_z) {
+            Some((chunk_z * size + chunk_x) as usize)
+        } else {
+            None
+        }
+    }
+
+    /// Splits a world position into chunk coordinates and coordinates inside the chunk
+    fn locate((x, y, z): BlockPos) -> Option<(usize, usize, usize, usize)> {
+        if !Self::in_bounds((x, y, z)) {
+            return None;
+        }
+        let index = Self::chunk_index(x >> 4, z >> 4)?;
+        Some((index, (x & 15) as usize, y as usize, (z & 15) as usize))
+    }
+
+    pub fn get_chunk(&self, chunk_x: i32, chunk_z: i32) -> Option<&Chunk> {
+        self.chunks[Self::chunk_index(chunk_x, chunk_z)?].as_ref()
+    }
+
+    pub fn get_chunk_mut(&mut self, chunk_x: i32, chunk_z: i32) -> Option<&mut Chunk> {
+        self.chunks[Self::chunk_index(chunk_x, chunk_z)?].as_mut()
+    }
+
+    /// Gets a chunk, creating an empty one if it doesn't exist yet
+    pub fn get_or_create_chunk(&mut self, chunk_x: i32, chunk_z: i32) -> Option<&mut Chunk> {
+        let index = Self::chunk_index(chunk_x, chunk_z)?;
+        Some(self.chunks[index].get_or_insert_with(Chunk::new))
+    }
+
+    /// Gets a chunk, generating and lighting it if it doesn't exist yet
+    pub fn get_or_generate_chunk(
+        &mut self,
+        chunk_x: i32,
+        chunk_z: i32,
+        generate: impl FnOnce(i32, i32) -> Chunk,
+    ) -> Option<&Chunk> {
+        let index = Self::chunk_index(chunk_x, chunk_z)?;
+        if self.chunks[index].is_none() {
+            let mut chunk = generate(chunk_x, chunk_z);
+            chunk.mark_dirty();
+            self.chunks[index] = Some(chunk);
+            self.light_chunk(chunk_x, chunk_z);
+        }
+        self.chunks[index].as_ref()
+    }
+
+    pub fn set_chunk(&mut self, chunk_x: i32, chunk_z: i32, mut chunk: Chunk) -> Result<()> {
+        let Some(index) = Self::chunk_index(chunk_x, chunk_z) else {
+            bail!("Chunk {} {} is outside of the world", chunk_x, chunk_z);
+        };
+        chunk.mark_dirty();
+        self.chunks[index] = Some(chunk);
+        Ok(())
+    }
+
+    /// Coordinates of all existing chunks
+    pub fn chunk_positions(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        self.chunks
+            .iter()
+            .enumerate()
+            .filter(|(_, chunk)| chunk.is_some())
+            .map(|(index, _)| {
+                let size = WORLD_SIZE_CHUNKS;
+                ((index % size) as i32, (index / size) as i32)
+            })
+    }
+
+    /// Coordinates of all chunks changed since they were last saved
+    pub fn dirty_chunks(&self) -> Vec<(i32, i32)> {
+        self.chunk_positions()
+            .filter(|(chunk_x, chunk_z)| {
+                self.get_chunk(*chunk_x, *chunk_z)
+                    .is_some_and(Chunk::is_dirty)
+            })
+            .collect()
+    }
+
+    /// Marks all chunks as saved
+    pub fn clear_dirty(&mut self) {
+        for chunk in self.chunks.iter_mut().flatten() {
+            chunk.clear_dirty();
+        }
+    }
+
+    /// The block id and aux value at a position, `None` outside of the world or in chunks that
+    /// don't exist yet
+    pub fn get_block(&self, pos: BlockPos) -> Option<(u8, u8)> {
+        let (index, x, y, z) = Self::locate(pos)?;
+        Some(self.chunks[index].as_ref()?.get_block(x, y, z))
+    }
+
+    /// Sets a block, updates the light around it and schedules block updates. A tile entity that
+    /// doesn't belong to the new block is removed.
+    pub fn set_block(&mut self, pos: BlockPos, id: u8, aux: u8) -> Result<()> {
+        self.write_block(pos, id, aux)?;
+        self.relight_block(pos);
+        self.schedule_updates(pos);
+        Ok(())
+    }
+
+    /// Sets a block without touching the light or the block updates
+    fn write_block(&mut self, pos: BlockPos, id: u8, aux: u8) -> Result<()> {
+        let Some((index, x, y, z)) = Self::locate(pos) else {
+            bail!("Block {:?} is outside of the world", pos);
+        };
+        self.chunks[index]
+            .get_or_insert_with(Chunk::new)
+            .set_block(x, y, z, id, aux);
+        if self
+            .tile_entities
+            .get(&pos)
+            .is_some_and(|tile_entity| !tile_entity.belongs_to(id))
+        {
+            self.tile_entities.remove(&pos);
+        }
+        Ok(())
+    }
+
+    /// Sets a block for a game rule, collecting the position for the block updates sent to the
+    /// players
+    fn change(&mut self, pos: BlockPos, id: u8, aux: u8, changed: &mut Vec<BlockPos>) {
+        if self.set_block(pos, id, aux).is_ok() {
+            changed.push(pos);
+        }
+    }
+
+    pub fn get_sky_light(&self, pos: BlockPos) -> Option<u8> {
+        let (index, x, y, z) = Self::locate(pos)?;
+        Some(self.chunks[index].as_ref()?.get_sky_light(x, y, z))
+    }
+
+    pub fn get_block_light(&self, pos: BlockPos) -> Option<u8> {
+        let (index, x, y, z) = Self::locate(pos)?;
+        Some(self.chunks[index].as_ref()?.get_block_light(x, y, z))
+    }
+}
+
+impl Default for World {
+    fn default() -> Self {
+        Self::new()
+    }
+}
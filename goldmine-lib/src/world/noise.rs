@@ -0,0 +1,95 @@
+//! Deterministic randomness for world generation. Everything is derived from the seed with
+//! integer hashing, so a seed yields the same world on every platform and version.
+
+fn mix(mut value: u64) -> u64 {
+    value = value.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    value ^ (value >> 31)
+}
+
+/// Hashes a lattice point. The salt separates independent noise layers using the same seed.
+pub fn hash(seed: u32, salt: u32, x: i32, z: i32) -> u64 {
+    let value = mix(u64::from(seed) | (u64::from(salt) << 32));
+    let value = mix(value ^ u64::from(x as u32));
+    mix(value ^ (u64::from(z as u32) << 32))
+}
+
+fn lattice(seed: u32, salt: u32, x: i32, z: i32) -> f64 {
+    (hash(seed, salt, x, z) >> 11) as f64 / (1u64 << 53) as f64 * 2.0 - 1.0
+}
+
+fn smooth(t: f64) -> f64 {
+    t * t * (3.0 - 2.0 * t)
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
+
+/// Smoothly interpolated random values on a lattice with a spacing of 1, in -1..1
+pub fn value_noise(seed: u32, salt: u32, x: f64, z: f64) -> f64 {
+    let (x0, z0) = (x.floor(), z.floor());
+    let (tx, tz) = (smooth(x - x0), smooth(z - z0));
+    let (x0, z0) = (x0 as i32, z0 as i32);
+    let top = lerp(
+        lattice(seed, salt, x0, z0),
+        lattice(seed, salt, x0 + 1, z0),
+        tx,
+    );
+    let bottom = lerp(
+        lattice(seed, salt, x0, z0 + 1),
+        lattice(seed, salt, x0 + 1, z0 + 1),
+        tx,
+    );
+    lerp(top, bottom, tz)
+}
+
+/// Octaves of value noise, each with double the frequency and half the amplitude, in -1..1
+pub fn fractal_noise(seed: u32, salt: u32, x: f64, z: f64, octaves: u32) -> f64 {
+    let mut sum = 0.0;
+    let mut amplitude = 1.0;
+    let mut total = 0.0;
+    let mut frequency = 1.0;
+    for octave in 0..octaves {
+        sum += value_noise(
+            seed,
+            salt.wrapping_add(octave),
+            x * frequency,
+            z * frequency,
+        ) * amplitude;
+        total += amplitude;
+        amplitude /= 2.0;
+        frequency *= 2.0;
+    }
+    sum / total
+}
+
+/// Small deterministic random number generator (splitmix64)
+pub struct Random(u64);
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        mix(self.0)
+    }
+
+    /// A number in `0..bound`
+    pub fn below(&mut self, bound: u32) -> u32 {
+        (self.next_u64() % u64::from(bound.max(1))) as u32
+    }
+
+    /// A number in `0.0..1.0`
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+
+    /// True with a chance of one in `n`
+    pub fn one_in(&mut self, n: u32) -> bool {
+        self.below(n) == 0
+    }
+}
@@ -0,0 +1,473 @@
+//! Scheduled block updates and the rules run by them: falling sand and gravel, flowing water and
+//! lava. Every block change schedules an update of the changed block and its neighbours.
+
+use std::collections::{BTreeMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+
+use crate::blocks::Blocks;
+
+use super::{BlockPos, World};
+
+/// Which physics rules run, stored with the world
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(rename_all = "PascalCase", default)]
+pub struct PhysicsRules {
+    pub falling_blocks: bool,
+    pub water_flow: bool,
+    pub lava_flow: bool,
+    /// Explosions destroy blocks, they still hurt entities without it
+    pub explosion_damage: bool,
+}
+
+impl Default for PhysicsRules {
+    fn default() -> Self {
+        Self {
+            falling_blocks: true,
+            water_flow: true,
+            lava_flow: true,
+            explosion_damage: true,
+        }
+    }
+}
+
+impl PhysicsRules {
+    pub const NAMES: [&'static str; 4] = [
+        "falling_blocks",
+        "water_flow",
+        "lava_flow",
+        "explosion_damage",
+    ];
+
+    pub fn get_mut(&mut self, name: &str) -> Option<&mut bool> {
+        match name {
+            "falling_blocks" => Some(&mut self.falling_blocks),
+            "water_flow" => Some(&mut self.water_flow),
+            "lava_flow" => Some(&mut self.lava_flow),
+            "explosion_damage" => Some(&mut self.explosion_damage),
+            _ => None,
+        }
+    }
+}
+
+/// More updates are left for the next tick, so a flood can't stall the server
+const UPDATES_PER_TICK: usize = 4096;
+
+/// Block positions waiting for an update, by the tick they are due
+#[derive(Default)]
+pub struct BlockUpdates {
+    tick: u64,
+    queue: BTreeMap<u64, Vec<BlockPos>>,
+    scheduled: HashSet<BlockPos>,
+}
+
+impl BlockUpdates {
+    /// Schedules an update in `delay` ticks, unless one is already scheduled
+    pub fn schedule(&mut self, pos: BlockPos, delay: u64) {
+        if self.scheduled.insert(pos) {
+            self.queue.entry(self.tick + delay).or_default().push(pos);
+        }
+    }
+
+    /// Advances a tick and takes the updates due
+    fn take_due(&mut self, limit: usize) -> Vec<BlockPos> {
+        self.tick += 1;
+        let mut due = Vec::new();
+        while due.len() < limit {
+            let Some(mut entry) = self.queue.first_entry() else {
+                break;
+            };
+            if *entry.key() > self.tick {
+                break;
+            }
+            let positions = entry.get_mut();
+            let count = positions.len().min(limit - due.len());
+            due.extend(positions.drain(..count));
+            if positions.is_empty() {
+                entry.remove();
+            }
+        }
+        for pos in &due {
+            self.scheduled.remove(pos);
+        }
+        due
+    }
+}
+
+/// Aux bit of fluid falling down, the lower bits are the distance to the source
+const FALLING: u8 = 0x8;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Fluid {
+    Water,
+    Lava,
+}
+
+impl Fluid {
+    fn of(id: u8) -> Option<Self> {
+        match Blocks::from_id(id)? {
+            Blocks::Water | Blocks::StillWater => Some(Fluid::Water),
+            Blocks::Lava | Blocks::StillLava => Some(Fluid::Lava),
+            _ => None,
+        }
+    }
+
+    fn flowing(self) -> u8 {
+        match self {
+            Fluid::Water => Blocks::Water.id(),
+            Fluid::Lava => Blocks::Lava.id(),
+        }
+    }
+
+    fn still(self) -> u8 {
+        match self {
+            Fluid::Water => Blocks::StillWater.id(),
+            Fluid::Lava => Blocks::StillLava.id(),
+        }
+    }
+
+    /// Levels lost per block flowed sideways
+    fn step(self) -> u8 {
+        match self {
+            Fluid::Water => 1,
+            Fluid::Lava => 2,
+        }
+    }
+}
+
+/// The distance to the source of a fluid aux value, falling fluid counts as a source
+fn fluid_level(aux: u8) -> u8 {
+    if aux & FALLING != 0 {
+        0
+    } else {
+        aux & 0x7
+    }
+}
+
+/// Ticks between a change and the update of a block, `None` for blocks without physics
+fn update_delay(id: u8) -> Option<u64> {
+    match Blocks::from_id(id)? {
+        Blocks::Sand | Blocks::Gravel => Some(2),
+        Blocks::Water | Blocks::StillWater => Some(5),
+        Blocks::Lava | Blocks::StillLava => Some(30),
+        _ => None,
+    }
+}
+
+const HORIZONTAL: [BlockPos; 4] = [(-1, 0, 0), (1, 0, 0), (0, 0, -1), (0, 0, 1)];
+
+fn offset((x, y, z): BlockPos, (dx, dy, dz): BlockPos) -> BlockPos {
+    (x + dx, y + dy, z + dz)
+}
+
+impl World {
+    /// Schedules updates for a changed block and its neighbours
+    pub fn schedule_updates(&mut self, pos: BlockPos) {
+        let neighbours = [(0, 0, 0), (0, -1, 0), (0, 1, 0)]
+            .into_iter()
+            .chain(HORIZONTAL)
+            .map(|direction| offset(pos, direction));
+        for neighbour in neighbours {
+            if let Some(delay) = self
+                .get_block(neighbour)
+                .and_then(|(id, _)| update_delay(id))
+            {
+                self.updates.schedule(neighbour, delay);
+            }
+        }
+    }
+
+    /// Runs the block updates due this tick, returning the positions of the changed blocks.
+    /// Blocks don't fall or flow from one position into another for which `protected` is true.
+    pub fn tick_updates(
+        &mut self,
+        rules: &PhysicsRules,
+        protected: impl Fn(BlockPos, BlockPos) -> bool,
+    ) -> Vec<BlockPos> {
+        let mut changed = Vec::new();
+        for pos in self.updates.take_due(UPDATES_PER_TICK) {
+            let Some((id, aux)) = self.get_block(pos) else {
+                continue;
+            };
+            match Fluid::of(id) {
+                Some(Fluid::Water) if rules.water_flow => {
+                    self.flow(pos, Fluid::Water, id, aux, &protected, &mut changed)
+                }
+                Some(Fluid::Lava) if rules.lava_flow => {
+                    self.flow(pos, Fluid::Lava, id, aux, &protected, &mut changed)
+                }
+                Some(_) => (),
+                None if rules.falling_blocks => self.fall(pos, id, aux, &protected, &mut changed),
+                None => (),
+            }
+        }
+        changed
+    }
+
+    /// Whether fluid can wash away the block at a position
+    fn is_flowable(&self, pos: BlockPos) -> bool {
+        self.get_block(pos)
+            .and_then(|(id, _)| Blocks::from_id(id))
+            .is_some_and(|block| {
+                block == Blocks::Air
+                    || (block.properties().replaceable && Fluid::of(block.id()).is_none())
+            })
+    }
+
+    /// Sand and gravel fall a block at a time while there is air, fluid or a plant below
+    fn fall(
+        &mut self,
+        pos: BlockPos,
+        id: u8,
+        aux: u8,
+        protected: &dyn Fn(BlockPos, BlockPos) -> bool,
+        changed: &mut Vec<BlockPos>,
+    ) {
+        if !matches!(Blocks::from_id(id), Some(Blocks::Sand | Blocks::Gravel)) {
+            return;
+        }
+        let below = offset(pos, (0, -1, 0));
+        let falls = !protected(pos, below)
+            && self
+                .get_block(below)
+                .and_then(|(below_id, _)| Blocks::from_id(below_id))
+                .is_some_and(|block| block.properties().replaceable);
+        if falls {
+            self.change(pos, Blocks::Air.id(), 0, changed);
+            self.change(below, id, aux, changed);
+        }
+    }
+
+    /// Fluid keeps its level from the neighbour closest to a source and dries up without one. It
+    /// flows down if it can and spreads sideways otherwise. Settled fluid turns still, so it
+    /// isn't updated until something next to it changes.
+    fn flow(
+        &mut self,
+        pos: BlockPos,
+        fluid: Fluid,
+        id: u8,
+        aux: u8,
+        protected: &dyn Fn(BlockPos, BlockPos) -> bool,
+        changed: &mut Vec<BlockPos>,
+    ) {
+        let above = offset(pos, (0, 1, 0));
+        let below = offset(pos, (0, -1, 0));
+        let fluid_at = |world: &World, pos| {
+            world
+                .get_block(pos)
+                .filter(|(id, _)| Fluid::of(*id) == Some(fluid))
+                .map(|(_, aux)| aux)
+        };
+
+        if fluid == Fluid::Lava {
+            let touches_water = [above]
+                .into_iter()
+                .chain(HORIZONTAL.map(|direction| offset(pos, direction)))
+                .any(|pos| {
+                    self.get_block(pos)
+                        .is_some_and(|(id, _)| Fluid::of(id) == Some(Fluid::Water))
+                });
+            if touches_water {
+                let hardened = if aux == 0 {
+                    Blocks::Obsidian
+                } else {
+                    Blocks::Cobblestone
+                };
+                self.change(pos, hardened.id(), 0, changed);
+                return;
+            }
+        }
+
+        let new_aux = if aux == 0 {
+            Some(0)
+        } else if fluid_at(self, above).is_some() {
+            Some(FALLING)
+        } else {
+            let mut sources = 0;
+            let mut nearest: Option<u8> = None;
+            for direction in HORIZONTAL {
+                if let Some(neighbour_aux) = fluid_at(self, offset(pos, direction)) {
+                    if neighbour_aux == 0 {
+                        sources += 1;
+                    }
+                    let level = fluid_level(neighbour_aux);
+                    nearest = Some(nearest.map_or(level, |nearest| nearest.min(level)));
+                }
+            }
+            let on_ground = fluid_at(self, below) == Some(0)
+                || self
+                    .get_block(below)
+                    .and_then(|(id, _)| Blocks::from_id(id))
+                    .is_some_and(|block| block.properties().solid);
+            if fluid == Fluid::Water && sources >= 2 && on_ground {
+                Some(0)
+            } else {
+                nearest
+                    .map(|level| level + fluid.step())
+                    .filter(|level| *level < 8)
+            }
+        };
+        let Some(new_aux) = new_aux else {
+            self.change(pos, Blocks::Air.id(), 0, changed);
+            return;
+        };
+
+        let mut active = new_aux != aux;
+        if active {
+            self.change(pos, fluid.flowing(), new_aux, changed);
+        }
+        let below_is_water = self
+            .get_block(below)
+            .is_some_and(|(id, _)| Fluid::of(id) == Some(Fluid::Water));
+        if fluid == Fluid::Lava && below_is_water {
+            self.change(below, Blocks::Stone.id(), 0, changed);
+            active = true;
+        } else if self.is_flowable(below) && !protected(pos, below) {
+            self.change(below, fluid.flowing(), FALLING, changed);
+            active = true;
+        } else if fluid_at(self, below).is_none() {
+            let level = fluid_level(new_aux) + fluid.step();
+            if level < 8 {
+                for direction in HORIZONTAL {
+                    let neighbour = offset(pos, direction);
+                    if self.is_flowable(neighbour) && !protected(pos, neighbour) {
+                        self.change(neighbour, fluid.flowing(), level, changed);
+                        active = true;
+                    }
+                }
+            }
+        }
+        if !active && id == fluid.flowing() {
+            self.change(pos, fluid.still(), new_aux, changed);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::world::chunk::Chunk;
+
+    use super::*;
+
+    /// Two by two chunks with a stone floor at y 0
+    fn floor_world() -> World {
+        let mut world = World::new();
+        for chunk_x in 0..2 {
+            for chunk_z in 0..2 {
+                let mut chunk = Chunk::new();
+                for x in 0..16 {
+                    for z in 0..16 {
+                        chunk.set_block(x, 0, z, Blocks::Stone.id(), 0);
+                    }
+                }
+                world.set_chunk(chunk_x, chunk_z, chunk).unwrap();
+                world.light_chunk(chunk_x, chunk_z);
+            }
+        }
+        world
+    }
+
+    /// Runs block updates until nothing changes anymore, failing if that takes too long
+    fn settle(world: &mut World, protected: impl Fn(BlockPos, BlockPos) -> bool) {
+        let rules = PhysicsRules::default();
+        let mut idle = 0;
+        for _ in 0..2000 {
+            if world.tick_updates(&rules, &protected).is_empty() {
+                idle += 1;
+                // Longer than the slowest update delay
+                if idle > 40 {
+                    return;
+                }
+            } else {
+                idle = 0;
+            }
+        }
+        panic!("Block updates didn't settle");
+    }
+
+    fn block(world: &World, pos: BlockPos) -> Option<Blocks> {
+        Blocks::from_id(world.get_block(pos).unwrap().0)
+    }
+
+    #[test]
+    fn sand_falls_to_the_ground() {
+        let mut world = floor_world();
+        world.set_block((8, 10, 8), Blocks::Sand.id(), 0).unwrap();
+        settle(&mut world, |_, _| false);
+        assert_eq!(block(&world, (8, 10, 8)), Some(Blocks::Air));
+        assert_eq!(block(&world, (8, 1, 8)), Some(Blocks::Sand));
+    }
+
+    #[test]
+    fn water_spreads_seven_blocks() {
+        let mut world = floor_world();
+        world.set_block((16, 1, 16), Blocks::Water.id(), 0).unwrap();
+        settle(&mut world, |_, _| false);
+        for distance in 1..8 {
+            let (id, aux) = world.get_block((16 + distance, 1, 16)).unwrap();
+            assert_eq!(Fluid::of(id), Some(Fluid::Water));
+            assert_eq!(i32::from(aux), distance);
+        }
+        assert_eq!(block(&world, (24, 1, 16)), Some(Blocks::Air));
+        assert_eq!(block(&world, (20, 1, 20)), Some(Blocks::Air));
+        assert_eq!(block(&world, (16, 2, 16)), Some(Blocks::Air));
+
+        // Removing the source dries up the flow
+        world.set_block((16, 1, 16), Blocks::Air.id(), 0).unwrap();
+        settle(&mut world, |_, _| false);
+        for x in 8..25 {
+            for z in 8..25 {
+                assert_eq!(block(&world, (x, 1, z)), Some(Blocks::Air), "{} {}", x, z);
+            }
+        }
+    }
+
+    #[test]
+    fn nothing_moves_into_protected_blocks() {
+        let mut world = floor_world();
+        let protected = |from: BlockPos, to: BlockPos| to.0 >= 19 && from.0 < 19;
+        world.set_block((16, 1, 16), Blocks::Water.id(), 0).unwrap();
+        world.set_block((20, 10, 8), Blocks::Sand.id(), 0).unwrap();
+        settle(&mut world, protected);
+        assert!(Fluid::of(world.get_block((18, 1, 16)).unwrap().0).is_some());
+        assert_eq!(block(&world, (19, 1, 16)), Some(Blocks::Air));
+        // Inside of the region things still move
+        assert_eq!(block(&world, (20, 1, 8)), Some(Blocks::Sand));
+    }
+
+    #[test]
+    fn updates_are_due_after_their_delay() {
+        let mut updates = BlockUpdates::default();
+        updates.schedule((0, 0, 0), 2);
+        updates.schedule((1, 0, 0), 0);
+        updates.schedule((2, 0, 0), 1);
+        assert_eq!(updates.take_due(10), [(1, 0, 0), (2, 0, 0)]);
+        assert_eq!(updates.take_due(10), [(0, 0, 0)]);
+        assert!(updates.take_due(10).is_empty());
+    }
+
+    #[test]
+    fn updates_are_scheduled_once() {
+        let mut updates = BlockUpdates::default();
+        updates.schedule((0, 0, 0), 1);
+        updates.schedule((0, 0, 0), 5);
+        assert_eq!(updates.take_due(10), [(0, 0, 0)]);
+        for _ in 0..5 {
+            assert!(updates.take_due(10).is_empty());
+        }
+        updates.schedule((0, 0, 0), 1);
+        assert_eq!(updates.take_due(10), [(0, 0, 0)]);
+    }
+
+    #[test]
+    fn updates_over_the_limit_are_kept() {
+        let mut updates = BlockUpdates::default();
+        for x in 0..5 {
+            updates.schedule((x, 0, 0), 1);
+        }
+        updates.schedule((9, 0, 0), 2);
+        assert_eq!(updates.take_due(3), [(0, 0, 0), (1, 0, 0), (2, 0, 0)]);
+        assert_eq!(updates.take_due(3), [(3, 0, 0), (4, 0, 0), (9, 0, 0)]);
+        assert!(updates.take_due(3).is_empty());
+    }
+}
@@ -0,0 +1,240 @@
+//! Random ticks: every tick a few random blocks of every 16 block high section of the existing
+//! chunks are updated, which drives the slow changes like plant growth, grass spread, leaf decay
+//! and melting.
+
+use std::collections::{HashSet, VecDeque};
+
+use crate::blocks::{block_properties, Blocks};
+
+use super::{BlockPos, World, CHUNK_HEIGHT, CHUNK_WIDTH};
+
+/// Blocks picked per section and tick
+pub const DEFAULT_RANDOM_TICK_SPEED: u32 = 3;
+/// Highest random tick speed, the ticks run while the world is locked
+pub const MAX_RANDOM_TICK_SPEED: u32 = 4096;
+
+const SECTION_HEIGHT: usize = 16;
+
+/// Light plants need to grow and grass needs to spread
+const GROWTH_LIGHT: u8 = 9;
+/// Grass dies below this light under an opaque block
+const GRASS_LIGHT: u8 = 4;
+/// Block light melting ice and snow
+const MELT_LIGHT: u8 = 12;
+/// Sugar cane and cactus stop growing at this height
+const MAX_PLANT_HEIGHT: i32 = 3;
+/// Distance through leaves in which a log keeps them from decaying
+const LEAF_RANGE: u32 = 4;
+/// Aux bit of leaves placed by players, they never decay
+pub const PERSISTENT_LEAVES: u8 = 0x4;
+
+fn offset((x, y, z): BlockPos, (dx, dy, dz): BlockPos) -> BlockPos {
+    (x + dx, y + dy, z + dz)
+}
+
+fn opacity(id: u8) -> u8 {
+    block_properties(id).map_or(15, |properties| properties.light_opacity)
+}
+
+impl World {
+    /// Picks `speed` random blocks in every section and updates them. Blocks with an id in
+    /// `handled` are left to the mods, they are returned with their position instead. Returns
+    /// the positions of the changed blocks and the blocks for the mods.
+    pub fn random_tick(
+        &mut self,
+        speed: u32,
+        handled: &HashSet<u8>,
+    ) -> (Vec<BlockPos>, Vec<(BlockPos, u8, u8)>) {
+        let mut changed = Vec::new();
+        let mut picked = Vec::new();
+        let chunks: Vec<(i32, i32)> = self.chunk_positions().collect();
+        for (chunk_x, chunk_z) in chunks {
+            for section in (0..CHUNK_HEIGHT).step_by(SECTION_HEIGHT) {
+                for _ in 0..speed {
+                    let random = self.random.next_u64();
+                    let pos = (
+                        chunk_x * CHUNK_WIDTH as i32 + (random & 15) as i32,
+                        (section as u64 + ((random >> 4) & 15)) as i32,
+                        chunk_z * CHUNK_WIDTH as i32 + ((random >> 8) & 15) as i32,
+                    );
+                    let Some((id, aux)) = self.get_block(pos) else {
+                        continue;
+                    };
+                    if handled.contains(&id) {
+                        picked.push((pos, id, aux));
+                    } else {
+                        self.random_tick_block(pos, id, aux, &mut changed);
+                    }
+                }
+            }
+        }
+        (changed, picked)
+    }
+
+    fn random_tick_block(&mut self, pos: BlockPos, id: u8, aux: u8, changed: &mut Vec<BlockPos>) {
+        let above = offset(pos, (0, 1, 0));
+        match Blocks::from_id(id) {
+            Some(Blocks::Grass) => self.spread_grass(pos, changed),
+            Some(Blocks::Sapling)
+                if self.light_at(above) >= GROWTH_LIGHT && self.random.one_in(7) =>
+            {
+                self.grow_tree(pos, aux & 3, changed)
+            }
+            Some(Blocks::Crops) => {
+                let Some((below, wetness)) = self.get_block(offset(pos, (0, -1, 0))) else {
+                    return;
+                };
+                let chance = if wetness > 0 { 3 } else { 6 };
+                if aux < 7
+                    && below == Blocks::Farmland.id()
+                    && self.light_at(above) >= GROWTH_LIGHT
+                    && self.random.one_in(chance)
+                {
+                    self.change(pos, id, aux + 1, changed);
+                }
+            }
+            Some(Blocks::SugarCane | Blocks::Cactus) => self.grow_plant(pos, id, aux, changed),
+            Some(Blocks::Leaves) if aux & PERSISTENT_LEAVES == 0 && !self.near_log(pos) => {
+                self.change(pos, Blocks::Air.id(), 0, changed)
+            }
+            Some(Blocks::Ice) if self.get_block_light(pos).unwrap_or(0) >= MELT_LIGHT => {
+                self.change(pos, Blocks::Water.id(), 0, changed)
+            }
+            Some(Blocks::SnowLayer) if self.get_block_light(pos).unwrap_or(0) >= MELT_LIGHT => {
+                self.change(pos, Blocks::Air.id(), 0, changed)
+            }
+            _ => (),
+        }
+    }
+
+    /// The brighter of the sky and block light, sky light ignores the time of day
+    fn light_at(&self, pos: BlockPos) -> u8 {
+        let sky = self.get_sky_light(pos).unwrap_or(0);
+        sky.max(self.get_block_light(pos).unwrap_or(0))
+    }
+
+    /// Whether grass at a position would be covered by an opaque block
+    fn is_covered(&self, pos: BlockPos) -> bool {
+        self.get_block(offset(pos, (0, 1, 0)))
+            .is_some_and(|(id, _)| opacity(id) > 2)
+    }
+
+    /// Grass in the dark turns into dirt, grass in the light spreads to dirt around it
+    fn spread_grass(&mut self, pos: BlockPos, changed: &mut Vec<BlockPos>) {
+        let light = self.light_at(offset(pos, (0, 1, 0)));
+        if light < GRASS_LIGHT && self.is_covered(pos) {
+            self.change(pos, Blocks::Dirt.id(), 0, changed);
+            return;
+        }
+        if light < GROWTH_LIGHT {
+            return;
+        }
+        let target = offset(
+            pos,
+            (
+                self.random.below(3) as i32 - 1,
+                self.random.below(5) as i32 - 3,
+                self.random.below(3) as i32 - 1,
+            ),
+        );
+        if self.get_block(target) == Some((Blocks::Dirt.id(), 0))
+            && self.light_at(offset(target, (0, 1, 0))) >= GRASS_LIGHT
+            && !self.is_covered(target)
+        {
+            self.change(target, Blocks::Grass.id(), 0, changed);
+        }
+    }
+
+    /// Sugar cane and cactus count up their aux value and grow a block when it runs over
+    fn grow_plant(&mut self, pos: BlockPos, id: u8, aux: u8, changed: &mut Vec<BlockPos>) {
+        let above = offset(pos, (0, 1, 0));
+        if self.get_block(above) != Some((Blocks::Air.id(), 0)) {
+            return;
+        }
+        let height = (1..MAX_PLANT_HEIGHT)
+            .take_while(|dy| {
+                self.get_block(offset(pos, (0, -dy, 0)))
+                    .is_some_and(|(below, _)| below == id)
+            })
+            .count() as i32
+            + 1;
+        if height >= MAX_PLANT_HEIGHT {
+            return;
+        }
+        if aux < 15 {
+            // Only the server tracks the growth, the clients don't need to know
+            let _ = self.set_block(pos, id, aux + 1);
+        } else {
+            let _ = self.set_block(pos, id, 0);
+            self.change(above, id, 0, changed);
+        }
+    }
+
+    /// Leaves without a log reachable through at most `LEAF_RANGE` leaves decay
+    fn near_log(&self, pos: BlockPos) -> bool {
+        let mut queue = VecDeque::from([(pos, 0)]);
+        let mut visited = HashSet::from([pos]);
+        while let Some((current, distance)) = queue.pop_front() {
+            for direction in [
+                (0, -1, 0),
+                (0, 1, 0),
+                (-1, 0, 0),
+                (1, 0, 0),
+                (0, 0, -1),
+                (0, 0, 1),
+            ] {
+                let neighbour = offset(current, direction);
+                match self
+                    .get_block(neighbour)
+                    .and_then(|(id, _)| Blocks::from_id(id))
+                {
+                    Some(Blocks::Log) => return true,
+                    Some(Blocks::Leaves)
+                        if distance + 1 < LEAF_RANGE && visited.insert(neighbour) =>
+                    {
+                        queue.push_back((neighbour, distance + 1))
+                    }
+                    _ => (),
+                }
+            }
+        }
+        false
+    }
+
+    /// Grows a sapling into a tree of its wood type, if there is room for it
+    fn grow_tree(&mut self, pos: BlockPos, wood: u8, changed: &mut Vec<BlockPos>) {
+        let trunk = 4 + self.random.below(3) as i32;
+        let top = pos.1 + trunk;
+        let (x, _, z) = pos;
+        let on_soil = self
+            .get_block(offset(pos, (0, -1, 0)))
+            .is_some_and(|(id, _)| id == Blocks::Grass.id() || id == Blocks::Dirt.id());
+        let has_room = top + 1 < CHUNK_HEIGHT as i32
+            && (pos.1 + 1..=top + 1).all(|y| {
+                self.get_block((x, y, z))
+                    .is_some_and(|(id, _)| id == Blocks::Air.id() || id == Blocks::Leaves.id())
+            });
+        if !on_soil || !has_room {
+            return;
+        }
+
+        self.change(offset(pos, (0, -1, 0)), Blocks::Dirt.id(), 0, changed);
+        for y in pos.1..=top {
+            self.change((x, y, z), Blocks::Log.id(), wood, changed);
+        }
+        for y in top - 2..=top + 1 {
+            let radius = if y >= top { 1 } else { 2 };
+            for leaf_x in x - radius..=x + radius {
+                for leaf_z in z - radius..=z + radius {
+                    let corner = (leaf_x - x).abs() == radius && (leaf_z - z).abs() == radius;
+                    if corner && (y > top || self.random.one_in(2)) {
+                        continue;
+                    }
+                    if self.get_block((leaf_x, y, leaf_z)) == Some((Blocks::Air.id(), 0)) {
+                        self.change((leaf_x, y, leaf_z), Blocks::Leaves.id(), wood, changed);
+                    }
+                }
+            }
+        }
+    }
+}
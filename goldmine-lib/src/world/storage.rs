@@ -0,0 +1,372 @@
+//! The on-disk format of the Pi and PE 0.x clients: `level.dat`, `chunks.dat` and
+//! `entities.dat` in the world directory.
+
+use std::{
+    fs::{self, File},
+    io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use anyhow::{bail, Context, Result};
+
+use crate::nbt::{self, Compound, Endian};
+
+use super::{chunk::Chunk, World, CHUNK_WIDTH, WORLD_SIZE_CHUNKS};
+
+/// `level.dat` header version written by the Pi edition
+pub const LEVEL_STORAGE_VERSION: i32 = 3;
+const ENTITIES_MAGIC: &[u8; 4] = b"ENT\0";
+const ENTITIES_VERSION: i32 = 1;
+
+const SECTOR_SIZE: usize = 4096;
+/// `chunks.dat` has room for 32x32 chunks although only 16x16 are used
+const REGION_WIDTH: usize = 32;
+/// Length prefix, block ids, aux, sky light, block light and per column flags
+const CHUNK_RECORD_LEN: usize = 4 + 32768 + 3 * 16384 + 256;
+const CHUNK_SECTORS: usize = CHUNK_RECORD_LEN.div_ceil(SECTOR_SIZE);
+
+fn read_i32<R: Read>(reader: &mut R) -> Result<i32> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(i32::from_le_bytes(bytes))
+}
+
+/// Writes to a temporary file first, so a crash never leaves a half written file behind
+fn write_atomic(path: &Path, write: impl FnOnce(&mut BufWriter<File>) -> Result<()>) -> Result<()> {
+    let temp_path = path.with_extension("tmp");
+    let mut writer = BufWriter::new(File::create(&temp_path)?);
+    write(&mut writer)?;
+    writer.into_inner()?.sync_all()?;
+    fs::rename(temp_path, path)?;
+    Ok(())
+}
+
+/// Reads `level.dat`, an i32 storage version and length followed by the NBT root
+pub fn read_level(path: &Path) -> Result<Compound> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let version = read_i32(&mut reader)?;
+    if version > LEVEL_STORAGE_VERSION {
+        bail!("Unsupported level.dat storage version {}", version);
+    }
+    let _len = read_i32(&mut reader)?;
+    Ok(nbt::read_root(&mut reader, Endian::Little)?.1)
+}
+
+pub fn write_level(path: &Path, level: &Compound) -> Result<()> {
+    let mut root = Vec::new();
+    nbt::write_root("", level, &mut root, Endian::Little)?;
+    write_atomic(path, |writer| {
+        writer.write_all(&LEVEL_STORAGE_VERSION.to_le_bytes())?;
+        writer.write_all(&i32::try_from(root.len())?.to_le_bytes())?;
+        writer.write_all(&root)?;
+        Ok(())
+    })
+}
+
+/// Reads `entities.dat`, returning the root compound with the `Entities` and `TileEntities`
+/// lists
+pub fn read_entities(path: &Path) -> Result<Compound> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != ENTITIES_MAGIC {
+        bail!("{:?} is not an entities file", path);
+    }
+    let _version = read_i32(&mut reader)?;
+    let _len = read_i32(&mut reader)?;
+    Ok(nbt::read_root(&mut reader, Endian::Little)?.1)
+}
+
+pub fn write_entities(path: &Path, entities: &Compound) -> Result<()> {
+    let mut root = Vec::new();
+    nbt::write_root("", entities, &mut root, Endian::Little)?;
+    write_atomic(path, |writer| {
+        writer.write_all(ENTITIES_MAGIC)?;
+        writer.write_all(&ENTITIES_VERSION.to_le_bytes())?;
+        writer.write_all(&i32::try_from(root.len())?.to_le_bytes())?;
+        writer.write_all(&root)?;
+        Ok(())
+    })
+}
+
+/// The chunk records of `chunks.dat` without their length prefix, indexed like its location
+/// sector by `chunk_x + chunk_z * REGION_WIDTH`
+type ChunkRecords = Vec<Option<Vec<u8>>>;
+
+/// Encodes a chunk as stored in `chunks.dat`, without the length prefix
+fn encode_chunk(chunk: &Chunk) -> Vec<u8> {
+    let mut record = Vec::with_capacity(CHUNK_RECORD_LEN - 4);
+    record.extend_from_slice(chunk.blocks());
+    record.extend_from_slice(chunk.aux().as_bytes());
+    record.extend_from_slice(chunk.sky_light().as_bytes());
+    record.extend_from_slice(chunk.block_light().as_bytes());
+    record.extend_from_slice(&[0; CHUNK_WIDTH * CHUNK_WIDTH]);
+    record
+}
+
+fn decode_chunk(record: &[u8]) -> Result<Chunk> {
+    let mut reader = record;
+    let mut chunk = Chunk::new();
+    reader.read_exact(chunk.blocks_mut())?;
+    reader.read_exact(chunk.aux_mut().as_bytes_mut())?;
+    reader.read_exact(chunk.sky_light_mut().as_bytes_mut())?;
+    reader.read_exact(chunk.block_light_mut().as_bytes_mut())?;
+    Ok(chunk)
+}
+
+/// Reads the chunk records of `chunks.dat`. The file starts with a sector of chunk locations,
+/// the sector offset shifted by 8 bits plus the number of sectors.
+fn read_chunk_records(path: &Path) -> Result<ChunkRecords> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut locations = [0; REGION_WIDTH * REGION_WIDTH];
+    for location in locations.iter_mut() {
+        *location = read_i32(&mut reader)? as u32;
+    }
+
+    let mut records = vec![None; REGION_WIDTH * REGION_WIDTH];
+    for chunk_z in 0..WORLD_SIZE_CHUNKS {
+        for chunk_x in 0..WORLD_SIZE_CHUNKS {
+            let index = chunk_x + chunk_z * REGION_WIDTH;
+            let location = locations[index];
+            if location == 0 {
+                continue;
+            }
+            let offset = (location >> 8) as u64 * SECTOR_SIZE as u64;
+            reader.seek(SeekFrom::Start(offset))?;
+            let len = read_i32(&mut reader)?;
+            if usize::try_from(len).ok() != Some(CHUNK_RECORD_LEN) {
+                bail!(
+                    "Chunk {} {} has an invalid length {}",
+                    chunk_x,
+                    chunk_z,
+                    len
+                );
+            }
+            let mut record = vec![0; CHUNK_RECORD_LEN - 4];
+            reader.read_exact(&mut record)?;
+            records[index] = Some(record);
+        }
+    }
+    Ok(records)
+}
+
+/// Writes the chunk records in row order after the location sector
+fn write_chunk_records(path: &Path, records: &ChunkRecords) -> Result<()> {
+    write_atomic(path, |writer| {
+        let mut locations = vec![0u32; REGION_WIDTH * REGION_WIDTH];
+        let present = records
+            .iter()
+            .enumerate()
+            .filter(|(_, record)| record.is_some());
+        for (slot, (index, _)) in present.enumerate() {
+            let sector = 1 + slot * CHUNK_SECTORS;
+            locations[index] = ((sector as u32) << 8) | CHUNK_SECTORS as u32;
+        }
+        for location in locations {
+            writer.write_all(&location.to_le_bytes())?;
+        }
+
+        let padding = vec![0; CHUNK_SECTORS * SECTOR_SIZE - CHUNK_RECORD_LEN];
+        for record in records.iter().flatten() {
+            writer.write_all(&(CHUNK_RECORD_LEN as i32).to_le_bytes())?;
+            writer.write_all(record)?;
+            writer.write_all(&padding)?;
+        }
+        Ok(())
+    })
+}
+
+/// Reads all chunks of `chunks.dat` into the world
+pub fn read_chunks(path: &Path, world: &mut World) -> Result<()> {
+    for (index, record) in read_chunk_records(path)?.into_iter().enumerate() {
+        let Some(record) = record else {
+            continue;
+        };
+        let (chunk_x, chunk_z) = ((index % REGION_WIDTH) as i32, (index / REGION_WIDTH) as i32);
+        world
+            .set_chunk(chunk_x, chunk_z, decode_chunk(&record)?)
+            .with_context(|| format!("Loading chunk {} {}", chunk_x, chunk_z))?;
+    }
+    world.clear_dirty();
+    Ok(())
+}
+
+/// Chunks taken out of the world to be written without holding on to it
+#[derive(Debug, Default)]
+pub struct ChunkSave {
+    records: Vec<(usize, Vec<u8>)>,
+}
+
+impl ChunkSave {
+    /// Encodes the chunks changed since the last save and marks them as saved
+    pub fn take_dirty(world: &mut World) -> Self {
+        let records = world
+            .dirty_chunks()
+            .into_iter()
+            .filter_map(|(chunk_x, chunk_z)| {
+                let chunk = world.get_chunk(chunk_x, chunk_z)?;
+                let index = chunk_x as usize + chunk_z as usize * REGION_WIDTH;
+                Some((index, encode_chunk(chunk)))
+            })
+            .collect();
+        world.clear_dirty();
+        Self { records }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    /// The positions of the chunks, to mark them as changed again when writing fails
+    pub fn positions(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        self.records
+            .iter()
+            .map(|(index, _)| ((index % REGION_WIDTH) as i32, (index / REGION_WIDTH) as i32))
+    }
+
+    /// Replaces the chunks in `chunks.dat`, keeping the other chunks already in the file
+    pub fn write(&self, path: &Path) -> Result<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+        let mut records = if path.exists() {
+            read_chunk_records(path)?
+        } else {
+            vec![None; REGION_WIDTH * REGION_WIDTH]
+        };
+        for (index, record) in &self.records {
+            records[*index] = Some(record.clone());
+        }
+        write_chunk_records(path, &records)
+    }
+}
+
+/// Writes every existing chunk into `chunks.dat`
+pub fn write_chunks(path: &Path, world: &World) -> Result<()> {
+    let mut records = vec![None; REGION_WIDTH * REGION_WIDTH];
+    for (chunk_x, chunk_z) in world.chunk_positions() {
+        if let Some(chunk) = world.get_chunk(chunk_x, chunk_z) {
+            records[chunk_x as usize + chunk_z as usize * REGION_WIDTH] = Some(encode_chunk(chunk));
+        }
+    }
+    write_chunk_records(path, &records)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::nbt::Tag;
+
+    use super::*;
+
+    /// A fresh directory for a test's files
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("goldmine-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn test_chunk(seed: u8) -> Chunk {
+        let mut chunk = Chunk::new();
+        for x in 0..CHUNK_WIDTH {
+            for z in 0..CHUNK_WIDTH {
+                chunk.set_block(x, 0, z, 7, 0);
+                chunk.set_block(x, 1, z, seed, (x + z) as u8 % 16);
+                chunk.set_sky_light(x, 2, z, 15);
+                chunk.set_block_light(x, 1, z, (x as u8 + seed) % 16);
+            }
+        }
+        chunk
+    }
+
+    fn assert_same_chunk(a: &Chunk, b: &Chunk) {
+        assert_eq!(a.blocks(), b.blocks());
+        assert_eq!(a.aux().as_bytes(), b.aux().as_bytes());
+        assert_eq!(a.sky_light().as_bytes(), b.sky_light().as_bytes());
+        assert_eq!(a.block_light().as_bytes(), b.block_light().as_bytes());
+    }
+
+    #[test]
+    fn level_round_trip() {
+        let dir = test_dir("level");
+        let level = Compound::from([
+            ("LevelName".to_owned(), Tag::String("world".to_owned())),
+            ("RandomSeed".to_owned(), Tag::Long(42)),
+        ]);
+        write_level(&dir.join("level.dat"), &level).unwrap();
+        assert_eq!(read_level(&dir.join("level.dat")).unwrap(), level);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn entities_round_trip() {
+        let dir = test_dir("entities");
+        let entity = Compound::from([("id".to_owned(), Tag::Int(65))]);
+        let entities = Compound::from([
+            (
+                "Entities".to_owned(),
+                Tag::List(vec![Tag::Compound(entity)]),
+            ),
+            ("TileEntities".to_owned(), Tag::List(Vec::new())),
+        ]);
+        write_entities(&dir.join("entities.dat"), &entities).unwrap();
+        assert_eq!(read_entities(&dir.join("entities.dat")).unwrap(), entities);
+        assert!(read_entities(&dir.join("missing.dat")).is_err());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn chunks_round_trip() {
+        let dir = test_dir("chunks");
+        let path = dir.join("chunks.dat");
+        let mut world = World::new();
+        world.set_chunk(0, 0, test_chunk(1)).unwrap();
+        world.set_chunk(3, 5, test_chunk(2)).unwrap();
+        write_chunks(&path, &world).unwrap();
+
+        let mut read = World::new();
+        read_chunks(&path, &mut read).unwrap();
+        assert_eq!(
+            read.chunk_positions().collect::<Vec<_>>(),
+            world.chunk_positions().collect::<Vec<_>>()
+        );
+        assert!(read.dirty_chunks().is_empty());
+        for (chunk_x, chunk_z) in world.chunk_positions() {
+            assert_same_chunk(
+                read.get_chunk(chunk_x, chunk_z).unwrap(),
+                world.get_chunk(chunk_x, chunk_z).unwrap(),
+            );
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn chunk_save_keeps_other_chunks() {
+        let dir = test_dir("chunk-save");
+        let path = dir.join("chunks.dat");
+        let mut world = World::new();
+        world.set_chunk(0, 0, test_chunk(1)).unwrap();
+        world.set_chunk(1, 0, test_chunk(2)).unwrap();
+        ChunkSave::take_dirty(&mut world).write(&path).unwrap();
+        assert!(world.dirty_chunks().is_empty());
+
+        world.get_chunk_mut(1, 0).unwrap().set_block(4, 9, 4, 3, 0);
+        world.set_chunk(2, 2, test_chunk(3)).unwrap();
+        let save = ChunkSave::take_dirty(&mut world);
+        assert_eq!(save.positions().collect::<Vec<_>>(), [(1, 0), (2, 2)]);
+        save.write(&path).unwrap();
+
+        let mut read = World::new();
+        read_chunks(&path, &mut read).unwrap();
+        assert_eq!(read.chunk_positions().count(), 3);
+        for (chunk_x, chunk_z) in world.chunk_positions() {
+            assert_same_chunk(
+                read.get_chunk(chunk_x, chunk_z).unwrap(),
+                world.get_chunk(chunk_x, chunk_z).unwrap(),
+            );
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
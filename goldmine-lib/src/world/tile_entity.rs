@@ -0,0 +1,284 @@
+//! Tile entities, the extra data some blocks carry, stored by block position and saved in
+//! `entities.dat`
+
+use anyhow::{bail, Result};
+
+use crate::{
+    blocks::Blocks,
+    game_packets::ItemStack,
+    nbt::{Compound, Tag},
+};
+
+use super::{
+    furnace::{Furnace, FURNACE_SLOTS},
+    BlockPos, World,
+};
+
+/// Longest line fitting on a sign
+pub const SIGN_LINE_LENGTH: usize = 15;
+pub const CHEST_SLOTS: usize = 27;
+/// Most items a slot holds
+pub const MAX_STACK: u8 = 64;
+/// Item ids go up to 511, everything above is garbage from the client
+const MAX_ITEM_ID: u16 = 511;
+
+#[derive(Clone, Debug)]
+pub enum TileEntity {
+    Sign {
+        lines: [String; 4],
+    },
+    Chest {
+        items: Vec<ItemStack>,
+    },
+    Furnace(Furnace),
+    /// A tile entity goldmine doesn't use, kept so it survives a save
+    Other(Compound),
+}
+
+impl TileEntity {
+    /// Reads a tile entity in the Pi format, returning it with its position
+    pub fn from_nbt(nbt: Compound) -> Option<(BlockPos, Self)> {
+        let int = |key: &str| nbt.get(key)?.as_i64().map(|value| value as i32);
+        let pos = (int("x")?, int("y")?, int("z")?);
+        let tile_entity = match nbt.get("id")?.as_str()? {
+            "Sign" => {
+                let line = |index: usize| {
+                    nbt.get(&format!("Text{}", index + 1))
+                        .and_then(Tag::as_str)
+                        .unwrap_or_default()
+                        .to_owned()
+                };
+                TileEntity::Sign {
+                    lines: [line(0), line(1), line(2), line(3)],
+                }
+            }
+            "Chest" => TileEntity::Chest {
+                items: read_items(&nbt, CHEST_SLOTS),
+            },
+            "Furnace" => {
+                let short = |key: &str| {
+                    nbt.get(key)
+                        .and_then(Tag::as_i64)
+                        .map_or(0, |value| value.max(0) as u16)
+                };
+                TileEntity::Furnace(Furnace {
+                    items: read_items(&nbt, FURNACE_SLOTS),
+                    burn_time: short("BurnTime"),
+                    max_burn_time: short("MaxTime"),
+                    cook_time: short("CookTime"),
+                })
+            }
+            _ => TileEntity::Other(nbt),
+        };
+        Some((pos, tile_entity))
+    }
+
+    pub fn to_nbt(&self, (x, y, z): BlockPos) -> Compound {
+        let mut nbt = match self {
+            TileEntity::Sign { lines } => {
+                let mut nbt = Compound::from([("id".to_owned(), Tag::String("Sign".to_owned()))]);
+                for (index, line) in lines.iter().enumerate() {
+                    nbt.insert(format!("Text{}", index + 1), Tag::String(line.clone()));
+                }
+                nbt
+            }
+            TileEntity::Chest { items } => Compound::from([
+                ("id".to_owned(), Tag::String("Chest".to_owned())),
+                ("Items".to_owned(), write_items(items)),
+            ]),
+            TileEntity::Furnace(furnace) => Compound::from([
+                ("id".to_owned(), Tag::String("Furnace".to_owned())),
+                ("Items".to_owned(), write_items(&furnace.items)),
+                ("BurnTime".to_owned(), Tag::Short(furnace.burn_time as i16)),
+                (
+                    "MaxTime".to_owned(),
+                    Tag::Short(furnace.max_burn_time as i16),
+                ),
+                ("CookTime".to_owned(), Tag::Short(furnace.cook_time as i16)),
+            ]),
+            TileEntity::Other(nbt) => nbt.clone(),
+        };
+        nbt.insert("x".to_owned(), Tag::Int(x));
+        nbt.insert("y".to_owned(), Tag::Int(y));
+        nbt.insert("z".to_owned(), Tag::Int(z));
+        nbt
+    }
+
+    /// Whether the tile entity belongs to a block id, it is removed when the block changes to
+    /// one it doesn't belong to
+    pub fn belongs_to(&self, id: u8) -> bool {
+        match self {
+            TileEntity::Sign { .. } => {
+                matches!(
+                    Blocks::from_id(id),
+                    Some(Blocks::SignPost | Blocks::WallSign)
+                )
+            }
+            TileEntity::Chest { .. } => id == Blocks::Chest.id(),
+            TileEntity::Furnace(_) => {
+                matches!(
+                    Blocks::from_id(id),
+                    Some(Blocks::Furnace | Blocks::LitFurnace)
+                )
+            }
+            TileEntity::Other(_) => id != Blocks::Air.id(),
+        }
+    }
+
+    /// An empty tile entity for a block id, `None` for blocks without one
+    pub fn for_block(id: u8) -> Option<Self> {
+        match Blocks::from_id(id)? {
+            Blocks::SignPost | Blocks::WallSign => Some(TileEntity::Sign {
+                lines: Default::default(),
+            }),
+            Blocks::Chest => Some(TileEntity::Chest {
+                items: vec![ItemStack::default(); CHEST_SLOTS],
+            }),
+            Blocks::Furnace | Blocks::LitFurnace => Some(TileEntity::Furnace(Furnace::default())),
+            _ => None,
+        }
+    }
+
+    /// The text of a sign
+    pub fn lines(&self) -> Option<&[String; 4]> {
+        match self {
+            TileEntity::Sign { lines } => Some(lines),
+            _ => None,
+        }
+    }
+
+    /// The slots of a container
+    pub fn items(&self) -> Option<&[ItemStack]> {
+        match self {
+            TileEntity::Chest { items } => Some(items),
+            TileEntity::Furnace(furnace) => Some(&furnace.items),
+            _ => None,
+        }
+    }
+
+    pub fn items_mut(&mut self) -> Option<&mut [ItemStack]> {
+        match self {
+            TileEntity::Chest { items } => Some(items),
+            TileEntity::Furnace(furnace) => Some(&mut furnace.items),
+            _ => None,
+        }
+    }
+
+    /// The window type of a container, as the client knows it
+    pub fn window_type(&self) -> Option<u8> {
+        match self {
+            TileEntity::Chest { .. } => Some(0),
+            TileEntity::Furnace(_) => Some(2),
+            _ => None,
+        }
+    }
+
+    /// The window properties of a container, pairs of property and value
+    pub fn window_data(&self) -> Vec<(u16, u16)> {
+        match self {
+            TileEntity::Furnace(furnace) => furnace.window_data(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Reads the `Items` list of a container into `slots` slots, items in other slots are dropped
+fn read_items(nbt: &Compound, slots: usize) -> Vec<ItemStack> {
+    let mut items = vec![ItemStack::default(); slots];
+    for item in nbt
+        .get("Items")
+        .and_then(Tag::as_list)
+        .unwrap_or_default()
+        .iter()
+        .filter_map(Tag::as_compound)
+    {
+        let int = |key: &str| item.get(key).and_then(Tag::as_i64).unwrap_or_default();
+        if let Some(slot) = items.get_mut(int("Slot") as usize) {
+            *slot = ItemStack {
+                id: int("id") as u16,
+                count: int("Count") as u8,
+                aux: int("Damage") as u16,
+            };
+        }
+    }
+    items
+}
+
+/// The non-empty slots of a container as an `Items` list
+fn write_items(items: &[ItemStack]) -> Tag {
+    Tag::List(
+        items
+            .iter()
+            .enumerate()
+            .filter(|(_, item)| !item.is_empty())
+            .map(|(slot, item)| {
+                Tag::Compound(Compound::from([
+                    ("Slot".to_owned(), Tag::Byte(slot as i8)),
+                    ("id".to_owned(), Tag::Short(item.id as i16)),
+                    ("Count".to_owned(), Tag::Byte(item.count as i8)),
+                    ("Damage".to_owned(), Tag::Short(item.aux as i16)),
+                ]))
+            })
+            .collect(),
+    )
+}
+
+/// Items the client put into a slot must be a single stack of a known id
+pub fn validate_item(item: &ItemStack) -> Result<()> {
+    if item.count > MAX_STACK {
+        bail!("Stack of {} items is too big", item.count);
+    }
+    if item.id > MAX_ITEM_ID {
+        bail!("Unknown item id {}", item.id);
+    }
+    Ok(())
+}
+
+/// Sign lines are limited to what the client can draw: printable ASCII that fits on the sign
+pub fn validate_sign_line(line: &str) -> Result<()> {
+    if line.len() > SIGN_LINE_LENGTH {
+        bail!(
+            "Sign line {:?} is longer than {} characters",
+            line,
+            SIGN_LINE_LENGTH
+        );
+    }
+    if let Some(invalid) = line.chars().find(|char| !matches!(char, ' '..='~')) {
+        bail!("Sign line {:?} contains {:?}", line, invalid);
+    }
+    Ok(())
+}
+
+impl World {
+    pub fn get_tile_entity(&self, pos: BlockPos) -> Option<&TileEntity> {
+        self.tile_entities.get(&pos)
+    }
+
+    pub fn get_tile_entity_mut(&mut self, pos: BlockPos) -> Option<&mut TileEntity> {
+        self.tile_entities.get_mut(&pos)
+    }
+
+    pub fn set_tile_entity(&mut self, pos: BlockPos, tile_entity: TileEntity) {
+        self.tile_entities.insert(pos, tile_entity);
+    }
+
+    pub fn remove_tile_entity(&mut self, pos: BlockPos) -> Option<TileEntity> {
+        self.tile_entities.remove(&pos)
+    }
+
+    pub fn tile_entities(&self) -> impl Iterator<Item = (BlockPos, &TileEntity)> + '_ {
+        self.tile_entities
+            .iter()
+            .map(|(pos, tile_entity)| (*pos, tile_entity))
+    }
+
+    /// The tile entities inside of a chunk column
+    pub fn chunk_tile_entities(
+        &self,
+        chunk_x: i32,
+        chunk_z: i32,
+    ) -> impl Iterator<Item = (BlockPos, &TileEntity)> + '_ {
+        self.tile_entities()
+            .filter(move |((x, _, z), _)| x >> 4 == chunk_x && z >> 4 == chunk_z)
+    }
+}
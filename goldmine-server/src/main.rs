@@ -1,10 +1,16 @@
 use anyhow::Result;
 
-use goldmine_lib::Server;
+use goldmine_lib::{config::ServerConfig, Server};
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let mut server = Server::new("0.0.0.0:19132", "example_mod/mod.lua")?;
-    server.execute().await?;
+    let config = ServerConfig::load("config.json")?;
+    let server = Server::with_config(config)?;
+    let mut running = server.clone();
+    tokio::select! {
+        result = running.execute() => result?,
+        _ = tokio::signal::ctrl_c() => println!("Shutting down"),
+    }
+    server.save_world().await?;
     Ok(())
 }
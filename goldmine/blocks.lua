@@ -0,0 +1,39 @@
+export type Drops = {
+    type: "nothing" | "itself" | "item" | "chance",
+    id: number?,
+    aux: number?,
+    aux_mask: number?,
+    min: number?,
+    max: number?,
+    one_in: number?
+}
+
+export type Block = {
+    id: number,
+    name: string,
+    solid: boolean,
+    transparent: boolean,
+    light_emission: number,
+    light_opacity: number,
+    hardness: number,
+    blast_resistance: number,
+    drops: Drops,
+    max_aux: number,
+    replaceable: boolean
+}
+
+local blocks: {
+    ids: {[string]: number},
+    get: (block: number | string) -> Block?,
+    all: () -> {Block}
+} = {
+    ids = {},
+    get = function(block: number | string): Block?
+        return nil
+    end,
+    all = function(): {Block}
+        return {}
+    end
+}
+
+return blocks
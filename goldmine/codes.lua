@@ -0,0 +1,19 @@
+export type CodeTable = {[string]: number}
+
+local codes: {
+    InteractAction: CodeTable,
+    EntityEventKind: CodeTable,
+    PlayerActionKind: CodeTable,
+    AnimateAction: CodeTable,
+    LevelEventKind: CodeTable,
+    BlockFace: CodeTable
+} = {
+    InteractAction = {},
+    EntityEventKind = {},
+    PlayerActionKind = {},
+    AnimateAction = {},
+    LevelEventKind = {},
+    BlockFace = {}
+}
+
+return codes
@@ -3,7 +3,25 @@ local gm_module = {}
 local registry = require("@goldmine/registry")
 gm_module.registry = registry
 
+local codes = require("@goldmine/codes")
+gm_module.codes = codes
+
+local packets = require("@goldmine/packets")
+gm_module.packets = packets
+
+local blocks = require("@goldmine/blocks")
+gm_module.blocks = blocks
+
+local time = require("@goldmine/time")
+gm_module.time = time
+
+local regions = require("@goldmine/regions")
+gm_module.regions = regions
+
 export type Mod = {name: string, version: number}
 function gm_module.register_mod(mod: Mod): () end
 
+function gm_module.send(connection_id: number, packet: packets.GamePacket): () end
+function gm_module.connections(): {number} return {} end
+
 return gm_module
\ No newline at end of file
@@ -0,0 +1,18 @@
+export type GamePacket = {
+    kind: string,
+    toString: (self: GamePacket) -> string,
+    fields: (self: GamePacket) -> {[string]: any},
+    [string]: any
+}
+
+local packets: {
+    new: (kind: string, fields: {[string]: any}?) -> GamePacket,
+    kinds: {string}
+} = {
+    new = function(kind: string, fields: {[string]: any}?): GamePacket
+        return (nil :: any)
+    end,
+    kinds = {}
+}
+
+return packets
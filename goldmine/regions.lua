@@ -0,0 +1,43 @@
+export type Position = {number}
+
+export type Region = {
+    name: string,
+    min: Position,
+    max: Position,
+    owner: string,
+    members: {string}
+}
+
+local regions: {
+    get: (name: string) -> Region?,
+    all: () -> {Region},
+    at: (pos: Position) -> {Region},
+    create: (name: string, first: Position, second: Position, owner: string) -> (),
+    remove: (name: string) -> boolean,
+    add_member: (name: string, username: string) -> (),
+    remove_member: (name: string, username: string) -> (),
+    set_owner: (name: string, username: string) -> (),
+    can_edit: (username: string, pos: Position) -> boolean
+} = {
+    get = function(name: string): Region?
+        return nil
+    end,
+    all = function(): {Region}
+        return {}
+    end,
+    at = function(pos: Position): {Region}
+        return {}
+    end,
+    create = function(name: string, first: Position, second: Position, owner: string): () end,
+    remove = function(name: string): boolean
+        return false
+    end,
+    add_member = function(name: string, username: string): () end,
+    remove_member = function(name: string, username: string): () end,
+    set_owner = function(name: string, username: string): () end,
+    can_edit = function(username: string, pos: Position): boolean
+        return false
+    end
+}
+
+return regions
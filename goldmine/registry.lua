@@ -1,11 +1,24 @@
+local packets = require("@goldmine/packets")
+local world = require("@goldmine/world")
+
 type internal_Registry = {
     pl_registry: Registry?,
+    gpl_registry: GamePacketListenerRegistry?,
     api_registry: Registry?,
-    lm_registry: Registry?
+    lm_registry: Registry?,
+    wg_registry: WorldGeneratorRegistry?,
+    rt_registry: RandomTickRegistry?
 }
 
 local registry: internal_Registry = {}
 
 export type Registry = {register: (string, any) -> (), get: (string) -> any, values: () -> {}}
 
-return registry
\ No newline at end of file
+export type GamePacketListener = (packet: packets.GamePacket, is_inbound: boolean, connection_id: number) -> (packets.GamePacket | {packets.GamePacket} | nil)
+export type GamePacketListenerRegistry = {register: (string, {string}?, GamePacketListener) -> ()}
+
+export type WorldGeneratorRegistry = {register: (string, world.WorldGenerator) -> (), get: (string) -> world.WorldGenerator?, values: () -> {world.WorldGenerator}}
+
+export type RandomTickRegistry = {register: (string, {number}, world.RandomTickHandler) -> ()}
+
+return registry
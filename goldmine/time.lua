@@ -0,0 +1,21 @@
+export type TimeName = "sunrise" | "day" | "noon" | "sunset" | "night" | "midnight"
+
+local time: {
+    DAY_LENGTH: number,
+    get: () -> number,
+    set: (time: number | TimeName) -> (),
+    is_frozen: () -> boolean,
+    freeze: (frozen: boolean?) -> ()
+} = {
+    DAY_LENGTH = 19200,
+    get = function(): number
+        return 0
+    end,
+    set = function(time: number | TimeName): () end,
+    is_frozen = function(): boolean
+        return false
+    end,
+    freeze = function(frozen: boolean?): () end
+}
+
+return time
@@ -0,0 +1,12 @@
+export type Chunk = {
+    get_block: (self: Chunk, x: number, y: number, z: number) -> (number, number),
+    set_block: (self: Chunk, x: number, y: number, z: number, id: number, aux: number?) -> (),
+    get_height: (self: Chunk, x: number, z: number) -> number?
+}
+
+export type WorldGenerator = (chunk: Chunk, chunk_x: number, chunk_z: number, seed: number) -> ()
+
+-- Returns nothing to keep the block or the id and aux of the new block
+export type RandomTickHandler = (x: number, y: number, z: number, id: number, aux: number) -> (number?, number?)
+
+return {}